@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use surrealdb::sql::Value;
+
+use crate::{db::{first_result, found_item, Item, DB}, prelude::{Error, W}, query::item_thing, utils::macros::map};
+
+/// Reads the `barcodes` field of an item record, which items created before barcodes existed don't have.
+pub fn barcodes_of(value: Option<&Value>) -> Result<Vec<String>, Error> {
+    match value {
+        Some(Value::Array(codes)) => codes.iter().map(|code| W(code.clone()).try_into()).collect(),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Checks a scanned or typed code is a UPC or EAN (GTIN-8, 12, 13 or 14) with a correct check digit.
+/// Spaces and dashes are dropped, so codes copied off a label as printed are accepted.
+pub fn normalize_barcode(code: &str) -> Result<String, Error> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    if !code.chars().all(|c| c.is_ascii_digit()) || ![8, 12, 13, 14].contains(&code.len()) {
+        return Err(Error::Validation(format!("'{}' is not a UPC or EAN, expected 8, 12, 13 or 14 digits", code)));
+    }
+    let digits: Vec<u32> = code.chars().filter_map(|c| c.to_digit(10)).collect();
+    let (check, body) = digits.split_last().unwrap_or((&0, &[]));
+    // Weights alternate 3, 1, ... starting from the digit next to the check digit
+    let sum: u32 = body.iter().rev().enumerate().map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { *digit }).sum();
+    if (10 - sum % 10) % 10 != *check {
+        return Err(Error::Validation(format!("'{}' has the wrong check digit", code)));
+    }
+    Ok(code)
+}
+
+impl DB {
+    /// Finds the item a barcode belongs to.
+    pub async fn item_by_barcode(&self, code: &str) -> Result<Item, Error> {
+        let code = normalize_barcode(code)?;
+        let sql = "SELECT * FROM items WHERE barcodes CONTAINS $code";
+        let vars: BTreeMap<String, Value> = map!("code".into() => Value::from(code.as_str()));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No item with barcode {}", code))),
+            value => W(value).try_into(),
+        }
+    }
+
+    /// Attaches a barcode to item `id`. A barcode belongs to one item only.
+    pub async fn add_barcode(&self, id: &str, code: &str) -> Result<Item, Error> {
+        let code = normalize_barcode(code)?;
+        let th = item_thing(id)?;
+        match self.item_by_barcode(&code).await {
+            Ok(owner) if owner.id.as_deref() == Some(th.to_string().as_str()) => return Ok(owner),
+            Ok(owner) => return Err(Error::Conflict(format!("Barcode {} already belongs to {}", code, owner.name))),
+            Err(Error::NotFound(_)) => {},
+            Err(e) => return Err(e),
+        }
+        let sql = "UPDATE items SET barcodes = array::union(barcodes ?? [], [$code]) WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => th.into(),
+            "code".into() => Value::from(code.as_str())
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_item(res, id)
+    }
+
+    /// Detaches a barcode from item `id`, if it had it.
+    pub async fn remove_barcode(&self, id: &str, code: &str) -> Result<Item, Error> {
+        let code = normalize_barcode(code)?;
+        let sql = "UPDATE items SET barcodes = array::complement(barcodes ?? [], [$code]) WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => item_thing(id)?.into(),
+            "code".into() => Value::from(code.as_str())
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_item(res, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_codes() {
+        assert_eq!(normalize_barcode("96385074").unwrap(), "96385074");
+        assert_eq!(normalize_barcode("036000291452").unwrap(), "036000291452");
+        assert_eq!(normalize_barcode("4006381333931").unwrap(), "4006381333931");
+        assert_eq!(normalize_barcode("10614141000415").unwrap(), "10614141000415");
+    }
+
+    #[test]
+    fn drops_spaces_and_dashes() {
+        assert_eq!(normalize_barcode(" 0-36000-29145-2 ").unwrap(), "036000291452");
+        assert_eq!(normalize_barcode("4 006381 333931").unwrap(), "4006381333931");
+    }
+
+    #[test]
+    fn rejects_wrong_check_digits() {
+        for code in ["96385075", "036000291453", "4006381333932"] {
+            assert!(matches!(normalize_barcode(code), Err(Error::Validation(_))), "{code}");
+        }
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_code() {
+        for code in ["", "9638507", "40063813339310", "403638133393100", "03600029145A", "ean-13"] {
+            assert!(matches!(normalize_barcode(code), Err(Error::Validation(_))), "{code}");
+        }
+    }
+}
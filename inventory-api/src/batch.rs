@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Thing, Value};
+
+use crate::{
+    config::OverConsumption, db::{all_results, AffectedRows, Item, DB}, ledger::{recorded, Operation, Source}, locations::{default_location, level_change, level_statements, location_thing, total, Levels, Transfer}, lots::{clear_empty_lots, draw_lots, new_lot, HeldLot}, packages::find_package, prelude::{Error, W}, purchases::{above_usual, cents, entry_price, purchase_statement}, query::{item_thing, Query}, units, RestockItem
+};
+
+/// An entry of a batch that was applied, with the item's stock afterwards.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppliedItem {
+    pub id: String,
+    pub name: String,
+    pub stock: f64,
+}
+
+/// An entry of a batch that was never attempted, or that the stock policy refused.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectedEntry {
+    pub id: String,
+    pub reason: String,
+}
+
+/// A consumption that asked for more than was in stock and was cut down to what was there.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClampedEntry {
+    pub id: String,
+    pub requested: f64,
+    pub consumed: f64,
+}
+
+/// A restock that cost notably more than the item usually does, both per one of the item's `unit`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AboveUsual {
+    pub id: String,
+    pub unit: String,
+    pub unit_price: f64,
+    pub usual: f64,
+}
+
+/// A budget a restock put over for the month, with what's been spent against it since.
+/// `category_name` is `None` for the overall budget.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverBudget {
+    pub budget: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_name: Option<String>,
+    pub amount: f64,
+    pub spent: f64,
+}
+
+/// What happened to each entry of a batch restock, consume or update.
+/// `rows_affected` is kept so clients expecting [`AffectedRows`] keep working.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub rows_affected: usize,
+    pub applied: Vec<AppliedItem>,
+    pub not_found: Vec<String>,
+    pub rejected: Vec<RejectedEntry>,
+    pub clamped: Vec<ClampedEntry>,
+    #[serde(default)]
+    pub above_usual: Vec<AboveUsual>,
+    #[serde(default)]
+    pub over_budget: Vec<OverBudget>,
+}
+
+impl BatchResult {
+    fn apply(&mut self, item: &Item) {
+        self.applied.push(AppliedItem {
+            id: item.id.clone().unwrap_or_default(),
+            name: item.name.clone(),
+            stock: item.stock
+        });
+        self.rows_affected = self.applied.len();
+    }
+
+    fn reject(&mut self, id: &str, reason: impl Into<String>) {
+        self.rejected.push(RejectedEntry { id: id.to_owned(), reason: reason.into() });
+    }
+
+    /// Turns the result of a one entry batch back into an error for the single item endpoints.
+    pub fn into_single(self, id: &str) -> Result<AffectedRows, Error> {
+        if let Some(rejected) = self.rejected.into_iter().next() {
+            return Err(Error::Validation(rejected.reason));
+        }
+        if !self.not_found.is_empty() {
+            return Err(Error::NotFound(format!("No item with id {}", id)));
+        }
+        Ok(AffectedRows { rows_affected: self.applied.len() })
+    }
+}
+
+/// A transfer as planned: how much moves, from and to where, and the lots it takes along.
+type PlannedMove = (f64, Thing, Thing, Vec<(HeldLot, f64)>);
+
+/// Parses every id in a batch up front. Entries with a bad id are rejected and never make it into the transaction.
+fn partition_batch<'a, T>(data: &'a [T], id: impl Fn(&T) -> Option<&str>, result: &mut BatchResult) -> Vec<(Thing, &'a T)> {
+    let mut valid = vec![];
+    for entry in data {
+        match id(entry) {
+            Some(id) => match item_thing(id) {
+                Ok(th) => valid.push((th, entry)),
+                Err(e) => result.reject(id, e.to_string()),
+            },
+            None => result.reject("", "Entry has no id"),
+        }
+    }
+    valid
+}
+
+/// Sums up entries for the same item so each item is only touched once per transaction.
+/// Restocks of one item with different best-by dates stay apart, as they make separate lots.
+fn merge_duplicates(data: Vec<(Thing, RestockItem)>) -> Vec<(Thing, RestockItem)> {
+    let mut merged: Vec<(Thing, RestockItem)> = vec![];
+    for (th, item) in data {
+        match merged.iter_mut().find(|(m, other)| *m == th && other.best_by == item.best_by) {
+            Some((_, m)) => m.count = units::round(m.count + item.count),
+            None => merged.push((th, item)),
+        }
+    }
+    merged
+}
+
+/// Converts each entry's count into the unit its item is kept in, going by the levels read before the batch.
+/// A count of packages is multiplied by the package's size, and a unit price becomes the price of the whole
+/// entry. Entries given in a unit that doesn't convert into the item's, in a package the item doesn't have,
+/// or with a price that makes no sense are rejected.
+fn in_item_units(entries: Vec<(Thing, &RestockItem)>, levels: &BTreeMap<String, Levels>, result: &mut BatchResult) -> Vec<(Thing, RestockItem)> {
+    let mut converted = vec![];
+    for (th, item) in entries {
+        let levels = &levels[&th.to_string()];
+        let count = match (&item.unit, &item.package) {
+            (Some(_), Some(_)) => Err(Error::Validation("Give either a unit or a package, not both".into())),
+            (Some(unit), None) => units::convert(item.count, unit, &levels.unit),
+            (None, Some(package)) => find_package(&levels.packages, package).map(|package| units::round(item.count * package.size)),
+            (None, None) => Ok(units::round(item.count)),
+        };
+        match count.and_then(|count| Ok((count, entry_price(item)?))) {
+            Ok((count, price)) => converted.push((th, RestockItem {
+                id: item.id.clone(),
+                count,
+                unit: None,
+                package: None,
+                best_by: item.best_by,
+                price,
+                unit_price: None,
+                store: item.store.clone()
+            })),
+            Err(e) => result.reject(&item.id, e.to_string()),
+        }
+    }
+    converted
+}
+
+/// Splits off entries bought at a store that doesn't exist.
+fn known_stores(entries: Vec<(Thing, RestockItem)>, stores: &BTreeMap<String, (Thing, String)>, result: &mut BatchResult) -> Vec<(Thing, RestockItem)> {
+    entries.into_iter().filter(|(_, item)| match item.store.as_ref() {
+        Some(store) if !stores.contains_key(store) => {
+            result.reject(&item.id, format!("No store with id {}", store));
+            false
+        },
+        _ => true,
+    }).collect()
+}
+
+/// Splits off entries for items that don't exist, going by the levels read before the batch.
+fn existing<'a, T>(entries: Vec<(Thing, &'a T)>, levels: &BTreeMap<String, Levels>, result: &mut BatchResult) -> Vec<(Thing, &'a T)> {
+    entries.into_iter().filter(|(th, _)| {
+        if !levels.contains_key(&th.to_string()) {
+            result.not_found.push(th.to_string());
+            return false;
+        }
+        true
+    }).collect()
+}
+
+/// Splits off entries with a count of zero or less, which make no sense to restock or consume.
+/// Runs on each entry as it was sent, before [`merge_duplicates`] could net a negative count into another.
+fn positive_counts(entries: Vec<(Thing, RestockItem)>, result: &mut BatchResult) -> Vec<(Thing, RestockItem)> {
+    entries.into_iter().filter(|(_, item)| {
+        if !(units::valid_quantity(item.count) && item.count > 0.0) {
+            result.reject(&item.id, format!("Count must be a positive number, got {}", item.count));
+            return false;
+        }
+        true
+    }).collect()
+}
+
+impl DB {
+    /// Runs `statement` once per entry of a batch in a single transaction and returns the updated items by id.
+    /// Statements changing stock should go through [`recorded`] so the change makes it into the ledger.
+    async fn run_batch<T>(
+        &self,
+        entries: &[(Thing, &T)],
+        statement: impl Fn(&mut Query, &Thing, String, &T) -> String
+    ) -> Result<BTreeMap<String, Item>, Error> {
+        let mut updated = BTreeMap::new();
+        if entries.is_empty() {
+            return Ok(updated);
+        }
+        let mut query = Query::transaction();
+        for (item, entry) in entries {
+            let th = query.bind(item.clone());
+            let sql = statement(&mut query, item, th, entry);
+            query.push(sql);
+        }
+        let (sql, vars) = query.commit();
+        for value in all_results(self.execute(&sql, Some(vars)).await?)? {
+            // Only the item updates return records, the ledger and stock level statements return nothing
+            let Value::Array(array) = value else { continue };
+            for record in array.into_iter().filter(|record| matches!(record, Value::Object(_))) {
+                let item: Item = W(record).try_into()?;
+                updated.insert(item.id.clone().unwrap_or_default(), item);
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Sorts the updated items of a batch into applied and, for items deleted while it ran, not found.
+    fn classify<T>(entries: Vec<(Thing, &T)>, updated: &BTreeMap<String, Item>, result: &mut BatchResult) {
+        for (th, _) in entries {
+            match updated.get(&th.to_string()) {
+                Some(item) => result.apply(item),
+                None => result.not_found.push(th.to_string()),
+            }
+        }
+    }
+
+    /// Reads the levels of every item in a restock or consume batch, then puts the entries for items that
+    /// exist into each item's unit. They still need [`positive_counts`], then [`merge_duplicates`] to make one per item and best-by date.
+    async fn prepare_counts(&self, data: &[RestockItem], result: &mut BatchResult) -> Result<(BTreeMap<String, Levels>, Vec<(Thing, RestockItem)>), Error> {
+        let entries = partition_batch(data, |item| Some(item.id.as_str()), result);
+        let levels = self.levels(entries.iter().map(|(th, _)| th.clone()).collect()).await?;
+        let entries = existing(entries, &levels, result);
+        let entries = in_item_units(entries, &levels, result);
+        Ok((levels, entries))
+    }
+
+    /// Adds stock at `location`, which must exist, as a new lot per entry. Counts may be in any unit that converts into the item's.
+    /// Entries with a price are recorded as purchases in the same transaction.
+    pub async fn restock_items(&self, data: Vec<RestockItem>, location: &Thing, source: &Source) -> Result<BatchResult, Error> {
+        let mut result = BatchResult::default();
+        let stores = self.purchase_stores(&data).await?;
+        let (levels, converted) = self.prepare_counts(&data, &mut result).await?;
+        let converted = positive_counts(known_stores(converted, &stores, &mut result), &mut result);
+        let data = merge_duplicates(converted.clone());
+        let entries: Vec<(Thing, &RestockItem)> = data.iter().map(|(th, item)| (th.clone(), item)).collect();
+        // Purchases are kept per entry, the way they were paid for, and go in with the merged entry they're part of
+        let purchases: Vec<&(Thing, RestockItem)> = converted.iter().filter(|(_, item)| item.price.is_some()).collect();
+        // Taken before the batch, so a dear restock doesn't raise the price it's compared with
+        let paid: BTreeMap<String, String> = purchases.iter()
+            .map(|(th, _)| (th.to_string(), levels[&th.to_string()].unit.clone()))
+            .collect();
+        let usual = self.usual_prices(&paid).await?;
+
+        let updated = self.run_batch(&entries, |query, item, th, entry| {
+            let levels = &levels[&item.to_string()];
+            let changes = level_statements(query, item, &th, levels, &[(location.clone(), entry.count)], entry.best_by);
+            let update = format!("{changes} UPDATE items SET stock = {}, last_updated = time::now() WHERE id = {th}", total(&th));
+            let mut statements = recorded(query, &th, &update, Operation::Restock, source);
+            for (_, purchase) in purchases.iter().filter(|(other, purchase)| other == item && purchase.best_by == entry.best_by) {
+                let store = purchase.store.as_ref().and_then(|store| stores.get(store));
+                statements += &purchase_statement(query, &th, purchase, &levels.unit, store);
+            }
+            statements
+        }).await?;
+
+        Self::classify(entries, &updated, &mut result);
+        for (th, purchase) in purchases.iter() {
+            let id = th.to_string();
+            let (Some(usual), Some(price)) = (usual.get(&id), purchase.price) else { continue };
+            let unit_price = price / purchase.count;
+            if above_usual(unit_price, *usual) && updated.contains_key(&id) {
+                result.above_usual.push(AboveUsual { unit: levels[&id].unit.clone(), id, unit_price: cents(unit_price), usual: cents(*usual) });
+            }
+        }
+        let mut paid: BTreeMap<&str, f64> = BTreeMap::new();
+        for (th, purchase) in purchases.iter() {
+            let (Some(item), Some(price)) = (updated.get(&th.to_string()), purchase.price) else { continue };
+            *paid.entry(item.category.as_str()).or_default() += price;
+        }
+        if !paid.is_empty() {
+            // The restock is in either way, so budgets that can't be checked are left out rather than failing it
+            match self.put_over_budget(&paid).await {
+                Ok(over) => result.over_budget = over,
+                Err(e) => warn!(target: "database", "Could not check budgets after a restock: {}", e),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Consumes every entry in one transaction, from `location` or, without one, from wherever the item is kept.
+    /// Either way the lot expiring soonest is used up first. Stock never drops below zero: depending on `policy`,
+    /// asking for more than is there either rejects that entry or consumes only what is there.
+    pub async fn consume_items(&self, mut data: Vec<RestockItem>, location: Option<&Thing>, policy: OverConsumption, source: &Source) -> Result<BatchResult, Error> {
+        let mut result = BatchResult::default();
+        // Consumption picks its own lots and isn't paid for, so a best-by date or price given with it means nothing
+        data.iter_mut().for_each(|item| {
+            item.best_by = None;
+            item.price = None;
+            item.unit_price = None;
+            item.store = None;
+        });
+        let (levels, data) = self.prepare_counts(&data, &mut result).await?;
+        let data = merge_duplicates(positive_counts(data, &mut result));
+        let entries: Vec<(Thing, &RestockItem)> = data.iter().map(|(th, item)| (th.clone(), item)).collect();
+
+        let mut plans = BTreeMap::new();
+        let entries: Vec<_> = entries.into_iter().filter(|(th, entry)| {
+            let id = th.to_string();
+            let item = &levels[&id];
+            let (available, plan) = match location {
+                Some(location) => {
+                    let available = item.at(location);
+                    (available, vec![(location.clone(), -available.min(entry.count))])
+                },
+                None => (item.stock, item.plan_consumption(entry.count)),
+            };
+            if available < entry.count {
+                if policy == OverConsumption::Reject {
+                    result.reject(&id, format!("Only {} {} in stock, cannot consume {}", available, item.unit, entry.count));
+                    return false;
+                }
+                result.clamped.push(ClampedEntry { id: id.clone(), requested: entry.count, consumed: available });
+            }
+            plans.insert(id, plan);
+            true
+        }).collect();
+
+        let updated = self.run_batch(&entries, |query, item, th, _| {
+            let changes = level_statements(query, item, &th, &levels[&item.to_string()], &plans[&item.to_string()], None);
+            let update = format!("{changes} UPDATE items SET stock = {}, last_updated = time::now() WHERE id = {th}", total(&th));
+            recorded(query, &th, &update, Operation::Consume, source)
+        }).await?;
+
+        Self::classify(entries, &updated, &mut result);
+        Ok(result)
+    }
+
+    /// Sets stock and desired stock of many items at once, as the web client's ReInventory tab does.
+    /// Stock added this way goes to the default location as a lot without a best-by date, stock removed is taken as when consuming.
+    pub async fn change_items(&self, data: Vec<Item>, source: &Source) -> Result<BatchResult, Error> {
+        let mut result = BatchResult::default();
+        let entries: Vec<_> = partition_batch(&data, |item| item.id.as_deref(), &mut result)
+            .into_iter()
+            .filter(|(th, item)| {
+                if !units::valid_quantity(item.stock) || !units::valid_quantity(item.desired_stock) {
+                    result.reject(&th.to_string(), "Stock and desired stock can not be negative");
+                    return false;
+                }
+                true
+            })
+            .collect();
+        let levels = self.levels(entries.iter().map(|(th, _)| th.clone()).collect()).await?;
+        let entries = existing(entries, &levels, &mut result);
+
+        let updated = self.run_batch(&entries, |query, item, th, entry| {
+            let levels = &levels[&item.to_string()];
+            let changes = level_statements(query, item, &th, levels, &levels.plan_set(units::round(entry.stock)), None);
+            let desired_stock = query.bind(units::round(entry.desired_stock));
+            let update = format!("{changes} UPDATE items SET stock = {}, desired_stock = {desired_stock}, last_updated = time::now() WHERE id = {th}", total(&th));
+            recorded(query, &th, &update, Operation::Reinventory, source)
+        }).await?;
+
+        Self::classify(entries, &updated, &mut result);
+        Ok(result)
+    }
+
+    /// Moves stock between locations, soonest expiring lots first. Moved lots keep their best-by and received dates.
+    /// An item's total stock doesn't change, so transfers aren't in the ledger.
+    pub async fn transfer_items(&self, data: Vec<Transfer>) -> Result<BatchResult, Error> {
+        let mut result = BatchResult::default();
+        let locations: Vec<Thing> = self.get_locations().await?
+            .into_iter()
+            .filter_map(|location| location_thing(&location.id).ok())
+            .collect();
+        let mut moves = vec![];
+        for transfer in data.iter() {
+            let (from, to) = match (location_thing(&transfer.from), location_thing(&transfer.to)) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(e), _) | (_, Err(e)) => {
+                    result.reject(&transfer.id, e.to_string());
+                    continue;
+                }
+            };
+            if let Some(missing) = [&from, &to].into_iter().find(|th| !locations.contains(*th)) {
+                result.reject(&transfer.id, format!("No location with id {}", missing));
+                continue;
+            }
+            if from == to {
+                result.reject(&transfer.id, "Can not transfer to the location stock is already at");
+                continue;
+            }
+            if !(units::valid_quantity(transfer.count) && transfer.count > 0.0) {
+                result.reject(&transfer.id, format!("Count must be a positive number, got {}", transfer.count));
+                continue;
+            }
+            moves.push((transfer, from, to));
+        }
+        let entries = partition_batch(&moves, |(transfer, _, _)| Some(transfer.id.as_str()), &mut result);
+        let levels = self.levels(entries.iter().map(|(th, _)| th.clone()).collect()).await?;
+        let entries = existing(entries, &levels, &mut result);
+
+        // Several transfers of one item may share a location, so availability and lots are tracked as the batch is planned
+        let mut available: BTreeMap<(String, String), f64> = BTreeMap::new();
+        let mut held = levels.clone();
+        let mut planned: Vec<(Thing, PlannedMove)> = vec![];
+        for (th, (transfer, from, to)) in entries {
+            let id = th.to_string();
+            let at_from = *available.entry((id.clone(), from.to_string())).or_insert_with(|| levels[&id].at(from));
+            if at_from < transfer.count {
+                result.reject(&id, format!("Only {} at {}, cannot transfer {}", at_from, from, transfer.count));
+                continue;
+            }
+            available.insert((id.clone(), from.to_string()), units::round(at_from - transfer.count));
+            *available.entry((id.clone(), to.to_string())).or_insert_with(|| levels[&id].at(to)) += transfer.count;
+            let moved = held.get_mut(&id).map(|levels| levels.take(from, transfer.count)).unwrap_or_default();
+            planned.push((th, (transfer.count, from.clone(), to.clone(), moved)));
+        }
+        let entries: Vec<_> = planned.iter().map(|(th, planned)| (th.clone(), planned)).collect();
+
+        let updated = self.run_batch(&entries, |query, item, th, (count, from, to, moved)| {
+            let mut changes = level_change(query, item, &th, from, -count);
+            changes += &level_change(query, item, &th, to, *count);
+            changes += &draw_lots(query, &moved.iter().map(|(lot, take)| (lot, *take)).collect::<Vec<_>>());
+            for (lot, take) in moved {
+                changes += &new_lot(query, &th, to, *take, lot.best_by, Some(lot.received));
+            }
+            // Stock no lot accounted for arrives without a best-by date
+            let unlotted = units::round(count - moved.iter().map(|(_, take)| take).sum::<f64>());
+            if unlotted > 0.0 {
+                changes += &new_lot(query, &th, to, unlotted, None, None);
+            }
+            changes += &clear_empty_lots(&th);
+            format!("{changes} UPDATE items SET last_updated = time::now() WHERE id = {th}")
+        }).await?;
+
+        Self::classify(entries, &updated, &mut result);
+        Ok(result)
+    }
+
+    pub async fn restock_item(&self, id: &str, stock: f64, source: &Source) -> Result<AffectedRows, Error> {
+        self.restock_items(vec![RestockItem { id: id.to_owned(), count: stock, unit: None, package: None, best_by: None, price: None, unit_price: None, store: None }], &default_location(), source)
+            .await?
+            .into_single(id)
+    }
+
+    pub async fn consume_item(&self, id: &str, stock: f64, policy: OverConsumption, source: &Source) -> Result<AffectedRows, Error> {
+        self.consume_items(vec![RestockItem { id: id.to_owned(), count: stock, unit: None, package: None, best_by: None, price: None, unit_price: None, store: None }], None, policy, source)
+            .await?
+            .into_single(id)
+    }
+}
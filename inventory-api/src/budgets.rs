@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{batch::OverBudget, categories::category_thing, db::{all_results, first_result, AffectedRows, DB}, prelude::{Error, W}, purchases::cents, query::record_thing, units, utils::macros::map};
+
+/// A monthly spending limit on one category, or on everything when there's no category.
+#[derive(Debug, Clone, Serialize)]
+pub struct Budget {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_name: Option<String>,
+    pub amount: f64,
+}
+
+impl TryFrom<W<Value>> for Budget {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            category: W(map.get("category").cloned().unwrap_or_default()).try_into()?,
+            category_name: W(map.get("category_name").cloned().unwrap_or_default()).try_into()?,
+            amount: W(map["amount"].clone()).try_into()?,
+        })
+    }
+}
+
+/// Body of `PUT /budget`. Setting a budget a category already has changes its amount.
+#[derive(Debug, Deserialize)]
+pub struct BudgetInput {
+    #[serde(default)]
+    pub category: Option<String>,
+    pub amount: f64,
+}
+
+/// How much of a budget has gone this month. `category` is `None` for the overall budget.
+#[derive(Debug, Serialize)]
+pub struct BudgetLine {
+    pub budget: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_name: Option<String>,
+    pub amount: f64,
+    pub spent: f64,
+    /// Negative once the budget is overspent
+    pub left: f64,
+    pub over: bool,
+}
+
+/// Response of `GET /budgets/status`, the overall budget first.
+#[derive(Debug, Serialize)]
+pub struct BudgetStatus {
+    /// As `YYYY-MM`
+    pub month: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub lines: Vec<BudgetLine>,
+}
+
+pub fn budget_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("budgets", id)
+}
+
+/// The first and last day of a month given as `YYYY-MM`, this month unless given.
+pub fn month_range(month: Option<&str>) -> Result<(NaiveDate, NaiveDate), Error> {
+    let from = match month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d")
+            .map_err(|_| Error::Validation(format!("'{}' is not a valid month, expected YYYY-MM", month)))?,
+        None => Utc::now().date_naive().with_day(1).unwrap_or_default(),
+    };
+    let next = match from.month() {
+        12 => NaiveDate::from_ymd_opt(from.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd_opt(from.year(), month + 1, 1),
+    };
+    let to = next.map_or(from, |next| next - Duration::days(1));
+    Ok((from, to))
+}
+
+impl DB {
+    /// Every budget, the overall one first and then by category name.
+    pub async fn get_budgets(&self) -> Result<Vec<Budget>, Error> {
+        let sql = "SELECT *, category.name AS category_name FROM budgets";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        let mut budgets: Vec<Budget> = array.into_iter().map(|value| W(value).try_into()).collect::<Result<_, _>>()?;
+        budgets.sort_by(|a, b| a.category_name.cmp(&b.category_name));
+        Ok(budgets)
+    }
+
+    /// Sets the monthly budget of a category, or the overall one without a category.
+    pub async fn set_budget(&self, input: BudgetInput) -> Result<Budget, Error> {
+        if !units::valid_quantity(input.amount) {
+            return Err(Error::Validation(format!("A budget must be a number of zero or more, got {}", input.amount)));
+        }
+        let category = match input.category.as_deref().map(str::trim).filter(|category| !category.is_empty()) {
+            Some(category) => Some(self.get_category(category).await?.id),
+            None => None,
+        };
+        let sql = match self.get_budgets().await?.iter().any(|budget| budget.category == category) {
+            true => "UPDATE budgets SET amount = $amount WHERE category = $category RETURN NONE",
+            false => "CREATE budgets SET category = $category, amount = $amount RETURN NONE",
+        };
+        let vars: BTreeMap<String, Value> = map!(
+            "category".into() => match &category {
+                Some(category) => category_thing(category)?.into(),
+                None => Value::None,
+            },
+            "amount".into() => Value::from(cents(input.amount))
+        );
+        let _ = all_results(self.execute(sql, Some(vars.clone())).await?)?;
+
+        let sql = "SELECT *, category.name AS category_name FROM budgets WHERE category = $category";
+        let res = self.execute(sql, Some(vars)).await?;
+        W(first_result(res)?.first()).try_into()
+    }
+
+    pub async fn delete_budget(&self, id: &str) -> Result<AffectedRows, Error> {
+        let sql = "DELETE budgets WHERE id = $th RETURN BEFORE";
+        let vars: BTreeMap<String, Value> = map!("th".into() => budget_thing(id)?.into());
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::Object(_) => Ok(AffectedRows { rows_affected: 1 }),
+            _ => Err(Error::NotFound(format!("No budget with id {}", id))),
+        }
+    }
+
+    /// The budgets of this month that `paid`, what was just spent by category name, put over. Whatever else
+    /// was spent this month, even at the same time, counts as spent before, so a budget that was over already
+    /// isn't reported again.
+    pub async fn put_over_budget(&self, paid: &BTreeMap<&str, f64>) -> Result<Vec<OverBudget>, Error> {
+        let total: f64 = paid.values().sum();
+        let status = self.budget_status(None).await?;
+
+        let over = status.lines.into_iter()
+            .filter(|line| {
+                let ours = match (&line.category, line.category_name.as_deref()) {
+                    (None, _) => total,
+                    (Some(_), Some(name)) => paid.get(name).copied().unwrap_or_default(),
+                    (Some(_), None) => 0.0,
+                };
+                ours > 0.0 && line.over && cents(line.spent - ours) <= line.amount
+            })
+            .map(|line| OverBudget { budget: line.budget, category_name: line.category_name, amount: line.amount, spent: line.spent })
+            .collect();
+        Ok(over)
+    }
+
+    /// How much of each budget the purchases of a month have used. Purchases are counted against
+    /// the category their item was in when it was bought.
+    pub async fn budget_status(&self, month: Option<&str>) -> Result<BudgetStatus, Error> {
+        let (from, to) = month_range(month)?;
+        let budgets = self.get_budgets().await?;
+        let purchases = self.purchases(from, to).await?;
+
+        let mut spent: BTreeMap<&str, f64> = BTreeMap::new();
+        for purchase in purchases.iter() {
+            *spent.entry(purchase.category.as_str()).or_default() += purchase.price;
+        }
+        let total: f64 = purchases.iter().map(|purchase| purchase.price).sum();
+
+        let lines = budgets.into_iter()
+            .map(|budget| {
+                let spent = cents(match (&budget.category, budget.category_name.as_deref()) {
+                    (None, _) => total,
+                    (Some(_), Some(name)) => spent.get(name).copied().unwrap_or_default(),
+                    (Some(_), None) => 0.0,
+                });
+                BudgetLine {
+                    budget: budget.id,
+                    category: budget.category,
+                    category_name: budget.category_name,
+                    amount: budget.amount,
+                    spent,
+                    left: cents(budget.amount - spent),
+                    over: spent > budget.amount,
+                }
+            })
+            .collect();
+
+        Ok(BudgetStatus { month: from.format("%Y-%m").to_string(), from, to, lines })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ledger::Source, locations::default_location, RestockItem};
+
+    fn bought(id: &str, price: f64) -> Vec<RestockItem> {
+        vec![RestockItem { id: id.to_owned(), count: 1.0, unit: None, package: None, best_by: None, price: Some(price), unit_price: None, store: None }]
+    }
+
+    async fn put_over(db: &DB, id: &str, price: f64) -> Vec<Option<String>> {
+        let result = db.restock_items(bought(id, price), &default_location(), &Source::default()).await.unwrap();
+        result.over_budget.into_iter().map(|over| over.category_name).collect()
+    }
+
+    #[tokio::test]
+    async fn reports_only_budgets_a_restock_puts_over() {
+        let db = DB::memory().await;
+        let milk = db.add_item("Milk", "Dairy", "count").await.unwrap().id.unwrap();
+        let bread = db.add_item("Bread", "Bakery", "count").await.unwrap().id.unwrap();
+        let dairy = db.get_categories().await.unwrap().into_iter().find(|category| category.name == "Dairy").unwrap().id;
+        db.set_budget(BudgetInput { category: None, amount: 20.0 }).await.unwrap();
+        db.set_budget(BudgetInput { category: Some(dairy), amount: 5.0 }).await.unwrap();
+
+        assert!(put_over(&db, &milk, 4.0).await.is_empty());
+        assert_eq!(put_over(&db, &milk, 2.0).await, [Some("Dairy".to_owned())]);
+        // Dairy was over already
+        assert!(put_over(&db, &milk, 1.0).await.is_empty());
+        assert_eq!(put_over(&db, &bread, 15.0).await, [None]);
+        assert!(put_over(&db, &bread, 1.0).await.is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{db::{all_results, first_result, AffectedRows, DB}, prelude::{Error, W}, query::{record_thing, Query}, utils::macros::map};
+
+/// A category items are grouped under. Items refer to their category by name, so renaming
+/// or merging a category rewrites `Item.category` on every item in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    /// Where the category is shown, lowest first
+    pub position: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+impl TryFrom<W<Value>> for Category {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            position: W(map["position"].clone()).try_into()?,
+            colour: W(map.get("colour").cloned().unwrap_or_default()).try_into()?,
+            icon: W(map.get("icon").cloned().unwrap_or_default()).try_into()?,
+            parent: W(map.get("parent").cloned().unwrap_or_default()).try_into()?,
+        })
+    }
+}
+
+/// Body of `POST /category` and `PATCH /category/<id>`.
+#[derive(Debug, Deserialize)]
+pub struct CategoryInput {
+    pub name: String,
+    #[serde(default)]
+    pub colour: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+pub fn category_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("categories", id)
+}
+
+/// Trims the input, turning blank optional fields into `None`, and checks what's left.
+fn validate_category(input: CategoryInput) -> Result<CategoryInput, Error> {
+    let optional = |value: Option<String>| value.map(|v| v.trim().to_owned()).filter(|v| !v.is_empty());
+    let input = CategoryInput {
+        name: input.name.trim().to_owned(),
+        colour: optional(input.colour),
+        icon: optional(input.icon),
+        parent: optional(input.parent),
+    };
+    if input.name.is_empty() {
+        return Err(Error::Validation("A category name is required".into()));
+    }
+    if let Some(colour) = &input.colour {
+        let hex = colour.strip_prefix('#').unwrap_or_default();
+        if !(hex.len() == 3 || hex.len() == 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::Validation(format!("'{}' is not a colour, expected #rgb or #rrggbb", colour)));
+        }
+    }
+    if input.icon.as_ref().is_some_and(|icon| icon.chars().count() > 8) {
+        return Err(Error::Validation("A category icon can be at most 8 characters".into()));
+    }
+    Ok(input)
+}
+
+/// A category's parent as the record `categories.parent` holds, or `NONE` at the top level.
+fn parent_value(parent: &Option<String>) -> Result<Value, Error> {
+    Ok(parent.as_deref().map(category_thing).transpose()?.map_or(Value::None, Value::from))
+}
+
+impl DB {
+    pub async fn get_categories(&self) -> Result<Vec<Category>, Error> {
+        let sql = "SELECT * FROM categories ORDER BY position ASC, name ASC";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn get_category(&self, id: &str) -> Result<Category, Error> {
+        let sql = "SELECT * FROM $th";
+        let vars: BTreeMap<String, Value> = map!("th".into() => category_thing(id)?.into());
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No category with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    async fn category_named(&self, name: &str) -> Result<Option<Category>, Error> {
+        let sql = "SELECT * FROM categories WHERE name = $name";
+        let vars: BTreeMap<String, Value> = map!("name".into() => Value::from(name));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Ok(None),
+            value => Ok(Some(W(value).try_into()?)),
+        }
+    }
+
+    /// Creates a category record for `name` if there isn't one, so items can keep naming new categories freely.
+    pub async fn ensure_category(&self, name: &str) -> Result<(), Error> {
+        let name = name.trim();
+        if name.is_empty() || self.category_named(name).await?.is_some() {
+            return Ok(());
+        }
+        self.create_category(CategoryInput { name: name.to_owned(), colour: None, icon: None, parent: None }).await?;
+        Ok(())
+    }
+
+    /// Gives every category already used by an item a record, for data written before categories existed.
+    pub async fn backfill_categories(&self) -> Result<(), Error> {
+        let sql = "SELECT category FROM items GROUP BY category";
+        let used: Array = W(first_result(self.execute(sql, None).await?)?).try_into()?;
+        for record in used {
+            let record: Object = W(record).try_into()?;
+            let name: String = W(record.get("category").cloned().unwrap_or_default()).try_into()?;
+            self.ensure_category(&name).await?;
+        }
+        Ok(())
+    }
+
+    /// Checks `parent` exists and that making it the parent of `id` wouldn't create a loop.
+    async fn check_parent(&self, id: Option<&str>, parent: &str) -> Result<Thing, Error> {
+        let parent_th = category_thing(parent)?;
+        let parents: BTreeMap<String, Option<String>> = self.get_categories().await?
+            .into_iter()
+            .map(|category| (category.id, category.parent))
+            .collect();
+        if !parents.contains_key(&parent_th.to_string()) {
+            return Err(Error::NotFound(format!("No category with id {}", parent)));
+        }
+        let mut ancestor = Some(parent_th.to_string());
+        // Bounded so a loop already in the data can't hang the request
+        for _ in 0..=parents.len() {
+            let Some(current) = ancestor else { break };
+            if id.is_some_and(|id| id == current) {
+                return Err(Error::Validation("A category can not be inside itself".into()));
+            }
+            ancestor = parents.get(&current).cloned().flatten();
+        }
+        Ok(parent_th)
+    }
+
+    pub async fn create_category(&self, input: CategoryInput) -> Result<Category, Error> {
+        let input = validate_category(input)?;
+        if self.category_named(&input.name).await?.is_some() {
+            return Err(Error::Conflict(format!("A category named {} already exists", input.name)));
+        }
+        let parent: Value = match &input.parent {
+            Some(parent) => self.check_parent(None, parent).await?.into(),
+            None => Value::None,
+        };
+        let sql = "CREATE categories SET name = $name, position = (math::max((SELECT VALUE position FROM categories)) ?? -1) + 1, colour = $colour, icon = $icon, parent = $parent";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(input.name.as_str()),
+            "colour".into() => input.colour.clone().map_or(Value::None, Value::from),
+            "icon".into() => input.icon.clone().map_or(Value::None, Value::from),
+            "parent".into() => parent
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        W(first_result(res)?.first()).try_into()
+    }
+
+    /// Updates a category. A new name is written to every item and purchase in the category in the same transaction.
+    pub async fn update_category(&self, id: &str, input: CategoryInput) -> Result<Category, Error> {
+        let input = validate_category(input)?;
+        let current = self.get_category(id).await?;
+        if let Some(existing) = self.category_named(&input.name).await? {
+            if existing.id != current.id {
+                return Err(Error::Conflict(format!("A category named {} already exists", input.name)));
+            }
+        }
+        let parent: Value = match &input.parent {
+            Some(parent) => self.check_parent(Some(&current.id), parent).await?.into(),
+            None => Value::None,
+        };
+
+        let mut query = Query::transaction();
+        let th = query.bind(category_thing(id)?);
+        let name = query.bind(input.name.as_str());
+        let colour = query.bind(input.colour.clone().map_or(Value::None, Value::from));
+        let icon = query.bind(input.icon.clone().map_or(Value::None, Value::from));
+        let parent = query.bind(parent);
+        query.push(format!("UPDATE categories SET name = {name}, colour = {colour}, icon = {icon}, parent = {parent} WHERE id = {th}"));
+        if current.name != input.name {
+            let old_name = query.bind(current.name.as_str());
+            query.push(format!("UPDATE items SET category = {name} WHERE category = {old_name} RETURN NONE"));
+            // Budgets count purchases by the category name they were made under
+            query.push(format!("UPDATE purchases SET category = {name} WHERE category = {old_name} RETURN NONE"));
+        }
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        self.get_category(id).await
+    }
+
+    /// Moves every item, purchase and sub-category of `id` into `into`, then deletes `id` and its budget, all in one transaction.
+    pub async fn merge_category(&self, id: &str, into: &str) -> Result<Category, Error> {
+        let from = self.get_category(id).await?;
+        let target = self.get_category(into).await?;
+        if from.id == target.id {
+            return Err(Error::Validation("A category can not be merged into itself".into()));
+        }
+
+        let mut query = Query::transaction();
+        let from_th = query.bind(category_thing(&from.id)?);
+        let target_th = query.bind(category_thing(&target.id)?);
+        let from_name = query.bind(from.name.as_str());
+        let target_name = query.bind(target.name.as_str());
+        query.push(format!("UPDATE items SET category = {target_name} WHERE category = {from_name} RETURN NONE"));
+        query.push(format!("UPDATE purchases SET category = {target_name} WHERE category = {from_name} RETURN NONE"));
+        query.push(format!("DELETE budgets WHERE category = {from_th}"));
+        // The target may itself have been inside the merged category; it takes that category's place
+        let from_parent = query.bind(parent_value(&from.parent)?);
+        query.push(format!("UPDATE categories SET parent = {from_parent} WHERE id = {target_th} AND parent = {from_th} RETURN NONE"));
+        query.push(format!("UPDATE categories SET parent = {target_th} WHERE parent = {from_th} RETURN NONE"));
+        query.push(format!("DELETE categories WHERE id = {from_th}"));
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        self.get_category(&target.id).await
+    }
+
+    /// Puts the categories in `ids` first, in that order. Categories left out keep their relative order after them.
+    pub async fn reorder_categories(&self, ids: Vec<String>) -> Result<Vec<Category>, Error> {
+        let mut order = vec![];
+        for id in ids.iter() {
+            let th = category_thing(id)?.to_string();
+            if !order.contains(&th) {
+                order.push(th);
+            }
+        }
+        let current = self.get_categories().await?;
+        if let Some(missing) = order.iter().find(|id| !current.iter().any(|category| &category.id == *id)) {
+            return Err(Error::NotFound(format!("No category with id {}", missing)));
+        }
+        for category in current {
+            if !order.contains(&category.id) {
+                order.push(category.id);
+            }
+        }
+
+        let mut query = Query::transaction();
+        for (position, id) in order.iter().enumerate() {
+            let th = query.bind(category_thing(id)?);
+            let position = query.bind(position as i64);
+            query.push(format!("UPDATE categories SET position = {position} WHERE id = {th} RETURN NONE"));
+        }
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        self.get_categories().await
+    }
+
+    /// Deletes an empty category and its budget. Its sub-categories move up to its parent.
+    pub async fn delete_category(&self, id: &str) -> Result<AffectedRows, Error> {
+        let category = self.get_category(id).await?;
+        let sql = "SELECT count() FROM items WHERE category = $name GROUP ALL";
+        let vars: BTreeMap<String, Value> = map!("name".into() => Value::from(category.name.as_str()));
+        let in_use: i64 = match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::Object(count) => W(count.get("count").cloned().unwrap_or_default()).try_into().unwrap_or(0),
+            _ => 0,
+        };
+        if in_use > 0 {
+            return Err(Error::Conflict(format!("{} items are still in {}, move or merge them first", in_use, category.name)));
+        }
+
+        let mut query = Query::transaction();
+        let th = query.bind(category_thing(id)?);
+        let parent = query.bind(parent_value(&category.parent)?);
+        query.push(format!("UPDATE categories SET parent = {parent} WHERE parent = {th} RETURN NONE"));
+        query.push(format!("DELETE budgets WHERE category = {th}"));
+        query.push(format!("DELETE categories WHERE id = {th}"));
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        Ok(AffectedRows { rows_affected: 1 })
+    }
+}
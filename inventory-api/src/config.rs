@@ -0,0 +1,163 @@
+use std::{env, net::{IpAddr, Ipv4Addr}, path::PathBuf};
+
+use rocket::figment::{providers::{Env, Format, Serialized, Toml}, Figment, Profile};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::Error;
+
+const USAGE: &str = "Usage: inventory-api [OPTIONS]
+
+Options:
+    --config <PATH>       Configuration file to read (default: Inventory.toml)
+    --address <ADDR>      Address to bind to
+    --port <PORT>         Port to listen on
+    --datastore <URL>     Datastore url: memory, file://<path> or rocksdb://<path>
+    --namespace <NS>      SurrealDB namespace
+    --database <DB>       SurrealDB database
+    --log-config <PATH>   log4rs configuration file
+    --log-dir <PATH>      Directory log files are written to and read from
+    --over-consumption <POLICY>
+                          reject or clamp consuming more than is in stock
+    --default-location <NAME>
+                          Name given to the location stock goes to when none is named
+    -h, --help            Print this message";
+
+/// Everything the api needs to know about the machine it is running on.
+///
+/// Values are layered, with later sources winning:
+/// 1. The defaults below
+/// 2. `Rocket.toml` and `ROCKET_` environment variables (for anything Rocket specific)
+/// 3. `Inventory.toml` (or the file named by `INVENTORY_CONFIG`/`--config`)
+/// 4. `INVENTORY_` environment variables, e.g. `INVENTORY_PORT=8000`
+/// 5. Command line flags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    pub datastore: String,
+    pub namespace: String,
+    pub database: String,
+    pub log_config: PathBuf,
+    pub log_dir: PathBuf,
+    pub over_consumption: OverConsumption,
+    /// Name of the location created on first start, which holds stock added without naming a location
+    pub default_location: String
+}
+
+/// What to do when asked to consume more of an item than is in stock.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverConsumption {
+    /// Refuse that entry, leaving its stock untouched
+    Reject,
+    /// Consume whatever is left, bringing the stock to zero
+    Clamp
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 26530,
+            datastore: "file://inventory.db".into(),
+            namespace: "my_ns".into(),
+            database: "my_bd".into(),
+            log_config: "logging_config.yaml".into(),
+            log_dir: "log".into(),
+            over_consumption: OverConsumption::Clamp,
+            default_location: "Pantry".into()
+        }
+    }
+}
+
+/// Command line flags. Only flags that were actually given are serialized, so
+/// anything left out falls through to the file and environment.
+#[derive(Debug, Default, Serialize)]
+struct CliArgs {
+    #[serde(skip_serializing)]
+    config: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datastore: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_config: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    over_consumption: Option<OverConsumption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_location: Option<String>
+}
+
+impl CliArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            if arg.eq("-h") || arg.eq("--help") {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_owned(), value.to_owned()),
+                None => {
+                    let value = args.next().ok_or(Error::Config(format!("Missing value for {}\n\n{}", arg, USAGE)))?;
+                    (arg, value)
+                }
+            };
+            match flag.as_str() {
+                "--config" => result.config = Some(value.into()),
+                "--address" => result.address = Some(value.parse().map_err(|_| Error::Config(format!("'{}' is not a valid address", value)))?),
+                "--port" => result.port = Some(value.parse().map_err(|_| Error::Config(format!("'{}' is not a valid port", value)))?),
+                "--datastore" => result.datastore = Some(value),
+                "--namespace" => result.namespace = Some(value),
+                "--database" => result.database = Some(value),
+                "--log-config" => result.log_config = Some(value.into()),
+                "--log-dir" => result.log_dir = Some(value.into()),
+                "--over-consumption" => result.over_consumption = Some(match value.as_str() {
+                    "reject" => OverConsumption::Reject,
+                    "clamp" => OverConsumption::Clamp,
+                    _ => return Err(Error::Config(format!("'{}' is not an over-consumption policy, expected reject or clamp", value)))
+                }),
+                "--default-location" => result.default_location = Some(value),
+                _ => return Err(Error::Config(format!("Unknown option {}\n\n{}", flag, USAGE)))
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Builds the figment used both for [`InventoryConfig`] and for Rocket itself,
+/// so `address` and `port` only have to be set once.
+pub fn figment() -> Result<Figment, Error> {
+    let args = CliArgs::parse(env::args().skip(1))?;
+    let config_file = match &args.config {
+        Some(path) => path.clone(),
+        None => Env::var_or("INVENTORY_CONFIG", "Inventory.toml").into()
+    };
+
+    let figment = Figment::from(rocket::Config::default())
+        .merge(Serialized::defaults(InventoryConfig::default()))
+        .merge(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+        .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global())
+        .merge(Toml::file(config_file).nested())
+        .merge(Env::prefixed("INVENTORY_").ignore(&["CONFIG", "PROFILE"]).global())
+        .merge(Serialized::globals(args))
+        .select(Profile::from_env_or("INVENTORY_PROFILE", rocket::Config::DEFAULT_PROFILE));
+    Ok(figment)
+}
+
+pub fn validate_datastore(datastore: &str) -> Result<(), Error> {
+    if datastore.eq("memory") || datastore.starts_with("file://") || datastore.starts_with("rocksdb://") {
+        Ok(())
+    } else {
+        Err(Error::Config(format!("Unsupported datastore '{}', expected memory, file://<path> or rocksdb://<path>", datastore)))
+    }
+}
@@ -4,25 +4,10 @@ use std::{collections::BTreeMap, fmt, sync::Arc};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use surrealdb::{
-    dbs::{Response, Session}, kvs::Datastore, sql::{thing, Array, Object, Value}
+    dbs::{Response, Session}, kvs::Datastore, sql::{Array, Object, Value}
 };
 
-use crate::{prelude::{Error, W}, utils::macros::map};
-
-#[derive(Debug, Serialize, Deserialize)]
-pub enum Categories {
-    Can,
-    Box,
-    Bag,
-    Frozen,
-    Refridgerated,
-    Other
-}
-impl fmt::Display for Categories {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:#?}", self)
-    }
-}
+use crate::{barcodes::barcodes_of, ledger::{recorded, Operation, Source}, locations::{default_location, level_statements, scale_statement, total, Levels}, packages::{packages_of, Package}, prelude::{Error, W}, query::{item_thing, Query}, units::{self, DEFAULT_UNIT}, utils::macros::map};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Item {
@@ -30,8 +15,23 @@ pub struct Item {
     pub id: Option<String>,
     pub name: String,
     pub category: String,
-    pub stock: i64,
-    pub desired_stock: i64,
+    pub stock: f64,
+    pub desired_stock: f64,
+    /// What `stock` and `desired_stock` are counted in, one of [`units::UNITS`]
+    #[serde(default = "units::default_unit")]
+    pub unit: String,
+    /// Packages the item is bought in, only changed through `PATCH /item/<id>/packages`
+    #[serde(default)]
+    pub packages: Vec<Package>,
+    /// UPC/EAN codes that identify the item, only changed through `/item/<id>/barcodes/<code>`
+    #[serde(default)]
+    pub barcodes: Vec<String>,
+    /// The store the item is usually bought at, only changed through `PATCH /item/<id>/store`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    /// Where in `store` it's found, one of the store's aisles unless they've changed since
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aisle: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub track_general: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -41,8 +41,8 @@ pub struct Item {
         writeln!(f, "\tid: {}", &self.id.clone().unwrap_or("None".to_string()))?;
         writeln!(f, "\tname: {}", &self.name)?;
         writeln!(f, "\tcategory: {}", &self.category)?;
-        writeln!(f, "\tstock {}", &self.stock)?;
-        writeln!(f, "\tdesired_stock: {}", &self.desired_stock)?;
+        writeln!(f, "\tstock {} {}", &self.stock, &self.unit)?;
+        writeln!(f, "\tdesired_stock: {} {}", &self.desired_stock, &self.unit)?;
         writeln!(f, "\ttrack_general: {}", &self.track_general.unwrap_or(false))?;
         write!(f, "\tlast_updated: {}", &self.last_updated.unwrap())
     }
@@ -65,12 +65,18 @@ impl TryFrom<W<Object>> for Item {
     type Error = Error;
     fn try_from(val: W<Object>) -> Result<Self, Error> {
         let map = val.0.clone();
+        let unit: Option<String> = W(map.get("unit").cloned().unwrap_or_default()).try_into()?;
         Ok(Self {
             id: Some(W(map["id"].clone()).try_into()?), 
             name: W(map["name"].clone()).try_into()?, 
             category: W(map["category"].clone()).try_into()?,
             stock: W(map["stock"].clone()).try_into()?,
             desired_stock: W(map["desired_stock"].clone()).try_into()?,
+            unit: unit.unwrap_or_else(|| DEFAULT_UNIT.to_owned()),
+            packages: packages_of(map.get("packages"))?,
+            barcodes: barcodes_of(map.get("barcodes"))?,
+            store: W(map.get("store").cloned().unwrap_or_default()).try_into()?,
+            aisle: W(map.get("aisle").cloned().unwrap_or_default()).try_into()?,
             track_general: Some(W(map["track_general"].clone()).try_into()?),
             last_updated: Some(W(map["last_updated"].clone()).try_into()?)
         })
@@ -109,6 +115,70 @@ pub struct AffectedRows {
     pub rows_affected: usize,
 }
 
+/// Takes the result of the first statement in a response.
+pub(crate) fn first_result(res: Vec<Response>) -> Result<Value, Error> {
+    let first_res = res.into_iter().next().expect("Did not get a response");
+    Ok(first_res.result?)
+}
+
+/// Takes the result of every statement in a response, failing if any of them failed.
+/// When a transaction fails every statement errors, so the one that actually caused it is preferred.
+pub(crate) fn all_results(res: Vec<Response>) -> Result<Vec<Value>, Error> {
+    let mut values = vec![];
+    let mut error = None;
+    for response in res {
+        match response.result {
+            Ok(value) => values.push(value),
+            Err(surrealdb::err::Error::QueryNotExecuted) => {
+                error.get_or_insert(surrealdb::err::Error::QueryNotExecuted);
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+    match error {
+        Some(e) => Err(e.into()),
+        None => Ok(values),
+    }
+}
+
+/// Takes the record returned by a statement against a single item. `UPDATE items ... WHERE id = $th`
+/// is used over `UPDATE $th` so a missing id returns nothing rather than creating the record.
+pub(crate) fn found_item(res: Vec<Response>, id: &str) -> Result<Item, Error> {
+    match first_result(res)?.first() {
+        Value::None => Err(Error::NotFound(format!("No item with id {}", id))),
+        value => W(value).try_into(),
+    }
+}
+
+/// Like [`found_item`], for a transaction built with [`recorded`] where the item isn't the first result.
+fn found_recorded_item(res: Vec<Response>, id: &str) -> Result<Item, Error> {
+    let record = all_results(res)?.into_iter().find_map(|value| match value {
+        Value::Array(array) => array.into_iter().find(|record| matches!(record, Value::Object(_))),
+        _ => None,
+    });
+    match record {
+        Some(record) => W(record).try_into(),
+        None => Err(Error::NotFound(format!("No item with id {}", id))),
+    }
+}
+
+fn validate_stock(stock: f64, desired_stock: f64) -> Result<(), Error> {
+    if !units::valid_quantity(stock) || !units::valid_quantity(desired_stock) {
+        return Err(Error::Validation("Stock and desired stock can not be negative".into()));
+    }
+    Ok(())
+}
+
+fn validate_name_and_category(name: &str, category: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::Validation("An item name is required".into()));
+    }
+    if category.trim().is_empty() {
+        return Err(Error::Validation("A category is required for all items".into()));
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 pub struct DB {
     pub ds: Arc<Datastore>,
@@ -116,6 +186,17 @@ pub struct DB {
 }
 
 impl DB {
+    /// A fresh database in memory with the schema and default location in place, for tests.
+    #[cfg(test)]
+    pub(crate) async fn memory() -> DB {
+        let ds = Arc::new(Datastore::new("memory").await.unwrap());
+        let sesh = Session::default().with_ns("test").with_db("test");
+        let db = DB { ds, sesh };
+        db.define_schema().await.unwrap();
+        db.init_locations("Pantry").await.unwrap();
+        db
+    }
+
     pub async fn execute(
         &self, 
         query: &str,
@@ -125,11 +206,15 @@ impl DB {
         Ok(res)
     }
     
-    pub async fn add_item(&self, name: &str, category: &str) -> Result<Item, crate::error::Error> {
-        let sql = "CREATE items SET name = $name, category = $category, stock = 0, desired_stock = 0, track_general = false, last_updated = time::now()";
+    pub async fn add_item(&self, name: &str, category: &str, unit: &str) -> Result<Item, crate::error::Error> {
+        validate_name_and_category(name, category)?;
+        let unit = units::unit(unit)?;
+        self.ensure_category(category).await?;
+        let sql = "CREATE items SET name = $name, category = $category, stock = 0, desired_stock = 0, unit = $unit, track_general = false, last_updated = time::now()";
         let vars: BTreeMap<String, Value> = map!(
             "name".into() => Value::Strand(name.into()),
-            "category".into() => Value::Strand(category.into())
+            "category".into() => Value::Strand(category.into()),
+            "unit".into() => Value::from(unit.name)
         );
         let res = self.execute(sql, Some(vars)).await?;
 
@@ -138,42 +223,60 @@ impl DB {
         W(first_res.result?.first()).try_into()
     }
 
-    pub async fn set_desired_stock(&self, id: &str, desired_stock: i64) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "UPDATE $th SET desired_stock = $desired_stock;";
-        let tid = format!("{}", id);
+    pub async fn set_desired_stock(&self, id: &str, desired_stock: f64) -> Result<AffectedRows, crate::error::Error> {
+        validate_stock(0.0, desired_stock)?;
+        let desired_stock = units::round(desired_stock);
+        let sql = "UPDATE items SET desired_stock = $desired_stock WHERE id = $th;";
         let vars: BTreeMap<String, Value> = map!(
-            "th".into() => thing(&tid)?.into(),
-            "desired_stock".into() => Value::Number(desired_stock.into())
+            "th".into() => item_thing(id)?.into(),
+            "desired_stock".into() => Value::from(desired_stock)
         );
-        let _ = self.execute(sql, Some(vars)).await?;
+        let res = self.execute(sql, Some(vars)).await?;
+        let _ = found_item(res, id)?;
         Ok(AffectedRows { rows_affected: 1 })
     }
 
-    pub async fn add_full_item(&self, name: &str, category: &str, stock: i64, desired_stock: i64, track_general: bool) -> Result<Item, crate::error::Error> {
-        let sql = "CREATE items SET name = $name, category = $category, stock = $stock, desired_stock = $desired_stock, track_general = $track_general, last_updated = time::now()";
+    pub async fn add_full_item(&self, name: &str, category: &str, stock: f64, desired_stock: f64, unit: &str, track_general: bool) -> Result<Item, crate::error::Error> {
+        validate_name_and_category(name, category)?;
+        validate_stock(stock, desired_stock)?;
+        let (stock, desired_stock) = (units::round(stock), units::round(desired_stock));
+        let unit = units::unit(unit)?;
+        self.ensure_category(category).await?;
+        let sql = "CREATE items SET name = $name, category = $category, stock = $stock, desired_stock = $desired_stock, unit = $unit, track_general = $track_general, last_updated = time::now()";
         let vars: BTreeMap<String, Value> = map!(
             "name".into() => Value::Strand(name.into()),
             "category".into() => Value::Strand(category.into()),
-            "stock".into() => Value::Number(stock.into()),
-            "desired_stock".into() => Value::Number(desired_stock.into()),
+            "stock".into() => Value::from(stock),
+            "desired_stock".into() => Value::from(desired_stock),
+            "unit".into() => Value::from(unit.name),
             "track_general".into() => Value::Bool(track_general.into())
         );
         let res = self.execute(sql, Some(vars)).await?;
 
         let first_res = res.into_iter().next().expect("Did not get a response");
+        let created = first_res.result?.first();
+
+        // Starting stock goes to the default location like any other stock added without one
+        if let (true, Value::Object(record)) = (stock > 0.0, &created) {
+            if let Some(Value::Thing(item)) = record.get("id") {
+                let mut query = Query::transaction();
+                let th = query.bind(item.clone());
+                let changes = level_statements(&mut query, item, &th, &Levels::default(), &[(default_location(), stock)], None);
+                query.push(changes);
+                let (sql, vars) = query.commit();
+                let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+            }
+        }
 
-        W(first_res.result?.first()).try_into()
+        W(created).try_into()
     }
 
     pub async fn get_item(&self, id: &str) -> Result<Item, crate::error::Error> {
         let sql = "SELECT * FROM $th";
-        let tid = format!("{}", id);
-        let vars: BTreeMap<String, Value> = map!("th".into() => thing(&tid)?.into());
+        let vars: BTreeMap<String, Value> = map!("th".into() => item_thing(id)?.into());
         let res = self.execute(sql, Some(vars)).await?;
 
-        let first_res = res.into_iter().next().expect("Did not get a response");
-
-        W(first_res.result?.first()).try_into()
+        found_item(res, id)
     }
 
     pub async fn get_all_items(&self) -> Result<Vec<Item>, crate::error::Error> {
@@ -193,90 +296,51 @@ impl DB {
         // todo!()
     }
 
-    pub async fn restock_item(&self, id: &str, stock: i64) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "UPDATE $th SET stock += $stock, last_updated = time::now();";
-        let tid = format!("{}", id);
-        let vars: BTreeMap<String, Value> = map!(
-            "th".into() => thing(&tid)?.into(),
-            "stock".into() => Value::Number(stock.into())
-        );
-        let _ = self.execute(sql, Some(vars)).await?;
-        Ok(AffectedRows { rows_affected: 1 })
-
-        // let first_res = res.into_iter().next().expect("Did not get a response");
-
-        // let array1: Array = W(first_res.result?).try_into()?;
-        // let array2: Array = W(array1.0.into_iter().next().unwrap()).try_into()?;
-
-        // array2.0.into_iter().map(|value| W(value).try_into()).collect()
-    }
-
-    pub async fn consume_item(&self, id: &str, stock: i64) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "UPDATE $th SET stock -= $stock, last_updated = time::now();";
-        let tid = format!("{}", id);
-        let vars: BTreeMap<String, Value> = map!(
-            "th".into() => thing(&tid)?.into(),
-            "stock".into() => Value::Number(stock.into())
-        );
-        let _ = self.execute(sql, Some(vars)).await?;
-        Ok(AffectedRows { rows_affected: 1 })
-    }
-
-    pub async fn restock_items(&self, data: Vec<crate::RestockItem>) -> Result<AffectedRows, crate::error::Error> {
-        let mut sql = "BEGIN TRANSACTION;".to_owned();
-        for item in data.iter() {
-            sql += &format!("UPDATE {} SET stock += {}, last_updated = time::now();", item.id, item.count.to_string());
-        }
-        sql += "COMMIT TRANSACTION;";
-        let _ = self.execute(&sql, None).await?;
-        Ok(AffectedRows { rows_affected: data.len() })
-    }
-
-    pub async fn consume_items(&self, data: Vec<crate::RestockItem>) -> Result<AffectedRows, crate::error::Error> {
-        let mut sql = "BEGIN TRANSACTION;".to_owned();
-        for item in data.iter() {
-            sql += &format!("UPDATE {} SET stock -= {}, last_updated = time::now();", item.id, item.count.to_string());
-        }
-        sql += "COMMIT TRANSACTION;";
-        let _ = self.execute(&sql, None).await?;
-        Ok(AffectedRows { rows_affected: data.len() })
-    }
-
-    pub async fn change_item(&self, id: &str, item: Item) -> Result<Item, crate::error::Error> {
-        let sql = "UPDATE $th SET name = $name, category = $category, stock = $stock, desired_stock = $desired_stock, track_general = $track_general, last_updated = time::now()";
-        let tid = format!("{}", id);
-        let vars: BTreeMap<String, Value> = map!(
-            "th".into() => thing(&tid)?.into(),
-            "name".into() => Value::Strand(item.name.into()),
-            "category".into() => Value::Strand(item.category.into()),
-            "stock".into() => Value::Number(item.stock.into()),
-            "desired_stock".into() => Value::Number(item.desired_stock.into()),
-            "track_general".into() => Value::Bool(item.track_general.unwrap_or(false).into())
-        );
-        let res = self.execute(sql, Some(vars)).await?;
-
-        let first_res = res.into_iter().next().expect("Did not get a response");
-
-        W(first_res.result?.first()).try_into()
-    }
-
-    pub async fn change_items(&self, data: Vec<Item>) -> Result<AffectedRows, crate::error::Error> {
-        let mut sql = "BEGIN TRANSACTION;".to_owned();
-        for item in data.iter() {
-            sql += &format!("UPDATE {} SET stock = {}, desired_stock = {}, last_updated = time::now();", item.id.clone().unwrap().to_string(), item.stock.to_string(), item.desired_stock.to_string());
+    /// Changes everything about an item but its packages. Moving to a unit that converts from the old one converts
+    /// the stock already at each location and the package sizes, so `item.stock` is always read in the new unit.
+    pub async fn change_item(&self, id: &str, item: Item, source: &Source) -> Result<Item, crate::error::Error> {
+        validate_name_and_category(&item.name, &item.category)?;
+        validate_stock(item.stock, item.desired_stock)?;
+        let unit = units::unit(&item.unit)?;
+        let item_th = item_thing(id)?;
+        self.ensure_category(&item.category).await?;
+        let mut levels = self.levels(vec![item_th.clone()]).await?.remove(&item_th.to_string()).unwrap_or_default();
+
+        let mut query = Query::transaction();
+        let th = query.bind(item_th.clone());
+        let mut changes = String::new();
+        // A unit that doesn't convert (count to g, say) keeps the numbers as they are
+        if let Ok(factor) = units::factor(&levels.unit, unit.name) {
+            if factor != 1.0 {
+                changes += &scale_statement(&mut query, &th, factor);
+                levels = levels.scaled(factor);
+            }
         }
-        sql += "COMMIT TRANSACTION;";
-        let _ = self.execute(&sql, None).await?;
-        Ok(AffectedRows { rows_affected: data.len() })
+        changes += &level_statements(&mut query, &item_th, &th, &levels, &levels.plan_set(units::round(item.stock)), None);
+        let name = query.bind(item.name);
+        let category = query.bind(item.category);
+        let desired_stock = query.bind(units::round(item.desired_stock));
+        let unit = query.bind(unit.name);
+        let packages = query.bind(Value::Array(Array::from(levels.packages.iter().map(Value::from).collect::<Vec<_>>())));
+        let track_general = query.bind(item.track_general.unwrap_or(false));
+        let update = format!("{changes} UPDATE items SET name = {name}, category = {category}, stock = {}, desired_stock = {desired_stock}, unit = {unit}, packages = {packages}, track_general = {track_general}, last_updated = time::now() WHERE id = {th}", total(&th));
+        let sql = recorded(&mut query, &th, &update, Operation::Change, source);
+        query.push(sql);
+        let (sql, vars) = query.commit();
+        let res = self.execute(&sql, Some(vars)).await?;
+
+        found_recorded_item(res, id)
     }
 
-    pub async fn delete_item(&self, id: &str) -> Result<AffectedRows, crate::error::Error> {
-        let sql = "DELETE $th";
-        let tid = format!("{}", id);
-        let vars: BTreeMap<String, Value> = map!(
-            "th".into() => thing(&tid)?.into()
-        );
-        let _ = self.execute(sql, Some(vars)).await?;
+    pub async fn delete_item(&self, id: &str, source: &Source) -> Result<AffectedRows, crate::error::Error> {
+        let mut query = Query::transaction();
+        let th = query.bind(item_thing(id)?);
+        let delete = format!("DELETE stock_levels WHERE item = {th}; DELETE lots WHERE item = {th}; DELETE shopping_extras WHERE item = {th}; DELETE items WHERE id = {th} RETURN BEFORE");
+        let sql = recorded(&mut query, &th, &delete, Operation::Delete, source);
+        query.push(sql);
+        let (sql, vars) = query.commit();
+        let res = self.execute(&sql, Some(vars)).await?;
+        let _ = found_recorded_item(res, id)?;
 
         Ok(AffectedRows { rows_affected: 1 })
     }
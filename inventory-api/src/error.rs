@@ -1,13 +1,104 @@
 // from: https://github.com/rust-awesome-app/template-app-base/blob/main/src-tauri/src/error.rs
 
+use log::error;
+use rocket::{http::Status, response::{self, Responder}, serde::json::Json, Request};
+use serde::Serialize;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Value not of type '{0}'")]
     XValueNotOfType(&'static str),
 
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
     #[error(transparent)]
     Surreal(#[from] surrealdb::err::Error),
 
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
+
+impl Error {
+    pub fn status(&self) -> Status {
+        match self {
+            Error::NotFound(_) => Status::NotFound,
+            Error::Validation(_) => Status::BadRequest,
+            Error::Conflict(_) => Status::Conflict,
+            // A write broke one of the field assertions in schema.rs
+            Error::Surreal(surrealdb::err::Error::FieldValue { .. } | surrealdb::err::Error::FieldCheck { .. }) => Status::BadRequest,
+            _ => Status::InternalServerError,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not_found",
+            Error::Validation(_) => "validation",
+            Error::Conflict(_) => "conflict",
+            Error::Surreal(surrealdb::err::Error::FieldValue { .. } | surrealdb::err::Error::FieldCheck { .. }) => "validation",
+            _ => "internal",
+        }
+    }
+}
+
+/// The JSON body sent with every error response.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if status == Status::InternalServerError {
+            error!("{} {} failed: {}", req.method(), req.uri(), self);
+        }
+        let body = ErrorBody { status: status.code, kind: self.kind(), message: self.to_string() };
+        (status, Json(body)).respond_to(req)
+    }
+}
+
+#[catch(400)]
+pub fn bad_request(req: &Request) -> Error {
+    Error::Validation(format!("Malformed request to {} {}", req.method(), req.uri()))
+}
+
+#[catch(404)]
+pub fn not_found(req: &Request) -> Error {
+    Error::NotFound(format!("No route matches {} {}", req.method(), req.uri()))
+}
+
+/// Rocket answers with 422 when a data or form guard fails, e.g. a body that
+/// isn't the JSON a route expects. A path segment that can't be parsed, like a
+/// non-numeric stock count, forwards instead and ends up at [`not_found`].
+#[catch(422)]
+pub fn unprocessable(req: &Request) -> (Status, Json<ErrorBody>) {
+    let body = ErrorBody {
+        status: Status::UnprocessableEntity.code,
+        kind: "validation",
+        message: format!("The body or parameters of {} {} could not be understood", req.method(), req.uri()),
+    };
+    (Status::UnprocessableEntity, Json(body))
+}
+
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request) -> (Status, Json<ErrorBody>) {
+    let body = ErrorBody {
+        status: status.code,
+        kind: if status.code < 500 { "validation" } else { "internal" },
+        message: format!("{} {} failed: {}", req.method(), req.uri(), status.reason_lossy()),
+    };
+    (status, Json(body))
+}
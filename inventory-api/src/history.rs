@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Datetime, Object, Value};
+
+use crate::{db::{all_results, DB}, ledger::Operation, prelude::{Error, W}, query::{item_thing, Query}, units::DEFAULT_UNIT};
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 500;
+
+/// Query string of `GET /history`. Every filter is optional, dates are RFC 3339 and pages start at 1.
+#[derive(Debug, Default, FromForm)]
+pub struct HistoryFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub item: Option<String>,
+    pub category: Option<String>,
+    pub operation: Option<Operation>,
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+/// A single change of stock, as written to `stock_events` by [`crate::ledger::recorded`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StockEvent {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    pub category: String,
+    /// The item's unit when the change was made, events from before units existed are in `count`
+    pub unit: String,
+    pub delta: f64,
+    pub stock: f64,
+    pub operation: String,
+    pub source: String,
+    pub time: DateTime<Utc>,
+}
+
+impl TryFrom<W<Value>> for StockEvent {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let unit: Option<String> = W(map.get("unit").cloned().unwrap_or_default()).try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            item: W(map["item"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            category: W(map["category"].clone()).try_into()?,
+            unit: unit.unwrap_or_else(|| DEFAULT_UNIT.to_owned()),
+            delta: W(map["delta"].clone()).try_into()?,
+            stock: W(map["stock"].clone()).try_into()?,
+            operation: W(map["operation"].clone()).try_into()?,
+            source: W(map["source"].clone()).try_into()?,
+            time: W(map["time"].clone()).try_into()?,
+        })
+    }
+}
+
+/// One page of history, newest first, with the total number of events matching the filters.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub events: Vec<StockEvent>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+fn parse_time(name: &str, value: &str) -> Result<Datetime, Error> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|time| Datetime::from(time.with_timezone(&Utc)))
+        .map_err(|_| Error::Validation(format!("'{}' is not a valid {} time, expected RFC 3339", value, name)))
+}
+
+impl DB {
+    pub async fn history(&self, filter: HistoryFilter) -> Result<HistoryPage, Error> {
+        let page = filter.page.unwrap_or(1).max(1);
+        let per_page = filter.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+        let mut query = Query::default();
+        let mut conditions = vec![];
+        if let Some(from) = filter.from.as_deref() {
+            conditions.push(format!("time >= {}", query.bind(parse_time("from", from)?)));
+        }
+        if let Some(to) = filter.to.as_deref() {
+            conditions.push(format!("time <= {}", query.bind(parse_time("to", to)?)));
+        }
+        if let Some(item) = filter.item.as_deref() {
+            conditions.push(format!("item = {}", query.bind(item_thing(item)?)));
+        }
+        if let Some(category) = filter.category {
+            conditions.push(format!("category = {}", query.bind(category)));
+        }
+        if let Some(operation) = filter.operation {
+            conditions.push(format!("operation = {}", query.bind(operation.as_str())));
+        }
+        let condition = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+        let limit = query.bind(per_page as i64);
+        // A page far past the end just comes back empty, rather than overflowing
+        let start = query.bind(page.saturating_sub(1).saturating_mul(per_page).min(i64::MAX as usize) as i64);
+        query.push(format!("SELECT * FROM stock_events {condition} ORDER BY time DESC LIMIT {limit} START {start}"));
+        query.push(format!("SELECT count() FROM stock_events {condition} GROUP ALL"));
+        let (sql, vars) = query.commit();
+
+        let mut results = all_results(self.execute(&sql, Some(vars)).await?)?.into_iter();
+        let events: Array = W(results.next().unwrap_or_default()).try_into()?;
+        let events = events.into_iter().map(|event| W(event).try_into()).collect::<Result<Vec<_>, _>>()?;
+        // GROUP ALL returns nothing at all rather than a count of 0 when no event matches
+        let total: i64 = match results.next().unwrap_or_default().first() {
+            Value::Object(count) => W(count.get("count").cloned().unwrap_or_default()).try_into().unwrap_or(0),
+            _ => 0,
+        };
+
+        Ok(HistoryPage { events, page, per_page, total: total as usize })
+    }
+}
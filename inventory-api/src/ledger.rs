@@ -0,0 +1,113 @@
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+
+use crate::{query::Query, units::{DEFAULT_UNIT, PRECISION}};
+
+/// The kind of change a `stock_events` record describes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    #[field(value = "restock")]
+    Restock,
+    #[field(value = "consume")]
+    Consume,
+    #[field(value = "change")]
+    Change,
+    #[field(value = "reinventory")]
+    Reinventory,
+    #[field(value = "delete")]
+    Delete,
+}
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Restock => "restock",
+            Operation::Consume => "consume",
+            Operation::Change => "change",
+            Operation::Reinventory => "reinventory",
+            Operation::Delete => "delete",
+        }
+    }
+}
+
+/// Who made a change, taken from the `X-Inventory-Source` header. Requests without one are recorded as `api`.
+#[derive(Debug, Clone)]
+pub struct Source(pub String);
+
+impl Default for Source {
+    fn default() -> Self {
+        Source("api".to_owned())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Source {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let source = req.headers()
+            .get_one("X-Inventory-Source")
+            .map(str::trim)
+            .filter(|s| !s.is_empty() && s.len() <= 64)
+            .map_or_else(Source::default, |s| Source(s.to_owned()));
+        Outcome::Success(source)
+    }
+}
+
+/// Surrounds `update`, a statement that may change the stock of the single item `th`, so any change it
+/// makes is written to `stock_events` in the same transaction. The item's name, category and unit are copied
+/// into the event so history still reads well after the item is renamed or deleted. Statements that leave the stock alone,
+/// or match no item, record nothing. Deleting an item that exists is always recorded, even with no stock left, so the
+/// deletion shows in its history.
+///
+/// The `LET` and `IF` statements added return no records, so the update's result is still the only
+/// array of items in the response.
+pub fn recorded(query: &mut Query, th: &str, update: &str, operation: Operation, source: &Source) -> String {
+    let before = query.local();
+    let after = query.local();
+    let recorded_if = match operation {
+        Operation::Delete => format!("{before} != NONE"),
+        _ => format!("{before} != NONE AND {after} != {before}.stock"),
+    };
+    let operation = query.bind(operation.as_str());
+    let source = query.bind(source.0.as_str());
+    let unit = query.bind(DEFAULT_UNIT);
+    let update = update.trim().trim_end_matches(';');
+    format!("
+        LET {before} = array::first((SELECT name, category, unit, stock FROM items WHERE id = {th}));
+        {update};
+        LET {after} = array::first((SELECT VALUE stock FROM items WHERE id = {th})) ?? 0;
+        IF {recorded_if} THEN
+            (CREATE stock_events SET item = {th}, name = {before}.name, category = {before}.category, unit = {before}.unit ?? {unit}, delta = math::fixed({after} - {before}.stock, {PRECISION}), stock = {after}, operation = {operation}, source = {source}, time = time::now() RETURN NONE)
+        END;
+    ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::DB, history::HistoryFilter};
+
+    async fn operations(db: &DB, item: &str) -> Vec<String> {
+        let filter = HistoryFilter { item: Some(item.to_owned()), ..Default::default() };
+        db.history(filter).await.unwrap().events.into_iter().map(|event| event.operation).collect()
+    }
+
+    #[tokio::test]
+    async fn records_deleting_an_item_without_stock() {
+        let db = DB::memory().await;
+        let item = db.add_item("Salt", "Spices", "count").await.unwrap().id.unwrap();
+        db.delete_item(&item, &Source::default()).await.unwrap();
+        assert_eq!(operations(&db, &item).await, ["delete"]);
+    }
+
+    #[tokio::test]
+    async fn records_only_changes_of_stock() {
+        let db = DB::memory().await;
+        let item = db.add_item("Rice", "Grains", "count").await.unwrap().id.unwrap();
+        db.restock_item(&item, 2.0, &Source::default()).await.unwrap();
+        db.set_desired_stock(&item, 4.0).await.unwrap();
+        db.delete_item(&item, &Source::default()).await.unwrap();
+        assert_eq!(operations(&db, &item).await, ["delete", "restock"]);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use surrealdb::{dbs::Response, sql::{Array, Id, Object, Thing, Value}};
+
+use chrono::{DateTime, Utc};
+
+use crate::{db::{all_results, first_result, AffectedRows, DB}, lots::{clear_empty_lots, draw_lots, new_lot, HeldLot}, prelude::{Error, W}, query::{item_thing, record_thing, Query}, packages::{packages_of, Package}, units::{self, DEFAULT_UNIT, PRECISION}, utils::macros::map};
+
+/// A place stock is kept, e.g. the pantry or the chest freezer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Location {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+}
+
+impl TryFrom<W<Value>> for Location {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            position: W(map["position"].clone()).try_into()?,
+        })
+    }
+}
+
+/// Body of `POST /location` and `PATCH /location/<id>`.
+#[derive(Debug, Deserialize)]
+pub struct LocationInput {
+    pub name: String,
+}
+
+/// How much of an item is kept at a location.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StockLevel {
+    pub item: String,
+    pub location: String,
+    pub stock: f64,
+}
+
+impl TryFrom<W<Value>> for StockLevel {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            item: W(map["item"].clone()).try_into()?,
+            location: W(map["location"].clone()).try_into()?,
+            stock: W(map["stock"].clone()).try_into()?,
+        })
+    }
+}
+
+/// Moving `count` of item `id` from one location to another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub count: f64,
+}
+
+/// The location stock goes to when a client doesn't name one. It is created on first start
+/// under this fixed id, so it can be renamed but never lost.
+pub fn default_location() -> Thing {
+    Thing::from(("locations", "default"))
+}
+
+pub fn location_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("locations", id)
+}
+
+/// Each (item, location) pair has a single `stock_levels` record with an id made of the two,
+/// so `UPDATE` on it creates the record the first time stock arrives there.
+fn level_thing(item: &Thing, location: &Thing) -> Thing {
+    Thing {
+        tb: "stock_levels".to_owned(),
+        id: Id::Array(Array::from(vec![Value::from(item.clone()), Value::from(location.clone())])),
+    }
+}
+
+/// A statement changing only the `stock_levels` record of item `th` at `location`, leaving its lots as they are.
+pub fn level_change(query: &mut Query, item: &Thing, th: &str, location: &Thing, change: f64) -> String {
+    let level = query.bind(level_thing(item, location));
+    let amount = query.bind(change.abs());
+    if change > 0.0 {
+        let location = query.bind(location.clone());
+        format!("UPDATE {level} SET item = {th}, location = {location}, stock = math::fixed((stock ?? 0) + {amount}, {PRECISION}) RETURN NONE;")
+    } else {
+        format!("UPDATE stock_levels SET stock = math::fixed(stock - {amount}, {PRECISION}) WHERE id = {level} RETURN NONE;")
+    }
+}
+
+/// Statements changing item `th`'s stock at each location by the amount given. Follow them
+/// with an update setting `items.stock` to [`total`] so the item's stock stays the sum.
+/// Levels are rounded on every change, so taking everything out leaves exactly zero.
+/// Stock added arrives as a new lot best used by `best_by`, stock taken comes out of the
+/// location's lots in [`Levels::fifo`] order.
+pub fn level_statements(query: &mut Query, item: &Thing, th: &str, levels: &Levels, changes: &[(Thing, f64)], best_by: Option<DateTime<Utc>>) -> String {
+    let mut sql = String::new();
+    let mut drawn = false;
+    for (location, change) in changes.iter().filter(|(_, change)| *change != 0.0) {
+        sql += &level_change(query, item, th, location, *change);
+        if *change > 0.0 {
+            sql += &new_lot(query, th, location, *change, best_by, None);
+        } else {
+            sql += &draw_lots(query, &levels.draw(Some(location), -change));
+            drawn = true;
+        }
+    }
+    if drawn {
+        sql += &clear_empty_lots(th);
+    }
+    sql
+}
+
+/// Statements converting every level and lot of item `th` by `factor`, for when the item changes unit.
+pub fn scale_statement(query: &mut Query, th: &str, factor: f64) -> String {
+    let factor = query.bind(factor);
+    format!("UPDATE stock_levels SET stock = math::fixed(stock * {factor}, {PRECISION}) WHERE item = {th} RETURN NONE;\
+        UPDATE lots SET stock = math::fixed(stock * {factor}, {PRECISION}) WHERE item = {th} RETURN NONE;")
+}
+
+/// SurrealQL for the sum of item `th`'s stock over every location.
+pub fn total(th: &str) -> String {
+    format!("math::fixed(math::sum((SELECT VALUE stock FROM stock_levels WHERE item = {th})), {PRECISION})")
+}
+
+/// An item's stock as stored on the item, the unit and packages it's counted in, how much of it
+/// is at each location and the lots that stock is made of.
+#[derive(Debug, Clone)]
+pub struct Levels {
+    pub stock: f64,
+    pub unit: String,
+    pub packages: Vec<Package>,
+    pub at: Vec<(Thing, f64)>,
+    pub lots: Vec<HeldLot>,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self { stock: 0.0, unit: units::default_unit(), packages: vec![], at: vec![], lots: vec![] }
+    }
+}
+
+impl Levels {
+    pub fn at(&self, location: &Thing) -> f64 {
+        self.at.iter().find(|(at, _)| at == location).map_or(0.0, |(_, stock)| *stock)
+    }
+
+    /// Where to take `count` from when no location was named: wherever the lot expiring soonest is,
+    /// see [`Levels::fifo`]. Stock no lot accounts for goes next, from the default location first,
+    /// then whichever holds the most. Plans to take less than `count` when there isn't enough.
+    pub fn plan_consumption(&self, count: f64) -> Vec<(Thing, f64)> {
+        let mut plan: Vec<(Thing, f64)> = vec![];
+        let mut remaining = count;
+        for (lot, take) in self.draw(None, count) {
+            let take = take.min(units::round(self.at(&lot.location) - planned(&plan, &lot.location)));
+            if take > 0.0 {
+                plan_take(&mut plan, &lot.location, take);
+                remaining = units::round(remaining - take);
+            }
+        }
+
+        let default = default_location();
+        let mut at = self.at.clone();
+        at.sort_by(|(a, a_stock), (b, b_stock)| (*a != default).cmp(&(*b != default)).then(b_stock.total_cmp(a_stock)));
+        for (location, stock) in at {
+            if remaining <= 0.0 {
+                break;
+            }
+            let take = units::round(stock - planned(&plan, &location)).min(remaining);
+            if take > 0.0 {
+                plan_take(&mut plan, &location, take);
+                remaining = units::round(remaining - take);
+            }
+        }
+        plan
+    }
+
+    /// Changes bringing the stock to `stock`, adding to the default location or taking as in [`Levels::plan_consumption`].
+    pub fn plan_set(&self, stock: f64) -> Vec<(Thing, f64)> {
+        let located: f64 = self.at.iter().map(|(_, stock)| stock).sum();
+        match units::round(stock - located) {
+            difference if difference > 0.0 => vec![(default_location(), difference)],
+            difference => self.plan_consumption(-difference),
+        }
+    }
+
+    /// The same levels with every quantity and package size multiplied by `factor`, as after [`scale_statement`].
+    pub fn scaled(&self, factor: f64) -> Levels {
+        Levels {
+            stock: units::round(self.stock * factor),
+            unit: self.unit.clone(),
+            packages: self.packages.iter().map(|package| Package { name: package.name.clone(), size: units::round(package.size * factor) }).collect(),
+            at: self.at.iter().map(|(location, stock)| (location.clone(), units::round(stock * factor))).collect(),
+            lots: self.lots.iter().map(|lot| HeldLot { stock: units::round(lot.stock * factor), ..lot.clone() }).collect(),
+        }
+    }
+}
+
+/// How much a consumption plan already takes from `location`.
+fn planned(plan: &[(Thing, f64)], location: &Thing) -> f64 {
+    plan.iter().find(|(at, _)| at == location).map_or(0.0, |(_, change)| -change)
+}
+
+fn plan_take(plan: &mut Vec<(Thing, f64)>, location: &Thing, take: f64) {
+    match plan.iter_mut().find(|(at, _)| at == location) {
+        Some((_, change)) => *change = units::round(*change - take),
+        None => plan.push((location.clone(), -take)),
+    }
+}
+
+fn found_location(res: Vec<Response>, id: &str) -> Result<Location, Error> {
+    match first_result(res)?.first() {
+        Value::None => Err(Error::NotFound(format!("No location with id {}", id))),
+        value => W(value).try_into(),
+    }
+}
+
+impl DB {
+    /// Creates the default location on first start and puts any stock not yet at a location there,
+    /// for data written before locations existed.
+    pub async fn init_locations(&self, default_name: &str) -> Result<(), Error> {
+        let sql = "SELECT * FROM $default";
+        let vars: BTreeMap<String, Value> = map!("default".into() => default_location().into());
+        if first_result(self.execute(sql, Some(vars)).await?)?.first().is_none() {
+            let sql = "CREATE $default SET name = $name, position = 0";
+            let vars: BTreeMap<String, Value> = map!(
+                "default".into() => default_location().into(),
+                "name".into() => Value::from(default_name.trim())
+            );
+            let _ = first_result(self.execute(sql, Some(vars)).await?)?;
+            info!(target: "database", "Created default location {}", default_name);
+        }
+
+        let sql = format!("SELECT id, stock, {} AS located FROM items", total("$parent.id"));
+        let items: Array = W(first_result(self.execute(&sql, None).await?)?).try_into()?;
+        let mut query = Query::transaction();
+        let mut moved = 0;
+        for record in items {
+            let record: Object = W(record).try_into()?;
+            let stock: f64 = W(record.get("stock").cloned().unwrap_or_default()).try_into()?;
+            let located: f64 = W(record.get("located").cloned().unwrap_or_default()).try_into().unwrap_or(0.0);
+            if stock == located {
+                continue;
+            }
+            let Some(Value::Thing(item)) = record.get("id").cloned() else { continue };
+            let th = query.bind(item.clone());
+            // More located than the item says it has can't be placed anywhere, so the item takes the located total
+            let changes = match stock > located {
+                true => level_statements(&mut query, &item, &th, &Levels::default(), &[(default_location(), units::round(stock - located))], None),
+                false => String::new(),
+            };
+            query.push(format!("{changes} UPDATE items SET stock = {} WHERE id = {th} RETURN NONE", total(&th)));
+            moved += 1;
+        }
+        if moved > 0 {
+            let (sql, vars) = query.commit();
+            let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+            info!(target: "database", "Moved unlocated stock of {} items to the default location", moved);
+        }
+        Ok(())
+    }
+
+    /// Stock of every item in `things` that exists, overall and per location, by item id.
+    pub async fn levels(&self, things: Vec<Thing>) -> Result<BTreeMap<String, Levels>, Error> {
+        let sql = "SELECT id, stock, unit, packages, (SELECT location, stock FROM stock_levels WHERE item = $parent.id) AS levels, \
+            (SELECT id, location, stock, best_by, received FROM lots WHERE item = $parent.id) AS lots FROM items WHERE id INSIDE $ids";
+        let ids: Vec<Value> = things.into_iter().map(Value::from).collect();
+        let vars: BTreeMap<String, Value> = map!("ids".into() => Value::Array(Array::from(ids)));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        let mut levels = BTreeMap::new();
+        for record in array {
+            let record: Object = W(record).try_into()?;
+            let id: String = W(record.get("id").cloned().unwrap_or_default()).try_into()?;
+            let unit: Option<String> = W(record.get("unit").cloned().unwrap_or_default()).try_into()?;
+            let mut item = Levels {
+                stock: W(record.get("stock").cloned().unwrap_or_default()).try_into().unwrap_or(0.0),
+                unit: unit.unwrap_or_else(|| DEFAULT_UNIT.to_owned()),
+                packages: packages_of(record.get("packages"))?,
+                at: vec![],
+                lots: vec![],
+            };
+            if let Some(Value::Array(at)) = record.get("levels") {
+                for level in at.iter() {
+                    let level: Object = W(level.clone()).try_into()?;
+                    if let Some(Value::Thing(location)) = level.get("location") {
+                        let stock: f64 = W(level.get("stock").cloned().unwrap_or_default()).try_into().unwrap_or(0.0);
+                        item.at.push((location.clone(), stock));
+                    }
+                }
+            }
+            if let Some(Value::Array(lots)) = record.get("lots") {
+                item.lots = lots.iter().map(|lot| W(lot.clone()).try_into()).collect::<Result<_, _>>()?;
+            }
+            levels.insert(id, item);
+        }
+        Ok(levels)
+    }
+
+    pub async fn get_locations(&self) -> Result<Vec<Location>, Error> {
+        let sql = "SELECT * FROM locations ORDER BY position ASC, name ASC";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    /// Parses a location id, failing with not found if there's no such location.
+    pub async fn existing_location(&self, id: &str) -> Result<Thing, Error> {
+        let th = location_thing(id)?;
+        let sql = "SELECT * FROM $th";
+        let vars: BTreeMap<String, Value> = map!("th".into() => th.clone().into());
+        let _ = found_location(self.execute(sql, Some(vars)).await?, id)?;
+        Ok(th)
+    }
+
+    async fn check_location_name_free(&self, name: &str, except: Option<&Thing>) -> Result<(), Error> {
+        if name.is_empty() {
+            return Err(Error::Validation("A location name is required".into()));
+        }
+        let sql = "SELECT id FROM locations WHERE name = $name AND id != $except";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(name),
+            "except".into() => except.map_or(Value::None, |th| th.clone().into())
+        );
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::None => Ok(()),
+            _ => Err(Error::Conflict(format!("A location named {} already exists", name))),
+        }
+    }
+
+    pub async fn create_location(&self, input: LocationInput) -> Result<Location, Error> {
+        let name = input.name.trim();
+        self.check_location_name_free(name, None).await?;
+        let sql = "CREATE locations SET name = $name, position = (math::max((SELECT VALUE position FROM locations)) ?? -1) + 1";
+        let vars: BTreeMap<String, Value> = map!("name".into() => Value::from(name));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_location(res, name)
+    }
+
+    pub async fn rename_location(&self, id: &str, input: LocationInput) -> Result<Location, Error> {
+        let th = location_thing(id)?;
+        let name = input.name.trim();
+        self.check_location_name_free(name, Some(&th)).await?;
+        let sql = "UPDATE locations SET name = $name WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => th.into(),
+            "name".into() => Value::from(name)
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_location(res, id)
+    }
+
+    /// Deletes a location with nothing left in it. The default location can't be deleted.
+    pub async fn delete_location(&self, id: &str) -> Result<AffectedRows, Error> {
+        let th = location_thing(id)?;
+        if th == default_location() {
+            return Err(Error::Conflict("The default location can not be deleted, rename it instead".into()));
+        }
+        let sql = "SELECT VALUE stock FROM stock_levels WHERE location = $th AND stock > 0";
+        let vars: BTreeMap<String, Value> = map!("th".into() => th.clone().into());
+        let stocked: Array = W(first_result(self.execute(sql, Some(vars)).await?)?).try_into()?;
+        if !stocked.is_empty() {
+            return Err(Error::Conflict(format!("{} items are still kept there, transfer or consume them first", stocked.len())));
+        }
+
+        let mut query = Query::transaction();
+        let th = query.bind(th);
+        query.push(format!("DELETE stock_levels WHERE location = {th}"));
+        query.push(format!("DELETE lots WHERE location = {th}"));
+        query.push(format!("DELETE locations WHERE id = {th} RETURN BEFORE"));
+        let (sql, vars) = query.commit();
+        let deleted = all_results(self.execute(&sql, Some(vars)).await?)?;
+        match deleted.last().map(Value::first) {
+            Some(Value::Object(_)) => Ok(AffectedRows { rows_affected: 1 }),
+            _ => Err(Error::NotFound(format!("No location with id {}", id))),
+        }
+    }
+
+    /// Stock at each location, for one item or for all of them.
+    pub async fn get_stock_levels(&self, item: Option<&str>) -> Result<Vec<StockLevel>, Error> {
+        let (sql, vars) = match item {
+            Some(item) => {
+                let vars: BTreeMap<String, Value> = map!("item".into() => item_thing(item)?.into());
+                ("SELECT * FROM stock_levels WHERE item = $item ORDER BY location", Some(vars))
+            },
+            None => ("SELECT * FROM stock_levels ORDER BY item, location", None),
+        };
+        let res = self.execute(sql, vars).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+}
@@ -7,4 +7,11 @@ pub fn log_vec(input: Vec<impl std::fmt::Display>) -> String {
     }
     let _ = result.pop();
     result
-}
\ No newline at end of file
+}
+
+/// The entries of a batch that were actually applied, so rejected or missing ids never reach the logs.
+pub fn applied<'a>(data: &'a [crate::RestockItem], result: &crate::batch::BatchResult) -> Vec<&'a crate::RestockItem> {
+    data.iter()
+        .filter(|item| result.applied.iter().any(|applied| applied.id.eq(&item.id)))
+        .collect()
+}
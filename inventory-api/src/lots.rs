@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::info;
+use serde::{Deserialize, Deserializer, Serialize};
+use surrealdb::sql::{Array, Datetime, Object, Thing, Value};
+
+use crate::{db::{all_results, first_result, DB}, locations::{default_location, Levels}, prelude::{Error, W}, query::{item_thing, Query}, units::{self, PRECISION}, utils::macros::map};
+
+const DEFAULT_EXPIRING_DAYS: i64 = 7;
+
+/// Stock of an item that arrived together at one location, with the date it's best used by.
+/// An item's lots at a location always add up to its `stock_levels` record there.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lot {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    pub unit: String,
+    pub location: String,
+    pub location_name: String,
+    pub stock: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_by: Option<DateTime<Utc>>,
+    pub received: DateTime<Utc>,
+}
+
+impl TryFrom<W<Value>> for Lot {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let unit: Option<String> = W(map.get("unit").cloned().unwrap_or_default()).try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            item: W(map["item"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            unit: unit.unwrap_or_else(units::default_unit),
+            location: W(map["location"].clone()).try_into()?,
+            location_name: W(map["location_name"].clone()).try_into()?,
+            stock: W(map["stock"].clone()).try_into()?,
+            best_by: optional_time(map.get("best_by"))?,
+            received: W(map["received"].clone()).try_into()?,
+        })
+    }
+}
+
+/// A lot as read before a batch, for working out which lots stock is taken from.
+#[derive(Debug, Clone)]
+pub struct HeldLot {
+    pub id: Thing,
+    pub location: Thing,
+    pub stock: f64,
+    pub best_by: Option<DateTime<Utc>>,
+    pub received: DateTime<Utc>,
+}
+
+impl TryFrom<W<Value>> for HeldLot {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let (Some(Value::Thing(id)), Some(Value::Thing(location))) = (map.get("id"), map.get("location")) else {
+            return Err(Error::XValueNotOfType("Thing"));
+        };
+        Ok(Self {
+            id: id.clone(),
+            location: location.clone(),
+            stock: W(map["stock"].clone()).try_into()?,
+            best_by: optional_time(map.get("best_by"))?,
+            received: W(map["received"].clone()).try_into()?,
+        })
+    }
+}
+
+pub(crate) fn optional_time(value: Option<&Value>) -> Result<Option<DateTime<Utc>>, Error> {
+    match value {
+        None | Some(Value::None) | Some(Value::Null) => Ok(None),
+        Some(value) => Ok(Some(W(value.clone()).try_into()?)),
+    }
+}
+
+fn optional_datetime(time: Option<DateTime<Utc>>) -> Value {
+    time.map_or(Value::None, |time| Value::Datetime(Datetime::from(time)))
+}
+
+/// Reads a best-by date, either a plain `YYYY-MM-DD` (the start of that day in UTC) or an RFC 3339 time.
+pub fn parse_best_by(value: &str) -> Result<DateTime<Utc>, Error> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| Error::Validation(format!("'{}' is not a valid best-by date, expected YYYY-MM-DD or RFC 3339", value)))
+}
+
+/// For `#[serde(deserialize_with)]` on optional best-by dates in request bodies, see [`parse_best_by`].
+pub fn deserialize_best_by<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) if !value.trim().is_empty() => parse_best_by(&value).map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+impl Levels {
+    /// The item's lots in the order they should be used: soonest best-by first, lots without one last.
+    /// Ties go to the default location, then to whatever arrived first.
+    pub fn fifo(&self, location: Option<&Thing>) -> Vec<&HeldLot> {
+        let default = default_location();
+        let mut lots: Vec<&HeldLot> = self.lots.iter()
+            .filter(|lot| lot.stock > 0.0 && location.is_none_or(|location| lot.location == *location))
+            .collect();
+        lots.sort_by_key(|lot| (lot.best_by.is_none(), lot.best_by, lot.location != default, lot.received));
+        lots
+    }
+
+    /// The lots to take `count` from, and how much from each, in [`Levels::fifo`] order.
+    pub fn draw(&self, location: Option<&Thing>, count: f64) -> Vec<(&HeldLot, f64)> {
+        let mut remaining = count;
+        let mut draws = vec![];
+        for lot in self.fifo(location) {
+            if remaining <= 0.0 {
+                break;
+            }
+            let take = lot.stock.min(remaining);
+            draws.push((lot, take));
+            remaining = units::round(remaining - take);
+        }
+        draws
+    }
+
+    /// Takes lots out as [`Levels::draw`] plans, so stock planned once can't be planned again in the same batch.
+    pub fn take(&mut self, location: &Thing, count: f64) -> Vec<(HeldLot, f64)> {
+        let draws: Vec<(HeldLot, f64)> = self.draw(Some(location), count)
+            .into_iter()
+            .map(|(lot, take)| (lot.clone(), take))
+            .collect();
+        for (drawn, take) in draws.iter() {
+            if let Some(lot) = self.lots.iter_mut().find(|lot| lot.id == drawn.id) {
+                lot.stock = units::round(lot.stock - take);
+            }
+        }
+        draws
+    }
+}
+
+/// A statement creating a lot of `amount` at `location`. Lots moved from elsewhere keep when they were `received`.
+pub fn new_lot(query: &mut Query, th: &str, location: &Thing, amount: f64, best_by: Option<DateTime<Utc>>, received: Option<DateTime<Utc>>) -> String {
+    let location = query.bind(location.clone());
+    let amount = query.bind(amount);
+    let best_by = query.bind(optional_datetime(best_by));
+    let received = match received {
+        Some(received) => query.bind(Datetime::from(received)),
+        None => "time::now()".to_owned(),
+    };
+    format!("CREATE lots SET item = {th}, location = {location}, stock = {amount}, best_by = {best_by}, received = {received} RETURN NONE;")
+}
+
+/// Statements taking stock out of lots. Follow them with [`clear_empty_lots`].
+pub fn draw_lots(query: &mut Query, draws: &[(&HeldLot, f64)]) -> String {
+    let mut sql = String::new();
+    for (lot, take) in draws.iter().filter(|(_, take)| *take > 0.0) {
+        let lot = query.bind(lot.id.clone());
+        let take = query.bind(*take);
+        sql += &format!("UPDATE lots SET stock = math::fixed(stock - {take}, {PRECISION}) WHERE id = {lot} RETURN NONE;");
+    }
+    sql
+}
+
+pub fn clear_empty_lots(th: &str) -> String {
+    format!("DELETE lots WHERE item = {th} AND stock <= 0 RETURN NONE;")
+}
+
+impl DB {
+    /// Puts stock at a location that no lot accounts for into a lot without a best-by date,
+    /// for data written before lots existed.
+    pub async fn init_lots(&self) -> Result<(), Error> {
+        let sql = "SELECT item, location, stock, math::sum((SELECT VALUE stock FROM lots WHERE item = $parent.item AND location = $parent.location)) AS in_lots FROM stock_levels";
+        let levels: Array = W(first_result(self.execute(sql, None).await?)?).try_into()?;
+        let mut query = Query::transaction();
+        let mut lotted = 0;
+        for level in levels {
+            let level: Object = W(level).try_into()?;
+            let stock: f64 = W(level.get("stock").cloned().unwrap_or_default()).try_into().unwrap_or(0.0);
+            let in_lots: f64 = W(level.get("in_lots").cloned().unwrap_or_default()).try_into().unwrap_or(0.0);
+            let (Some(Value::Thing(item)), Some(Value::Thing(location))) = (level.get("item"), level.get("location")) else { continue };
+            if units::round(stock - in_lots) <= 0.0 {
+                continue;
+            }
+            let th = query.bind(item.clone());
+            let sql = new_lot(&mut query, &th, location, units::round(stock - in_lots), None, None);
+            query.push(sql);
+            lotted += 1;
+        }
+        if lotted > 0 {
+            let (sql, vars) = query.commit();
+            let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+            info!(target: "database", "Put stock of {} item locations into lots", lotted);
+        }
+        Ok(())
+    }
+
+    /// Every lot, or those of one item, by best-by date.
+    pub async fn get_lots(&self, item: Option<&str>) -> Result<Vec<Lot>, Error> {
+        let mut query = Query::default();
+        let condition = match item {
+            Some(item) => format!("WHERE item = {}", query.bind(item_thing(item)?)),
+            None => String::new(),
+        };
+        query.push(format!("SELECT *, item.name AS name, item.unit AS unit, location.name AS location_name FROM lots {condition} ORDER BY best_by ASC, received ASC"));
+        let (sql, vars) = query.commit();
+        let res = self.execute(&sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    /// Lots whose best-by date is within `days` from now, including those already past it.
+    pub async fn expiring_lots(&self, days: Option<i64>) -> Result<Vec<Lot>, Error> {
+        let days = days.unwrap_or(DEFAULT_EXPIRING_DAYS);
+        if days < 0 {
+            return Err(Error::Validation(format!("Days must not be negative, got {}", days)));
+        }
+        let before = Utc::now() + Duration::days(days);
+        let sql = "SELECT *, item.name AS name, item.unit AS unit, location.name AS location_name FROM lots WHERE best_by != NONE AND best_by <= $before AND stock > 0 ORDER BY best_by ASC";
+        let vars: BTreeMap<String, Value> = map!("before".into() => Value::Datetime(Datetime::from(before)));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc()
+    }
+
+    fn lot(id: &str, location: &Thing, stock: f64, best_by: Option<u32>, received: u32) -> HeldLot {
+        HeldLot { id: Thing::from(("lots", id)), location: location.clone(), stock, best_by: best_by.map(day), received: day(received) }
+    }
+
+    fn ids(lots: &[&HeldLot]) -> Vec<String> {
+        lots.iter().map(|lot| lot.id.id.to_raw()).collect()
+    }
+
+    #[test]
+    fn uses_soonest_best_by_first_and_undated_last() {
+        let here = default_location();
+        let levels = Levels { lots: vec![
+            lot("undated", &here, 1.0, None, 1),
+            lot("later", &here, 1.0, Some(20), 2),
+            lot("sooner", &here, 1.0, Some(10), 3),
+        ], ..Default::default() };
+        assert_eq!(ids(&levels.fifo(None)), ["sooner", "later", "undated"]);
+    }
+
+    #[test]
+    fn breaks_best_by_ties_by_location_then_arrival() {
+        let (here, pantry) = (default_location(), Thing::from(("locations", "pantry")));
+        let levels = Levels { lots: vec![
+            lot("pantry", &pantry, 1.0, Some(10), 1),
+            lot("newer", &here, 1.0, Some(10), 5),
+            lot("older", &here, 1.0, Some(10), 2),
+            lot("empty", &here, 0.0, Some(1), 1),
+        ], ..Default::default() };
+        assert_eq!(ids(&levels.fifo(None)), ["older", "newer", "pantry"]);
+        assert_eq!(ids(&levels.fifo(Some(&pantry))), ["pantry"]);
+    }
+
+    #[test]
+    fn draws_across_lots_in_fifo_order() {
+        let here = default_location();
+        let levels = Levels { lots: vec![
+            lot("later", &here, 3.0, Some(20), 1),
+            lot("sooner", &here, 1.5, Some(10), 2),
+            lot("undated", &here, 5.0, None, 1),
+        ], ..Default::default() };
+        let draws: Vec<(String, f64)> = levels.draw(None, 4.0).into_iter().map(|(lot, take)| (lot.id.id.to_raw(), take)).collect();
+        assert_eq!(draws, [("sooner".to_owned(), 1.5), ("later".to_owned(), 2.5)]);
+
+        let everything: f64 = levels.draw(None, 20.0).into_iter().map(|(_, take)| take).sum();
+        assert_eq!(everything, 9.5);
+    }
+
+    #[test]
+    fn takes_drawn_stock_out_of_its_lots() {
+        let here = default_location();
+        let mut levels = Levels { lots: vec![lot("a", &here, 2.0, Some(10), 1), lot("b", &here, 2.0, Some(12), 1)], ..Default::default() };
+        assert_eq!(levels.take(&here, 3.0).len(), 2);
+        assert_eq!(ids(&levels.fifo(None)), ["b"]);
+        assert_eq!(levels.draw(None, 5.0)[0].1, 1.0);
+    }
+}
@@ -1,31 +1,70 @@
 #[macro_use]
 extern crate rocket;
 
-use std::{env, io::ErrorKind, sync::Arc};
+use std::{env, sync::Arc};
 
+use config::InventoryConfig;
 use cors::CORS;
+use batch::BatchResult;
+use budgets::{Budget, BudgetInput, BudgetStatus};
+use categories::{Category, CategoryInput};
 use db::{AffectedRows, Item, DB};
-use rocket::{serde::json::Json, State};
+use error::Error;
+use history::{HistoryFilter, HistoryPage};
+use ledger::Source;
+use locations::{Location, LocationInput, StockLevel, Transfer};
+use lots::Lot;
+use meal_plan::{PlannedMeal, PlannedMealChange, PlannedMealInput, PlannedNeed};
+use packages::Package;
+use purchases::{PriceComparison, Purchase, SpendBy, SpendingReport};
+use recipes::{Cookable, Recipe, RecipeInput};
+use shopping::{Extra, ExtraInput, ShoppingList};
+use stores::{ItemStoreInput, Store, StoreInput};
+use trips::{FinishedTrip, Trip, TripInput, TripLine, TripLineChange};
+use rocket::{serde::json::Json, Build, Rocket, State};
 use serde::{Deserialize, Serialize};
 use surrealdb::{dbs::Session, kvs::Datastore};
+use units::Unit;
 use log::{self, info, warn};
 use log4rs;
 
+mod barcodes;
+mod batch;
+mod budgets;
+mod categories;
+mod config;
 mod db;
 mod error;
+mod history;
+mod ledger;
+mod locations;
+mod lots;
+mod meal_plan;
 mod prelude;
+mod purchases;
+mod recipes;
+mod query;
+mod schema;
+mod shopping;
+mod stores;
+mod trips;
+mod units;
 mod utils;
 mod cors;
 mod logging;
+mod packages;
 
 #[post("/item", format = "json", data = "<data>")]
-async fn add_item(data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Item>, std::io::Error> {
+async fn add_item(data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Item>, Error> {
+    if data.len() < 2 {
+        return Err(Error::Validation("Expected [name, category, unit?]".into()));
+    }
     let name = data[0].as_str();
     let category = data[1].as_str(); 
+    let unit = data.get(2).map_or(units::DEFAULT_UNIT, String::as_str);
     let item = db
-        .add_item(name, category)
-        .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Unable to create item."))?;
+        .add_item(name, category, unit)
+        .await?;
 
     info!(target: "database", "Created new item:\n{}", item);
 
@@ -33,18 +72,20 @@ async fn add_item(data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Item>,
 }
 
 #[patch("/item/<id>/desired/<desired_stock>")]
-async fn set_desired_stock(id: &str, desired_stock: i64, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn set_desired_stock(id: &str, desired_stock: f64, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
     let result = db
         .set_desired_stock(id, desired_stock)
-        .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Unable to create item."))?;
+        .await?;
 
     Ok(Json(result))
 }
 
 #[post("/dev/item/<name>", format="json", data="<data>")]
-async fn add_full_item(name: &str, data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Item>, std::io::Error> {
-    // [category, track_general, stock, desired_stock]
+async fn add_full_item(name: &str, data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Item>, Error> {
+    // [category, track_general, stock, desired_stock, unit]
+    if data.len() < 2 {
+        return Err(Error::Validation("Expected [category, track_general, stock?, desired_stock?, unit?]".into()));
+    }
     let category = &data[0];
     let track_general = data[1].clone().parse().unwrap_or(false);
     let stock;
@@ -54,18 +95,18 @@ async fn add_full_item(name: &str, data: Json<Vec<String>>, db: &State<DB>) -> R
             stock = s.parse().unwrap_or_default();
             match data.get(3) {
                 Some(d) => desired_stock = d.parse().unwrap_or_default(),
-                None => desired_stock = 0,
+                None => desired_stock = 0.0,
             }
         },
         None => {
-            stock = 0;
-            desired_stock = 0;
+            stock = 0.0;
+            desired_stock = 0.0;
         }
     }
+    let unit = data.get(4).map_or(units::DEFAULT_UNIT, String::as_str);
     let item = db
-        .add_full_item(name, category, stock, desired_stock, track_general)
-        .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Unable to create item."))?;
+        .add_full_item(name, category, stock, desired_stock, unit, track_general)
+        .await?;
 
     info!(target: "database", "Created new item:\n{}", item);
 
@@ -73,51 +114,46 @@ async fn add_full_item(name: &str, data: Json<Vec<String>>, db: &State<DB>) -> R
 }
 
 #[get("/item/<id>")]
-async fn get_item(id: &str, db: &State<DB>) -> Result<Json<Item>, std::io::Error> {
+async fn get_item(id: &str, db: &State<DB>) -> Result<Json<Item>, Error> {
     let item = db
         .get_item(id)
-        .await
-        .map_err(|_| std::io::Error::new(ErrorKind::Other, "Unable to fetch item."))?;
+        .await?;
 
     Ok(Json(item))
 }
 
 #[get("/items")]
-async fn get_all_items(db: &State<DB>) -> Result<Json<Vec<Item>>, std::io::Error> {
+async fn get_all_items(db: &State<DB>) -> Result<Json<Vec<Item>>, Error> {
     let items = db
         .get_all_items()
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, format!("Unable to fetch all items\n{}",e.to_string())))?;
+        .await?;
 
     Ok(Json(items))
 }
 
 #[patch("/item/<id>/<stock>")]
-async fn restock_item(id: &str, stock: i64, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn restock_item(id: &str, stock: f64, db: &State<DB>, source: Source) -> Result<Json<AffectedRows>, Error> {
     let result = db
-        .restock_item(id, stock)
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .restock_item(id, stock, &source)
+        .await?;
 
     Ok(Json(result))
 }
 
 #[patch("/item/<id>/consume/<stock>")]
-async fn consume_item(id: &str, stock: i64, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn consume_item(id: &str, stock: f64, db: &State<DB>, config: &State<InventoryConfig>, source: Source) -> Result<Json<AffectedRows>, Error> {
     let result = db
-        .consume_item(id, stock)
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .consume_item(id, stock, config.over_consumption, &source)
+        .await?;
 
     Ok(Json(result))
 }
 
 #[patch("/item/update/<id>", format="json", data="<data>", rank=1)]
-async fn change_item(id: &str, data: Json<Item>, db: &State<DB>) -> Result<Json<Item>, std::io::Error> {
+async fn change_item(id: &str, data: Json<Item>, db: &State<DB>, source: Source) -> Result<Json<Item>, Error> {
     let result = db
-        .change_item(id, data.0)
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .change_item(id, data.0, &source)
+        .await?;
 
     info!(target: "database", "Changed item:\n{}", result);
 
@@ -125,21 +161,72 @@ async fn change_item(id: &str, data: Json<Item>, db: &State<DB>) -> Result<Json<
 }
 
 #[patch("/items/update", format="json", data="<data>")]
-async fn change_items(data: Json<Vec<Item>>, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn change_items(data: Json<Vec<Item>>, db: &State<DB>, source: Source) -> Result<Json<BatchResult>, Error> {
     let result = db
-        .change_items(data.0)
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .change_items(data.0, &source)
+        .await?;
 
     Ok(Json(result))
 }
 
+#[patch("/item/<id>/packages", format="json", data="<data>", rank=2)]
+async fn set_packages(id: &str, data: Json<Vec<Package>>, db: &State<DB>) -> Result<Json<Item>, Error> {
+    let item = db
+        .set_packages(id, data.0)
+        .await?;
+
+    info!(target: "database", "Set packages of {}: {:?}", item.name, item.packages);
+
+    Ok(Json(item))
+}
+
+#[patch("/item/<id>/store", format="json", data="<data>", rank=2)]
+async fn set_item_store(id: &str, data: Json<ItemStoreInput>, db: &State<DB>) -> Result<Json<Item>, Error> {
+    let item = db
+        .set_item_store(id, data.0)
+        .await?;
+
+    info!(target: "database", "Set store of {}: {:?}, aisle {:?}", item.name, item.store, item.aisle);
+
+    Ok(Json(item))
+}
+
+#[get("/item/by-barcode/<code>")]
+async fn get_item_by_barcode(code: &str, db: &State<DB>) -> Result<Json<Item>, Error> {
+    let item = db
+        .item_by_barcode(code)
+        .await?;
+
+    Ok(Json(item))
+}
+
+#[post("/item/<id>/barcodes/<code>")]
+async fn add_barcode(id: &str, code: &str, db: &State<DB>) -> Result<Json<Item>, Error> {
+    let item = db
+        .add_barcode(id, code)
+        .await?;
+
+    info!(target: "database", "Added barcode {} to {}", code, item.name);
+
+    Ok(Json(item))
+}
+
+#[delete("/item/<id>/barcodes/<code>")]
+async fn remove_barcode(id: &str, code: &str, db: &State<DB>) -> Result<Json<Item>, Error> {
+    let item = db
+        .remove_barcode(id, code)
+        .await?;
+
+    info!(target: "database", "Removed barcode {} from {}", code, item.name);
+
+    Ok(Json(item))
+}
+
 #[delete("/item/<id>")]
-async fn delete_item(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn delete_item(id: &str, db: &State<DB>, source: Source) -> Result<Json<AffectedRows>, Error> {
     let result = db
-        .delete_item(id)
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .delete_item(id, &source)
+        .await?;
 
     warn!(target: "database", "Deleted: {}", id);
 
@@ -159,65 +246,622 @@ async fn delete_item(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, std
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestockItem {
     pub id: String,
-    pub count: i64
+    pub count: f64,
+    /// The unit `count` is in when it isn't the item's own, converted before it's applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// One of the item's packages, making `count` the number of packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// When the stock restocked is best used by, as `YYYY-MM-DD` or RFC 3339. Ignored when consuming
+    #[serde(default, deserialize_with = "lots::deserialize_best_by", skip_serializing_if = "Option::is_none")]
+    pub best_by: Option<chrono::DateTime<chrono::Utc>>,
+    /// What was paid for the whole entry, recorded as a purchase. Ignored when consuming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    /// What was paid for each one of `count`, as a shelf label gives it. Give either this or `price`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit_price: Option<f64>,
+    /// The store it was bought at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>
 } impl std::fmt::Display for RestockItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {}", self.id, self.count)
+        match (&self.unit, &self.package) {
+            (_, Some(package)) => write!(f, "{}: {} x {}", self.id, self.count, package),
+            (Some(unit), None) => write!(f, "{}: {} {}", self.id, self.count, unit),
+            (None, None) => write!(f, "{}: {}", self.id, self.count),
+        }?;
+        if let Some(best_by) = self.best_by {
+            write!(f, " (best by {})", best_by.format("%Y-%m-%d"))?;
+        }
+        match (self.price, self.unit_price) {
+            (Some(price), _) => write!(f, " for {}", price),
+            (None, Some(unit_price)) => write!(f, " at {} each", unit_price),
+            (None, None) => Ok(()),
+        }
     }
 }
 
 #[patch("/items/restock", format="json", data="<data>")]
-async fn restock_items(data: Json<Vec<RestockItem>>, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn restock_items(data: Json<Vec<RestockItem>>, db: &State<DB>, source: Source) -> Result<Json<BatchResult>, Error> {
     let data = data.0;
     let result = db
-        .restock_items(data.clone())
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .restock_items(data.clone(), &locations::default_location(), &source)
+        .await?;
 
-    info!(target: "database", "Restocked:\n{}", logging::log_vec(data));
+    if !result.applied.is_empty() {
+        info!(target: "database", "Restocked:\n{}", logging::log_vec(logging::applied(&data, &result)));
+    }
 
     Ok(Json(result))
 }
 
 #[patch("/items/consume", format="json", data="<data>")]
-async fn consume_items(data: Json<Vec<RestockItem>>, db: &State<DB>) -> Result<Json<AffectedRows>, std::io::Error> {
+async fn consume_items(data: Json<Vec<RestockItem>>, db: &State<DB>, config: &State<InventoryConfig>, source: Source) -> Result<Json<BatchResult>, Error> {
     let data = data.0;
     let result = db
-        .consume_items(data.clone())
-        .await
-        .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        .consume_items(data.clone(), None, config.over_consumption, &source)
+        .await?;
 
-    info!(target: "database", "Consumed:\n{}", logging::log_vec(data));
+    if !result.applied.is_empty() {
+        info!(target: "database", "Consumed:\n{}", logging::log_vec(logging::applied(&data, &result)));
+    }
     
     Ok(Json(result))
 }
 
-#[get("/logs")]
-async fn present_logs() -> Result<Json<(String, String)>, std::io::Error> {
-    let running = std::fs::read_to_string("log/running.log").unwrap_or_default();
-    let yesterday = std::fs::read_to_string("log/old1.log").unwrap_or_default();
-    Ok(Json((running, yesterday)))
+#[get("/locations")]
+async fn get_locations(db: &State<DB>) -> Result<Json<Vec<Location>>, Error> {
+    let locations = db
+        .get_locations()
+        .await?;
+
+    Ok(Json(locations))
 }
 
-#[launch]
-async fn rocket() -> _ {
-    log4rs::init_file("logging_config.yaml", Default::default()).unwrap();
+#[post("/location", format="json", data="<data>")]
+async fn create_location(data: Json<LocationInput>, db: &State<DB>) -> Result<Json<Location>, Error> {
+    let location = db
+        .create_location(data.0)
+        .await?;
 
-    let ds = Arc::new(Datastore::new("file://inventory.db").await.unwrap());
-    // let ds = Arc::new(Datastore::new("memory").await.unwrap());
-    let mut sesh = Session::default();
+    info!(target: "database", "Created location {}", location.name);
+
+    Ok(Json(location))
+}
 
-    sesh.ns = Some("my_ns".to_owned());
-    sesh.db = Some("my_bd".to_owned());
+#[patch("/location/<id>", format="json", data="<data>")]
+async fn rename_location(id: &str, data: Json<LocationInput>, db: &State<DB>) -> Result<Json<Location>, Error> {
+    let location = db
+        .rename_location(id, data.0)
+        .await?;
 
-    let db = DB {ds, sesh};
+    info!(target: "database", "Renamed location {} to {}", id, location.name);
+
+    Ok(Json(location))
+}
+
+#[delete("/location/<id>")]
+async fn delete_location(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_location(id)
+        .await?;
+
+    warn!(target: "database", "Deleted location: {}", id);
+
+    Ok(Json(result))
+}
 
-    env::set_var("ROCKET_ADDRESS", "192.168.1.229");
-    // env::set_var("ROCKET_ADDRESS", "192.168.1.11");
-    env::set_var("ROCKET_PORT", "26530");
-    // env::set_var("ROCKET_LOG_LEVEL", "off");
+#[get("/stock-levels?<item>")]
+async fn get_stock_levels(item: Option<&str>, db: &State<DB>) -> Result<Json<Vec<StockLevel>>, Error> {
+    let levels = db
+        .get_stock_levels(item)
+        .await?;
 
-    rocket::build()
+    Ok(Json(levels))
+}
+
+#[get("/lots?<item>")]
+async fn get_lots(item: Option<&str>, db: &State<DB>) -> Result<Json<Vec<Lot>>, Error> {
+    let lots = db
+        .get_lots(item)
+        .await?;
+
+    Ok(Json(lots))
+}
+
+#[get("/lots/expiring?<days>")]
+async fn expiring_lots(days: Option<i64>, db: &State<DB>) -> Result<Json<Vec<Lot>>, Error> {
+    let lots = db
+        .expiring_lots(days)
+        .await?;
+
+    Ok(Json(lots))
+}
+
+#[patch("/location/<id>/restock", format="json", data="<data>")]
+async fn restock_at(id: &str, data: Json<Vec<RestockItem>>, db: &State<DB>, source: Source) -> Result<Json<BatchResult>, Error> {
+    let location = db.existing_location(id).await?;
+    let data = data.0;
+    let result = db
+        .restock_items(data.clone(), &location, &source)
+        .await?;
+
+    if !result.applied.is_empty() {
+        info!(target: "database", "Restocked at {}:\n{}", id, logging::log_vec(logging::applied(&data, &result)));
+    }
+
+    Ok(Json(result))
+}
+
+#[patch("/location/<id>/consume", format="json", data="<data>")]
+async fn consume_at(id: &str, data: Json<Vec<RestockItem>>, db: &State<DB>, config: &State<InventoryConfig>, source: Source) -> Result<Json<BatchResult>, Error> {
+    let location = db.existing_location(id).await?;
+    let data = data.0;
+    let result = db
+        .consume_items(data.clone(), Some(&location), config.over_consumption, &source)
+        .await?;
+
+    if !result.applied.is_empty() {
+        info!(target: "database", "Consumed at {}:\n{}", id, logging::log_vec(logging::applied(&data, &result)));
+    }
+
+    Ok(Json(result))
+}
+
+#[patch("/items/transfer", format="json", data="<data>")]
+async fn transfer_items(data: Json<Vec<Transfer>>, db: &State<DB>) -> Result<Json<BatchResult>, Error> {
+    let data = data.0;
+    let result = db
+        .transfer_items(data.clone())
+        .await?;
+
+    if !result.applied.is_empty() {
+        let applied: Vec<_> = data.iter()
+            .filter(|transfer| result.applied.iter().any(|applied| applied.id.eq(&transfer.id)))
+            .map(|transfer| format!("{}: {} from {} to {}", transfer.id, transfer.count, transfer.from, transfer.to))
+            .collect();
+        info!(target: "database", "Transferred:\n{}", logging::log_vec(applied));
+    }
+
+    Ok(Json(result))
+}
+
+#[get("/units")]
+async fn get_units() -> Json<Vec<Unit>> {
+    Json(units::UNITS.to_vec())
+}
+
+#[get("/recipes")]
+async fn get_recipes(db: &State<DB>) -> Result<Json<Vec<Recipe>>, Error> {
+    let recipes = db
+        .get_recipes()
+        .await?;
+
+    Ok(Json(recipes))
+}
+
+#[get("/recipes/cookable?<missing>")]
+async fn cookable_recipes(missing: Option<usize>, db: &State<DB>) -> Result<Json<Cookable>, Error> {
+    let cookable = db
+        .cookable_recipes(missing)
+        .await?;
+
+    Ok(Json(cookable))
+}
+
+#[get("/recipe/<id>")]
+async fn get_recipe(id: &str, db: &State<DB>) -> Result<Json<Recipe>, Error> {
+    let recipe = db
+        .get_recipe(id)
+        .await?;
+
+    Ok(Json(recipe))
+}
+
+#[post("/recipe", format="json", data="<data>")]
+async fn create_recipe(data: Json<RecipeInput>, db: &State<DB>) -> Result<Json<Recipe>, Error> {
+    let recipe = db
+        .create_recipe(data.0)
+        .await?;
+
+    info!(target: "database", "Created recipe {}", recipe.name);
+
+    Ok(Json(recipe))
+}
+
+#[patch("/recipe/<id>", format="json", data="<data>")]
+async fn update_recipe(id: &str, data: Json<RecipeInput>, db: &State<DB>) -> Result<Json<Recipe>, Error> {
+    let recipe = db
+        .update_recipe(id, data.0)
+        .await?;
+
+    info!(target: "database", "Changed recipe {}", recipe.name);
+
+    Ok(Json(recipe))
+}
+
+#[delete("/recipe/<id>")]
+async fn delete_recipe(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_recipe(id)
+        .await?;
+
+    warn!(target: "database", "Deleted recipe: {}", id);
+
+    Ok(Json(result))
+}
+
+#[patch("/recipe/<id>/cook?<servings>")]
+async fn cook_recipe(id: &str, servings: Option<f64>, db: &State<DB>, config: &State<InventoryConfig>, source: Source) -> Result<Json<BatchResult>, Error> {
+    let result = db
+        .cook_recipe(id, servings, config.over_consumption, &source)
+        .await?;
+
+    if !result.applied.is_empty() {
+        info!(target: "database", "Cooked {}: consumed {} items", id, result.applied.len());
+    }
+
+    Ok(Json(result))
+}
+
+#[get("/meal-plan?<from>&<to>")]
+async fn get_meal_plan(from: Option<&str>, to: Option<&str>, db: &State<DB>) -> Result<Json<Vec<PlannedMeal>>, Error> {
+    let (from, to) = meal_plan::plan_range(from, to)?;
+    let meals = db
+        .get_meal_plan(from, to)
+        .await?;
+
+    Ok(Json(meals))
+}
+
+#[post("/meal-plan", format="json", data="<data>")]
+async fn plan_meal(data: Json<PlannedMealInput>, db: &State<DB>) -> Result<Json<PlannedMeal>, Error> {
+    let meal = db
+        .plan_meal(data.0)
+        .await?;
+
+    info!(target: "database", "Planned {} for {}", meal.recipe_name, meal.date);
+
+    Ok(Json(meal))
+}
+
+#[patch("/meal-plan/<id>", format="json", data="<data>")]
+async fn change_planned_meal(id: &str, data: Json<PlannedMealChange>, db: &State<DB>) -> Result<Json<PlannedMeal>, Error> {
+    let meal = db
+        .change_planned_meal(id, data.0)
+        .await?;
+
+    info!(target: "database", "Changed planned meal {} to {} for {}", meal.id, meal.recipe_name, meal.date);
+
+    Ok(Json(meal))
+}
+
+#[delete("/meal-plan/<id>")]
+async fn delete_planned_meal(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_planned_meal(id)
+        .await?;
+
+    warn!(target: "database", "Deleted planned meal: {}", id);
+
+    Ok(Json(result))
+}
+
+#[get("/meal-plan/shopping?<from>&<to>")]
+async fn planned_needs(from: Option<&str>, to: Option<&str>, db: &State<DB>) -> Result<Json<Vec<PlannedNeed>>, Error> {
+    let (from, to) = meal_plan::plan_range(from, to)?;
+    let needs = db
+        .planned_needs(from, to)
+        .await?;
+
+    Ok(Json(needs))
+}
+
+#[get("/stores")]
+async fn get_stores(db: &State<DB>) -> Result<Json<Vec<Store>>, Error> {
+    let stores = db
+        .get_stores()
+        .await?;
+
+    Ok(Json(stores))
+}
+
+#[post("/store", format="json", data="<data>")]
+async fn create_store(data: Json<StoreInput>, db: &State<DB>) -> Result<Json<Store>, Error> {
+    let store = db
+        .create_store(data.0)
+        .await?;
+
+    info!(target: "database", "Created store {}", store.name);
+
+    Ok(Json(store))
+}
+
+#[patch("/store/<id>", format="json", data="<data>")]
+async fn update_store(id: &str, data: Json<StoreInput>, db: &State<DB>) -> Result<Json<Store>, Error> {
+    let store = db
+        .update_store(id, data.0)
+        .await?;
+
+    info!(target: "database", "Changed store {}: aisles {:?}", store.name, store.aisles);
+
+    Ok(Json(store))
+}
+
+#[delete("/store/<id>")]
+async fn delete_store(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_store(id)
+        .await?;
+
+    warn!(target: "database", "Deleted store: {}", id);
+
+    Ok(Json(result))
+}
+
+#[get("/shopping-list?<from>")]
+async fn shopping_list(from: Option<&str>, db: &State<DB>) -> Result<Json<ShoppingList>, Error> {
+    let (from, to) = meal_plan::plan_range(from, None)?;
+    let list = db
+        .shopping_list(from, to)
+        .await?;
+
+    Ok(Json(list))
+}
+
+#[post("/shopping-list/extras", format="json", data="<data>")]
+async fn add_extra(data: Json<ExtraInput>, db: &State<DB>) -> Result<Json<Extra>, Error> {
+    let extra = db
+        .add_extra(data.0)
+        .await?;
+
+    info!(target: "database", "Added {} to the shopping list", extra.name);
+
+    Ok(Json(extra))
+}
+
+#[delete("/shopping-list/extras/<id>")]
+async fn delete_extra(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_extra(id)
+        .await?;
+
+    info!(target: "database", "Removed shopping list extra: {}", id);
+
+    Ok(Json(result))
+}
+
+#[delete("/shopping-list/extras")]
+async fn clear_extras(db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .clear_extras()
+        .await?;
+
+    info!(target: "database", "Cleared {} shopping list extras", result.rows_affected);
+
+    Ok(Json(result))
+}
+
+#[get("/trip")]
+async fn current_trip(db: &State<DB>) -> Result<Json<Option<Trip>>, Error> {
+    let trip = db
+        .current_trip()
+        .await?;
+
+    Ok(Json(trip))
+}
+
+#[get("/trips")]
+async fn get_trips(db: &State<DB>) -> Result<Json<Vec<Trip>>, Error> {
+    let trips = db
+        .get_trips()
+        .await?;
+
+    Ok(Json(trips))
+}
+
+#[get("/trip/<id>")]
+async fn get_trip(id: &str, db: &State<DB>) -> Result<Json<Trip>, Error> {
+    let trip = db
+        .get_trip(id)
+        .await?;
+
+    Ok(Json(trip))
+}
+
+#[post("/trip", format="json", data="<data>")]
+async fn start_trip(data: Json<TripInput>, db: &State<DB>) -> Result<Json<Trip>, Error> {
+    let trip = db
+        .start_trip(data.0)
+        .await?;
+
+    info!(target: "database", "Started shopping trip {} with {} lines", trip.id, trip.lines.len());
+
+    Ok(Json(trip))
+}
+
+#[patch("/trip/<id>/line/<line>", format="json", data="<data>")]
+async fn check_trip_line(id: &str, line: &str, data: Json<TripLineChange>, db: &State<DB>) -> Result<Json<TripLine>, Error> {
+    let line = db
+        .check_trip_line(id, line, data.0)
+        .await?;
+
+    Ok(Json(line))
+}
+
+#[patch("/trip/<id>/finish")]
+async fn finish_trip(id: &str, db: &State<DB>, source: Source) -> Result<Json<FinishedTrip>, Error> {
+    let finished = db
+        .finish_trip(id, &source)
+        .await?;
+
+    info!(target: "database", "Finished shopping trip {}: restocked {} items", id, finished.result.applied.len());
+
+    Ok(Json(finished))
+}
+
+#[delete("/trip/<id>")]
+async fn abandon_trip(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .abandon_trip(id)
+        .await?;
+
+    warn!(target: "database", "Abandoned shopping trip: {}", id);
+
+    Ok(Json(result))
+}
+
+#[get("/prices?<item>")]
+async fn item_prices(item: &str, db: &State<DB>) -> Result<Json<Vec<Purchase>>, Error> {
+    let purchases = db
+        .item_prices(item)
+        .await?;
+
+    Ok(Json(purchases))
+}
+
+#[get("/prices/compare?<item>")]
+async fn compare_prices(item: &str, db: &State<DB>) -> Result<Json<PriceComparison>, Error> {
+    let comparison = db
+        .compare_prices(item)
+        .await?;
+
+    Ok(Json(comparison))
+}
+
+#[get("/reports/spending?<by>&<from>&<to>")]
+async fn spending(by: Option<SpendBy>, from: Option<&str>, to: Option<&str>, db: &State<DB>) -> Result<Json<SpendingReport>, Error> {
+    let (from, to) = purchases::report_range(from, to)?;
+    let report = db
+        .spending(by.unwrap_or(SpendBy::Week), from, to)
+        .await?;
+
+    Ok(Json(report))
+}
+
+#[get("/budgets")]
+async fn get_budgets(db: &State<DB>) -> Result<Json<Vec<Budget>>, Error> {
+    let budgets = db
+        .get_budgets()
+        .await?;
+
+    Ok(Json(budgets))
+}
+
+#[get("/budgets/status?<month>")]
+async fn budget_status(month: Option<&str>, db: &State<DB>) -> Result<Json<BudgetStatus>, Error> {
+    let status = db
+        .budget_status(month)
+        .await?;
+
+    Ok(Json(status))
+}
+
+#[put("/budget", format="json", data="<data>")]
+async fn set_budget(data: Json<BudgetInput>, db: &State<DB>) -> Result<Json<Budget>, Error> {
+    let budget = db
+        .set_budget(data.0)
+        .await?;
+
+    info!(target: "database", "Set the {} budget to {}", budget.category_name.as_deref().unwrap_or("overall"), budget.amount);
+
+    Ok(Json(budget))
+}
+
+#[delete("/budget/<id>")]
+async fn delete_budget(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_budget(id)
+        .await?;
+
+    warn!(target: "database", "Deleted budget: {}", id);
+
+    Ok(Json(result))
+}
+
+#[get("/categories")]
+async fn get_categories(db: &State<DB>) -> Result<Json<Vec<Category>>, Error> {
+    let categories = db
+        .get_categories()
+        .await?;
+
+    Ok(Json(categories))
+}
+
+#[post("/category", format="json", data="<data>")]
+async fn create_category(data: Json<CategoryInput>, db: &State<DB>) -> Result<Json<Category>, Error> {
+    let category = db
+        .create_category(data.0)
+        .await?;
+
+    info!(target: "database", "Created category {}", category.name);
+
+    Ok(Json(category))
+}
+
+#[patch("/category/<id>", format="json", data="<data>")]
+async fn update_category(id: &str, data: Json<CategoryInput>, db: &State<DB>) -> Result<Json<Category>, Error> {
+    let category = db
+        .update_category(id, data.0)
+        .await?;
+
+    info!(target: "database", "Changed category {}", category.name);
+
+    Ok(Json(category))
+}
+
+#[patch("/category/<id>/merge/<into>")]
+async fn merge_category(id: &str, into: &str, db: &State<DB>) -> Result<Json<Category>, Error> {
+    let category = db
+        .merge_category(id, into)
+        .await?;
+
+    warn!(target: "database", "Merged category {} into {}", id, category.name);
+
+    Ok(Json(category))
+}
+
+#[patch("/categories/order", format="json", data="<data>")]
+async fn reorder_categories(data: Json<Vec<String>>, db: &State<DB>) -> Result<Json<Vec<Category>>, Error> {
+    let categories = db
+        .reorder_categories(data.0)
+        .await?;
+
+    Ok(Json(categories))
+}
+
+#[delete("/category/<id>")]
+async fn delete_category(id: &str, db: &State<DB>) -> Result<Json<AffectedRows>, Error> {
+    let result = db
+        .delete_category(id)
+        .await?;
+
+    warn!(target: "database", "Deleted category: {}", id);
+
+    Ok(Json(result))
+}
+
+#[get("/history?<filter..>")]
+async fn get_history(filter: HistoryFilter, db: &State<DB>) -> Result<Json<HistoryPage>, Error> {
+    let page = db
+        .history(filter)
+        .await?;
+
+    Ok(Json(page))
+}
+
+#[get("/logs")]
+async fn present_logs(config: &State<InventoryConfig>) -> Result<Json<(String, String)>, Error> {
+    let running = std::fs::read_to_string(config.log_dir.join("running.log")).unwrap_or_default();
+    let yesterday = std::fs::read_to_string(config.log_dir.join("old1.log")).unwrap_or_default();
+    Ok(Json((running, yesterday)))
+}
+
+/// Every route and catcher of the api, with CORS. The database and configuration still need to be managed.
+fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket
         .mount(
             "/",
             routes![add_item, set_desired_stock,
@@ -225,12 +869,91 @@ async fn rocket() -> _ {
                 get_item, get_all_items, 
                 restock_item, consume_item, 
                 restock_items, consume_items,
-                change_item, change_items,
+                change_item, change_items, set_packages, set_item_store,
+                get_item_by_barcode, add_barcode, remove_barcode,
                 delete_item,
+                get_locations, create_location, rename_location, delete_location,
+                get_stock_levels, get_lots, expiring_lots, restock_at, consume_at, transfer_items,
+                get_units,
+                get_recipes, cookable_recipes, get_recipe, create_recipe, update_recipe, delete_recipe, cook_recipe,
+                get_meal_plan, plan_meal, change_planned_meal, delete_planned_meal, planned_needs,
+                get_stores, create_store, update_store, delete_store,
+                shopping_list, add_extra, delete_extra, clear_extras,
+                item_prices, compare_prices, spending,
+                get_budgets, budget_status, set_budget, delete_budget,
+                current_trip, get_trips, get_trip, start_trip, check_trip_line, finish_trip, abandon_trip,
+                get_categories, create_category, update_category,
+                merge_category, reorder_categories, delete_category,
                 // run_command,
-                present_logs
+                present_logs, get_history
             ],
         )
+        .register("/", catchers![error::bad_request, error::not_found, error::unprocessable, error::default_catcher])
         .attach(CORS)
+}
+
+#[launch]
+async fn rocket() -> _ {
+    let figment = config::figment().unwrap_or_else(|e| panic!("{}", e));
+    let config: InventoryConfig = figment.extract().expect("Unable to read configuration");
+    config::validate_datastore(&config.datastore).unwrap();
+
+    // logging_config.yaml refers to the log directory as $ENV{INVENTORY_LOG_DIR}
+    env::set_var("INVENTORY_LOG_DIR", &config.log_dir);
+    log4rs::init_file(&config.log_config, Default::default()).unwrap();
+
+    let ds = Arc::new(Datastore::new(&config.datastore).await.unwrap());
+    let mut sesh = Session::default();
+
+    sesh.ns = Some(config.namespace.clone());
+    sesh.db = Some(config.database.clone());
+
+    let db = DB {ds, sesh};
+    db.define_schema().await.unwrap();
+    db.init_locations(&config.default_location).await.unwrap();
+    db.init_lots().await.unwrap();
+
+    mount(rocket::custom(figment))
         .manage(db)
-}
\ No newline at end of file
+        .manage(config)
+}
+#[cfg(test)]
+mod tests {
+    use rocket::{http::{ContentType, Status}, local::asynchronous::Client};
+
+    use super::*;
+
+    async fn client() -> Client {
+        let rocket = mount(rocket::build()).manage(DB::memory().await).manage(InventoryConfig::default());
+        Client::tracked(rocket).await.expect("the api should launch without route collisions")
+    }
+
+    #[rocket::async_test]
+    async fn launches() {
+        let _ = client().await;
+    }
+
+    #[rocket::async_test]
+    async fn item_subpaths_reach_their_routes() {
+        let client = client().await;
+        let db = client.rocket().state::<DB>().unwrap();
+        let id = db.add_item("Flour", "Baking", "g").await.unwrap().id.unwrap();
+
+        let restocked = client.patch(format!("/item/{id}/500")).dispatch().await;
+        assert_eq!(restocked.status(), Status::Ok);
+        let packages = client.patch(format!("/item/{id}/packages"))
+            .header(ContentType::JSON)
+            .body(r#"[{"name": "bag", "size": 1000}]"#)
+            .dispatch().await;
+        assert_eq!(packages.status(), Status::Ok);
+        let store = client.patch(format!("/item/{id}/store"))
+            .header(ContentType::JSON)
+            .body("{}")
+            .dispatch().await;
+        assert_eq!(store.status(), Status::Ok);
+
+        let item = db.get_item(&id).await.unwrap();
+        assert_eq!(item.stock, 500.0);
+        assert_eq!(item.packages.len(), 1);
+    }
+}
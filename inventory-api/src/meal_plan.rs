@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{db::{first_result, AffectedRows, Item, DB}, prelude::{Error, W}, query::record_thing, recipes::{recipe_thing, Recipe}, units, utils::macros::map};
+
+/// How many days a plan covers, starting today, when no range is given.
+const DEFAULT_PLAN_DAYS: i64 = 7;
+
+/// A recipe planned for a day, made for `servings` people.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedMeal {
+    pub id: String,
+    pub date: NaiveDate,
+    pub recipe: String,
+    pub recipe_name: String,
+    pub servings: f64,
+}
+
+impl TryFrom<W<Value>> for PlannedMeal {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let date: String = W(map["date"].clone()).try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            date: parse_date(&date)?,
+            recipe: W(map["recipe"].clone()).try_into()?,
+            recipe_name: W(map.get("recipe_name").cloned().unwrap_or_default()).try_into().unwrap_or_default(),
+            servings: W(map["servings"].clone()).try_into()?,
+        })
+    }
+}
+
+/// Body of `POST /meal-plan`. Without `servings` the recipe is planned for as many as it serves.
+#[derive(Debug, Deserialize)]
+pub struct PlannedMealInput {
+    pub date: String,
+    pub recipe: String,
+    #[serde(default)]
+    pub servings: Option<f64>,
+}
+
+/// Body of `PATCH /meal-plan/<id>`, moving a meal to another day or changing how many it's for.
+#[derive(Debug, Deserialize)]
+pub struct PlannedMealChange {
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub servings: Option<f64>,
+}
+
+/// One planned meal's share of what an item is needed for.
+#[derive(Debug, Serialize)]
+pub struct MealNeed {
+    pub meal: String,
+    pub date: NaiveDate,
+    pub recipe_name: String,
+    pub quantity: f64,
+}
+
+/// An item the planned meals use, with what to buy so they can all be cooked and still leave it at
+/// `desired_stock`. Quantities are in the item's unit.
+#[derive(Debug, Serialize)]
+pub struct PlannedNeed {
+    pub item: String,
+    pub name: String,
+    pub category: String,
+    pub unit: String,
+    pub stock: f64,
+    pub desired_stock: f64,
+    pub planned: f64,
+    pub to_buy: f64,
+    pub meals: Vec<MealNeed>,
+}
+
+pub fn meal_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("meal_plan", id)
+}
+
+/// Reads a plan date, which is a plain `YYYY-MM-DD` day.
+pub fn parse_date(value: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| Error::Validation(format!("'{}' is not a valid date, expected YYYY-MM-DD", value)))
+}
+
+/// The days from `from` to `to`, both included. A week from today unless given.
+pub fn plan_range(from: Option<&str>, to: Option<&str>) -> Result<(NaiveDate, NaiveDate), Error> {
+    let from = match from {
+        Some(from) => parse_date(from)?,
+        None => Utc::now().date_naive(),
+    };
+    let to = match to {
+        Some(to) => parse_date(to)?,
+        None => from + Duration::days(DEFAULT_PLAN_DAYS - 1),
+    };
+    if to < from {
+        return Err(Error::Validation(format!("The plan can't end ({}) before it starts ({})", to, from)));
+    }
+    Ok((from, to))
+}
+
+fn validate_servings(servings: f64) -> Result<f64, Error> {
+    if !(units::valid_quantity(servings) && servings > 0.0) {
+        return Err(Error::Validation(format!("Servings must be a positive number, got {}", servings)));
+    }
+    Ok(units::round(servings))
+}
+
+impl DB {
+    /// Meals planned from `from` to `to`, by day.
+    pub async fn get_meal_plan(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<PlannedMeal>, Error> {
+        let sql = "SELECT *, recipe.name AS recipe_name FROM meal_plan WHERE date >= $from AND date <= $to ORDER BY date ASC";
+        let vars: BTreeMap<String, Value> = map!(
+            "from".into() => Value::from(from.to_string()),
+            "to".into() => Value::from(to.to_string())
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn get_planned_meal(&self, id: &str) -> Result<PlannedMeal, Error> {
+        let sql = "SELECT *, recipe.name AS recipe_name FROM $th";
+        let vars: BTreeMap<String, Value> = map!("th".into() => meal_thing(id)?.into());
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No planned meal with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    pub async fn plan_meal(&self, input: PlannedMealInput) -> Result<PlannedMeal, Error> {
+        let date = parse_date(&input.date)?;
+        let recipe = self.get_recipe(&input.recipe).await?;
+        let servings = validate_servings(input.servings.unwrap_or(recipe.servings))?;
+        let sql = "CREATE meal_plan SET date = $date, recipe = $recipe, servings = $servings";
+        let vars: BTreeMap<String, Value> = map!(
+            "date".into() => Value::from(date.to_string()),
+            "recipe".into() => recipe_thing(&recipe.id)?.into(),
+            "servings".into() => Value::from(servings)
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+        let mut meal: PlannedMeal = W(first_result(res)?.first()).try_into()?;
+        meal.recipe_name = recipe.name;
+
+        Ok(meal)
+    }
+
+    pub async fn change_planned_meal(&self, id: &str, change: PlannedMealChange) -> Result<PlannedMeal, Error> {
+        let th = meal_thing(id)?;
+        let mut sets = vec![];
+        let mut vars: BTreeMap<String, Value> = map!("th".into() => th.into());
+        if let Some(date) = change.date.as_deref() {
+            sets.push("date = $date");
+            vars.insert("date".into(), Value::from(parse_date(date)?.to_string()));
+        }
+        if let Some(servings) = change.servings {
+            sets.push("servings = $servings");
+            vars.insert("servings".into(), Value::from(validate_servings(servings)?));
+        }
+        if !sets.is_empty() {
+            let sql = format!("UPDATE meal_plan SET {} WHERE id = $th RETURN id", sets.join(", "));
+            let res = self.execute(&sql, Some(vars)).await?;
+            if first_result(res)?.first().is_none() {
+                return Err(Error::NotFound(format!("No planned meal with id {}", id)));
+            }
+        }
+
+        self.get_planned_meal(id).await
+    }
+
+    pub async fn delete_planned_meal(&self, id: &str) -> Result<AffectedRows, Error> {
+        let sql = "DELETE meal_plan WHERE id = $th RETURN BEFORE";
+        let vars: BTreeMap<String, Value> = map!("th".into() => meal_thing(id)?.into());
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::Object(_) => Ok(AffectedRows { rows_affected: 1 }),
+            _ => Err(Error::NotFound(format!("No planned meal with id {}", id))),
+        }
+    }
+
+    /// What the meals planned from `from` to `to` need beyond the stock there is now, item by item.
+    /// Only items with something to buy are listed. An item tracked generally is bought when it's out,
+    /// since its stock is a level rather than an amount.
+    pub async fn planned_needs(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<PlannedNeed>, Error> {
+        let meals = self.get_meal_plan(from, to).await?;
+        if meals.is_empty() {
+            return Ok(vec![]);
+        }
+        let recipes: BTreeMap<String, Recipe> = self.get_recipes().await?.into_iter()
+            .map(|recipe| (recipe.id.clone(), recipe))
+            .collect();
+        let items: BTreeMap<String, Item> = self.get_all_items().await?.into_iter()
+            .filter_map(|item| Some((item.id.clone()?, item)))
+            .collect();
+
+        let mut by_item: BTreeMap<String, Vec<MealNeed>> = BTreeMap::new();
+        for meal in meals.iter() {
+            let Some(recipe) = recipes.get(&meal.recipe) else { continue };
+            for (item, quantity) in recipe.needs(meal.servings, &items) {
+                // An ingredient that can't be converted to its item's unit can't be shopped for either
+                let Ok(quantity) = quantity else { continue };
+                by_item.entry(item.id.clone().unwrap_or_default()).or_default().push(MealNeed {
+                    meal: meal.id.clone(),
+                    date: meal.date,
+                    recipe_name: recipe.name.clone(),
+                    quantity,
+                });
+            }
+        }
+
+        let mut needs: Vec<PlannedNeed> = by_item.into_iter()
+            .filter_map(|(id, meals)| {
+                let item = items.get(&id)?;
+                let planned = units::round(meals.iter().map(|meal| meal.quantity).sum());
+                let to_buy = match item.track_general.unwrap_or(false) {
+                    true if item.stock > 0.0 => 0.0,
+                    true => 1.0,
+                    false => units::round((item.desired_stock + planned - item.stock).max(0.0)),
+                };
+                (to_buy > 0.0).then(|| PlannedNeed {
+                    item: id,
+                    name: item.name.clone(),
+                    category: item.category.clone(),
+                    unit: item.unit.clone(),
+                    stock: item.stock,
+                    desired_stock: item.desired_stock,
+                    planned,
+                    to_buy,
+                    meals,
+                })
+            })
+            .collect();
+        needs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(needs)
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Value};
+
+use crate::{db::{found_item, Item, DB}, prelude::{Error, W}, query::item_thing, units, utils::macros::map};
+
+/// A package an item is bought in, e.g. a case of 12. `size` is in the item's own unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub size: f64,
+}
+
+impl TryFrom<W<Value>> for Package {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            name: W(map["name"].clone()).try_into()?,
+            size: W(map["size"].clone()).try_into()?,
+        })
+    }
+}
+
+impl From<&Package> for Value {
+    fn from(package: &Package) -> Self {
+        Value::Object(Object::from(map!(
+            "name".to_owned() => Value::from(package.name.as_str()),
+            "size".to_owned() => Value::from(package.size)
+        )))
+    }
+}
+
+/// Reads the `packages` field of an item record, which items created before packages existed don't have.
+pub fn packages_of(value: Option<&Value>) -> Result<Vec<Package>, Error> {
+    match value {
+        Some(Value::Array(packages)) => packages.iter().map(|package| W(package.clone()).try_into()).collect(),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Finds the package called `name` among an item's packages.
+pub fn find_package<'a>(packages: &'a [Package], name: &str) -> Result<&'a Package, Error> {
+    let name = name.trim();
+    packages.iter()
+        .find(|package| package.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::Validation(format!("The item has no package called '{}'", name)))
+}
+
+/// Trims names and rounds sizes, checking every package has a name of its own and holds something.
+fn validate_packages(packages: Vec<Package>) -> Result<Vec<Package>, Error> {
+    let mut valid: Vec<Package> = vec![];
+    for package in packages {
+        let name = package.name.trim().to_owned();
+        if name.is_empty() {
+            return Err(Error::Validation("A package name is required".into()));
+        }
+        if valid.iter().any(|other| other.name.eq_ignore_ascii_case(&name)) {
+            return Err(Error::Validation(format!("There is more than one package called '{}'", name)));
+        }
+        if !(units::valid_quantity(package.size) && package.size > 0.0) {
+            return Err(Error::Validation(format!("Package '{}' must hold more than nothing, got {}", name, package.size)));
+        }
+        valid.push(Package { name, size: units::round(package.size) });
+    }
+    Ok(valid)
+}
+
+impl DB {
+    /// Replaces the packages item `id` is bought in.
+    pub async fn set_packages(&self, id: &str, packages: Vec<Package>) -> Result<Item, Error> {
+        let packages = validate_packages(packages)?;
+        let sql = "UPDATE items SET packages = $packages WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => item_thing(id)?.into(),
+            "packages".into() => Value::Array(Array::from(packages.iter().map(Value::from).collect::<Vec<_>>()))
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_item(res, id)
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Datetime, Object, Thing, Value};
+
+use crate::{db::{first_result, DB}, meal_plan::parse_date, prelude::{Error, W}, query::{item_thing, Query}, stores::store_thing, units, utils::macros::map, RestockItem};
+
+/// How far back a spending report goes when it isn't given a start.
+const DEFAULT_REPORT_DAYS: i64 = 91;
+
+/// Where purchases without a store are totalled in a report by store.
+const NO_STORE: &str = "No store";
+
+/// How many times an item's usual unit price a restock has to cost to be flagged.
+const NOTABLY_ABOVE: f64 = 1.2;
+
+/// How many earlier purchases an item needs before it has a usual price.
+const USUAL_AFTER: usize = 2;
+
+/// What was paid for some of an item when it was restocked. `quantity` is in the item's unit at the
+/// time, and `unit_price` is what one of `price_unit` came to, see [`Purchase::normalize`].
+#[derive(Debug, Clone, Serialize)]
+pub struct Purchase {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    pub category: String,
+    pub quantity: f64,
+    pub unit: String,
+    pub price: f64,
+    pub unit_price: f64,
+    pub price_unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_name: Option<String>,
+    pub time: DateTime<Utc>,
+}
+
+impl TryFrom<W<Value>> for Purchase {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let quantity: f64 = W(map["quantity"].clone()).try_into()?;
+        let price: f64 = W(map["price"].clone()).try_into()?;
+        let unit: String = W(map["unit"].clone()).try_into()?;
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            item: W(map["item"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            category: W(map["category"].clone()).try_into()?,
+            quantity,
+            unit: unit.clone(),
+            price,
+            unit_price: if quantity > 0.0 { price / quantity } else { price },
+            price_unit: unit,
+            store: W(map.get("store").cloned().unwrap_or_default()).try_into()?,
+            store_name: W(map.get("store_name").cloned().unwrap_or_default()).try_into()?,
+            time: W(map["time"].clone()).try_into()?,
+        })
+    }
+}
+
+impl Purchase {
+    /// Puts `unit_price` per one of `unit`, the item's unit now, so purchases made in other sizes or
+    /// while the item was kept in another unit can be compared. A unit that doesn't convert is left as it was.
+    pub fn normalize(&mut self, unit: &str) {
+        match units::convert(self.quantity, &self.unit, unit) {
+            Ok(quantity) if quantity > 0.0 => {
+                self.unit_price = self.price / quantity;
+                self.price_unit = unit.to_owned();
+            },
+            _ => (),
+        }
+    }
+}
+
+/// What a store has charged for an item, per one of the item's unit.
+#[derive(Debug, Serialize)]
+pub struct StorePrice {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    pub store_name: String,
+    pub latest: f64,
+    pub latest_time: DateTime<Utc>,
+    pub average: f64,
+    pub purchases: usize,
+}
+
+/// Response of `GET /prices/compare`, with stores cheapest first by what they charged last.
+#[derive(Debug, Serialize)]
+pub struct PriceComparison {
+    pub item: String,
+    pub name: String,
+    pub unit: String,
+    /// The middle unit price of every purchase, once there are a few
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usual: Option<f64>,
+    pub stores: Vec<StorePrice>,
+}
+
+/// The middle unit price of purchases already normalized to `unit`, once there are enough to go by.
+pub fn usual_unit_price(purchases: &[Purchase], unit: &str) -> Option<f64> {
+    let mut prices: Vec<f64> = purchases.iter().filter(|purchase| purchase.price_unit == unit).map(|purchase| purchase.unit_price).collect();
+    if prices.len() < USUAL_AFTER {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let middle = prices.len() / 2;
+    Some(match prices.len() % 2 {
+        0 => (prices[middle - 1] + prices[middle]) / 2.0,
+        _ => prices[middle],
+    })
+}
+
+/// Whether a unit price is notably more than the usual one.
+pub fn above_usual(unit_price: f64, usual: f64) -> bool {
+    unit_price > usual * NOTABLY_ABOVE
+}
+
+/// Rounds money to the cent.
+pub(crate) fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// How `GET /reports/spending` totals purchases up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "lowercase")]
+pub enum SpendBy {
+    #[field(value = "week")]
+    Week,
+    #[field(value = "month")]
+    Month,
+    #[field(value = "category")]
+    Category,
+    #[field(value = "store")]
+    Store,
+}
+
+/// What was spent in a week, month, category or store. Weeks are keyed by the Monday they start on
+/// and months as `YYYY-MM`.
+#[derive(Debug, Serialize)]
+pub struct SpendRow {
+    pub key: String,
+    pub total: f64,
+    pub purchases: usize,
+}
+
+/// Response of `GET /reports/spending`. Weeks and months come in order with nothing spent included,
+/// so they can be read as a trend; categories and stores come biggest first.
+#[derive(Debug, Serialize)]
+pub struct SpendingReport {
+    pub by: SpendBy,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total: f64,
+    pub rows: Vec<SpendRow>,
+}
+
+/// Works out what was paid for a restock entry, from its price or its unit price times the count.
+pub fn entry_price(entry: &RestockItem) -> Result<Option<f64>, Error> {
+    let price = match (entry.price, entry.unit_price) {
+        (Some(_), Some(_)) => return Err(Error::Validation("Give either a price or a unit price, not both".into())),
+        (Some(price), None) => price,
+        (None, Some(unit_price)) => unit_price * entry.count,
+        (None, None) => return Ok(None),
+    };
+    match units::valid_quantity(price) {
+        true => Ok(Some(cents(price))),
+        false => Err(Error::Validation(format!("Price must be a number of zero or more, got {}", price))),
+    }
+}
+
+/// Statements recording a restock entry that was paid for, to go in the same transaction as the
+/// restock. `entry` must already be in the item's `unit` with its price worked out by [`entry_price`].
+pub fn purchase_statement(query: &mut Query, th: &str, entry: &RestockItem, unit: &str, store: Option<&(Thing, String)>) -> String {
+    let Some(price) = entry.price else { return String::new() };
+    let info = query.local();
+    let quantity = query.bind(entry.count);
+    let unit = query.bind(unit);
+    let price = query.bind(price);
+    let store_th = query.bind(store.map_or(Value::None, |(th, _)| Value::from(th.clone())));
+    let store_name = query.bind(store.map_or(Value::None, |(_, name)| Value::from(name.as_str())));
+    format!("
+        LET {info} = array::first((SELECT name, category FROM items WHERE id = {th}));
+        CREATE purchases SET item = {th}, name = {info}.name, category = {info}.category, quantity = {quantity}, unit = {unit}, price = {price}, store = {store_th}, store_name = {store_name}, time = time::now() RETURN NONE;
+    ")
+}
+
+/// Parses the range of a report, defaulting to the last [`DEFAULT_REPORT_DAYS`] up to today.
+pub fn report_range(from: Option<&str>, to: Option<&str>) -> Result<(NaiveDate, NaiveDate), Error> {
+    let to = match to {
+        Some(to) => parse_date(to)?,
+        None => Utc::now().date_naive(),
+    };
+    let from = match from {
+        Some(from) => parse_date(from)?,
+        None => to - Duration::days(DEFAULT_REPORT_DAYS - 1),
+    };
+    if from > to {
+        return Err(Error::Validation(format!("The report can't start ({}) after it ends ({})", from, to)));
+    }
+    Ok((from, to))
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn month_key(date: NaiveDate) -> String {
+    date.format("%Y-%m").to_string()
+}
+
+/// Every week or month from `from` to `to`, so ones where nothing was bought still show.
+fn periods(by: SpendBy, from: NaiveDate, to: NaiveDate) -> Vec<String> {
+    let mut keys: Vec<String> = vec![];
+    let mut date = match by {
+        SpendBy::Week => week_start(from),
+        _ => from,
+    };
+    while date <= to {
+        let key = match by {
+            SpendBy::Week => date.to_string(),
+            _ => month_key(date),
+        };
+        if keys.last() != Some(&key) {
+            keys.push(key);
+        }
+        date += Duration::days(if by == SpendBy::Week { 7 } else { 1 });
+    }
+    keys
+}
+
+impl DB {
+    /// The record and name of each store a batch says it was bought at, by id.
+    pub async fn purchase_stores(&self, data: &[RestockItem]) -> Result<BTreeMap<String, (Thing, String)>, Error> {
+        let mut stores = BTreeMap::new();
+        if data.iter().any(|entry| entry.store.is_some()) {
+            for store in self.get_stores().await? {
+                stores.insert(store.id.clone(), (store_thing(&store.id)?, store.name));
+            }
+        }
+        Ok(stores)
+    }
+
+    /// Every price paid for an item, latest first, with unit prices per one of the item's unit.
+    pub async fn item_prices(&self, id: &str) -> Result<Vec<Purchase>, Error> {
+        let item = self.get_item(id).await?;
+        let sql = "SELECT * FROM purchases WHERE item = $th ORDER BY time DESC";
+        let vars: BTreeMap<String, Value> = map!("th".into() => item_thing(id)?.into());
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        let mut purchases: Vec<Purchase> = array.into_iter().map(|value| W(value).try_into()).collect::<Result<_, _>>()?;
+        purchases.iter_mut().for_each(|purchase| purchase.normalize(&item.unit));
+        Ok(purchases)
+    }
+
+    /// Ranks the stores an item has been bought at by the unit price they charged last, then on average.
+    pub async fn compare_prices(&self, id: &str) -> Result<PriceComparison, Error> {
+        let item = self.get_item(id).await?;
+        let purchases = self.item_prices(id).await?;
+
+        // Latest first, so the first purchase seen from a store is its latest
+        let mut by_store: BTreeMap<Option<String>, Vec<&Purchase>> = BTreeMap::new();
+        for purchase in purchases.iter().filter(|purchase| purchase.price_unit == item.unit) {
+            by_store.entry(purchase.store.clone()).or_default().push(purchase);
+        }
+        let mut stores: Vec<StorePrice> = by_store.into_iter()
+            .map(|(store, bought)| StorePrice {
+                store,
+                store_name: bought[0].store_name.clone().unwrap_or_else(|| NO_STORE.to_owned()),
+                latest: cents(bought[0].unit_price),
+                latest_time: bought[0].time,
+                average: cents(bought.iter().map(|purchase| purchase.unit_price).sum::<f64>() / bought.len() as f64),
+                purchases: bought.len(),
+            })
+            .collect();
+        stores.sort_by(|a, b| a.latest.total_cmp(&b.latest).then(a.average.total_cmp(&b.average)));
+
+        Ok(PriceComparison {
+            usual: usual_unit_price(&purchases, &item.unit).map(cents),
+            item: item.id.unwrap_or_default(),
+            name: item.name,
+            unit: item.unit,
+            stores,
+        })
+    }
+
+    /// The usual unit price of each item in `units` that has one, by id, per one of the unit it's mapped to.
+    pub async fn usual_prices(&self, units: &BTreeMap<String, String>) -> Result<BTreeMap<String, f64>, Error> {
+        if units.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        let sql = "SELECT * FROM purchases WHERE item INSIDE $items";
+        let items: Vec<Value> = units.keys().map(|id| item_thing(id).map(Value::from)).collect::<Result<_, _>>()?;
+        let vars: BTreeMap<String, Value> = map!("items".into() => Value::Array(Array::from(items)));
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        let mut by_item: BTreeMap<String, Vec<Purchase>> = BTreeMap::new();
+        for value in array {
+            let purchase: Purchase = W(value).try_into()?;
+            by_item.entry(purchase.item.clone()).or_default().push(purchase);
+        }
+        let mut usual = BTreeMap::new();
+        for (id, unit) in units.iter() {
+            let Some(purchases) = by_item.get_mut(id) else { continue };
+            purchases.iter_mut().for_each(|purchase| purchase.normalize(unit));
+            if let Some(price) = usual_unit_price(purchases, unit) {
+                usual.insert(id.clone(), price);
+            }
+        }
+        Ok(usual)
+    }
+
+    /// Purchases made from the start of `from` to the end of `to`, in UTC days.
+    pub async fn purchases(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Purchase>, Error> {
+        let start = |date: NaiveDate| Datetime::from(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+        let sql = "SELECT * FROM purchases WHERE time >= $from AND time < $to ORDER BY time ASC";
+        let vars: BTreeMap<String, Value> = map!(
+            "from".into() => Value::from(start(from)),
+            "to".into() => Value::from(start(to + Duration::days(1)))
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn spending(&self, by: SpendBy, from: NaiveDate, to: NaiveDate) -> Result<SpendingReport, Error> {
+        let purchases = self.purchases(from, to).await?;
+
+        let mut totals: BTreeMap<String, (f64, usize)> = match by {
+            SpendBy::Week | SpendBy::Month => periods(by, from, to).into_iter().map(|key| (key, (0.0, 0))).collect(),
+            SpendBy::Category | SpendBy::Store => BTreeMap::new(),
+        };
+        for purchase in purchases.iter() {
+            let date = purchase.time.date_naive();
+            let key = match by {
+                SpendBy::Week => week_start(date).to_string(),
+                SpendBy::Month => month_key(date),
+                SpendBy::Category => purchase.category.clone(),
+                SpendBy::Store => purchase.store_name.clone().unwrap_or_else(|| NO_STORE.to_owned()),
+            };
+            let (total, count) = totals.entry(key).or_default();
+            *total += purchase.price;
+            *count += 1;
+        }
+
+        let mut rows: Vec<SpendRow> = totals.into_iter()
+            .map(|(key, (total, purchases))| SpendRow { key, total: cents(total), purchases })
+            .collect();
+        if matches!(by, SpendBy::Category | SpendBy::Store) {
+            rows.sort_by(|a, b| b.total.total_cmp(&a.total));
+        }
+        let total = cents(purchases.iter().map(|purchase| purchase.price).sum::<f64>());
+
+        Ok(SpendingReport { by, from, to, total, rows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn purchase(quantity: f64, unit: &str, price: f64) -> Purchase {
+        Purchase {
+            id: "purchases:test".into(),
+            item: "items:test".into(),
+            name: "Test".into(),
+            category: "Test".into(),
+            quantity,
+            unit: unit.into(),
+            price,
+            unit_price: price / quantity,
+            price_unit: unit.into(),
+            store: None,
+            store_name: None,
+            time: Utc::now(),
+        }
+    }
+
+    fn at(unit_prices: &[f64]) -> Vec<Purchase> {
+        unit_prices.iter().map(|price| purchase(1.0, "kg", *price)).collect()
+    }
+
+    #[test]
+    fn usual_is_the_middle_price_of_an_odd_count() {
+        assert_eq!(usual_unit_price(&at(&[4.0, 2.0, 30.0]), "kg"), Some(4.0));
+        assert_eq!(usual_unit_price(&at(&[5.0, 1.0, 3.0, 2.0, 4.0]), "kg"), Some(3.0));
+    }
+
+    #[test]
+    fn usual_is_between_the_middle_prices_of_an_even_count() {
+        assert_eq!(usual_unit_price(&at(&[2.0, 3.0]), "kg"), Some(2.5));
+        assert_eq!(usual_unit_price(&at(&[10.0, 2.0, 4.0, 3.0]), "kg"), Some(3.5));
+    }
+
+    #[test]
+    fn usual_needs_enough_purchases_in_the_unit() {
+        assert_eq!(usual_unit_price(&[], "kg"), None);
+        assert_eq!(usual_unit_price(&at(&[2.0]), "kg"), None);
+        let mut purchases = at(&[2.0]);
+        purchases.push(purchase(1.0, "count", 2.0));
+        assert_eq!(usual_unit_price(&purchases, "kg"), None);
+    }
+
+    #[test]
+    fn normalizes_to_the_item_unit() {
+        let mut bought = purchase(500.0, "g", 2.0);
+        bought.normalize("kg");
+        assert_eq!((bought.unit_price, bought.price_unit.as_str()), (4.0, "kg"));
+
+        let mut unconvertible = purchase(2.0, "count", 3.0);
+        unconvertible.normalize("kg");
+        assert_eq!((unconvertible.unit_price, unconvertible.price_unit.as_str()), (1.5, "count"));
+    }
+
+    #[test]
+    fn above_usual_only_when_notably_more() {
+        assert!(!above_usual(2.0, 2.0));
+        assert!(!above_usual(2.4, 2.0));
+        assert!(above_usual(2.5, 2.0));
+        assert!(!above_usual(1.0, 2.0));
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use surrealdb::sql::{thing, Thing, Value};
+
+use crate::prelude::Error;
+
+/// Builds SurrealQL where every client supplied value is bound as a variable
+/// instead of being formatted into the query text.
+///
+/// ```ignore
+/// let mut query = Query::transaction();
+/// for item in data.iter() {
+///     let th = query.bind(item_thing(&item.id)?);
+///     let count = query.bind(item.count);
+///     query.push(format!("UPDATE items SET stock += {count} WHERE id = {th};"));
+/// }
+/// let (sql, vars) = query.commit();
+/// ```
+#[derive(Debug, Default)]
+pub struct Query {
+    sql: String,
+    vars: BTreeMap<String, Value>,
+    locals: usize,
+    in_transaction: bool
+}
+
+impl Query {
+    /// Starts a query where every statement pushed succeeds or fails together.
+    pub fn transaction() -> Self {
+        Self { sql: "BEGIN TRANSACTION;".to_owned(), in_transaction: true, ..Default::default() }
+    }
+
+    /// Binds `value` to a fresh variable, returning its name (including the `$`)
+    /// to be placed in a statement.
+    pub fn bind(&mut self, value: impl Into<Value>) -> String {
+        let name = format!("v{}", self.vars.len());
+        self.vars.insert(name.clone(), value.into());
+        format!("${}", name)
+    }
+
+    /// A fresh name for a `LET` inside the query, which unlike [`Query::bind`] has no value up front.
+    pub fn local(&mut self) -> String {
+        self.locals += 1;
+        format!("$l{}", self.locals)
+    }
+
+    /// Adds a statement. Only variable names returned by [`Query::bind`] and
+    /// constant SurrealQL should ever make it into `statement`.
+    pub fn push(&mut self, statement: impl AsRef<str>) {
+        self.sql += statement.as_ref().trim();
+        if !self.sql.ends_with(';') {
+            self.sql.push(';');
+        }
+    }
+
+    /// Finishes the query, returning the text and variables to pass to [`crate::db::DB::execute`].
+    pub fn commit(mut self) -> (String, BTreeMap<String, Value>) {
+        if self.in_transaction {
+            self.sql += "COMMIT TRANSACTION;";
+        }
+        (self.sql, self.vars)
+    }
+}
+
+/// Parses a client supplied id, which must be a record in the `items` table.
+pub fn item_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("items", id)
+}
+
+/// Parses a client supplied id, which must be a record in `table`.
+/// Anything that isn't a plain `table:id` (sub-queries, ranges, other tables) is rejected.
+pub fn record_thing(table: &str, id: &str) -> Result<Thing, Error> {
+    let invalid = || Error::Validation(format!("'{}' is not a valid {} id", id, table));
+    let th = thing(id.trim()).map_err(|_| invalid())?;
+    if th.tb != table {
+        return Err(invalid());
+    }
+    Ok(th)
+}
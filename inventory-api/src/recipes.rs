@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{batch::BatchResult, config::OverConsumption, db::{all_results, first_result, AffectedRows, Item, DB}, ledger::Source, prelude::{Error, W}, query::{item_thing, record_thing, Query}, units, utils::macros::map, RestockItem};
+
+/// A meal and what goes into it, for `servings` people.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    pub servings: f64,
+    pub ingredients: Vec<Ingredient>,
+}
+
+impl TryFrom<W<Value>> for Recipe {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let ingredients: Array = W(map.get("ingredients").cloned().unwrap_or_default()).try_into().unwrap_or_default();
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            servings: W(map["servings"].clone()).try_into()?,
+            ingredients: ingredients.into_iter().map(|ingredient| W(ingredient).try_into()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Recipe {
+    /// What each ingredient comes to when the recipe is made for `servings`, in the unit its item is kept in.
+    /// Ingredients whose item is gone are left out, and one in a unit that can't be converted to the item's,
+    /// as when the item's unit changed since the recipe was saved, comes with the error saying so.
+    pub fn needs<'a>(&self, servings: f64, items: &'a BTreeMap<String, Item>) -> Vec<(&'a Item, Result<f64, Error>)> {
+        let scale = servings / self.servings;
+        self.ingredients.iter()
+            .filter_map(|ingredient| {
+                let item = items.get(&ingredient.item)?;
+                let quantity = ingredient.quantity * scale;
+                let quantity = match &ingredient.unit {
+                    Some(unit) => units::convert(quantity, unit, &item.unit),
+                    None => Ok(units::round(quantity)),
+                };
+                Some((item, quantity))
+            })
+            .collect()
+    }
+}
+
+/// `quantity` of item `item` per recipe, in the item's own unit unless `unit` says otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ingredient {
+    pub item: String,
+    pub quantity: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+impl TryFrom<W<Value>> for Ingredient {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        Ok(Self {
+            item: W(map["item"].clone()).try_into()?,
+            quantity: W(map["quantity"].clone()).try_into()?,
+            unit: W(map.get("unit").cloned().unwrap_or_default()).try_into()?,
+        })
+    }
+}
+
+/// Body of `POST /recipe` and `PATCH /recipe/<id>`.
+#[derive(Debug, Deserialize)]
+pub struct RecipeInput {
+    pub name: String,
+    pub servings: f64,
+    pub ingredients: Vec<Ingredient>,
+}
+
+/// An ingredient there isn't enough of, or that can't be used at all, in the unit its item is kept in.
+#[derive(Debug, Serialize)]
+pub struct Missing {
+    pub item: String,
+    pub name: String,
+    pub needed: f64,
+    pub stock: f64,
+    pub unit: String,
+    /// Why the ingredient can't be had however much there is, e.g. its item was deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlmostCookable {
+    pub recipe: Recipe,
+    pub missing: Vec<Missing>,
+}
+
+/// Response of `GET /recipes/cookable`.
+#[derive(Debug, Serialize)]
+pub struct Cookable {
+    /// Recipes there's enough of everything for
+    pub ready: Vec<Recipe>,
+    /// Recipes short of a few ingredients, fewest missing first
+    pub almost: Vec<AlmostCookable>,
+}
+
+/// How many missing ingredients still count as almost cookable when not asked otherwise.
+const DEFAULT_MAX_MISSING: usize = 2;
+
+pub fn recipe_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("recipes", id)
+}
+
+/// Trims the input and checks it, returning the ingredients as they're stored.
+fn validate_recipe(input: &RecipeInput) -> Result<(String, Value), Error> {
+    let name = input.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(Error::Validation("A recipe name is required".into()));
+    }
+    if !(units::valid_quantity(input.servings) && input.servings > 0.0) {
+        return Err(Error::Validation(format!("Servings must be a positive number, got {}", input.servings)));
+    }
+    if input.ingredients.is_empty() {
+        return Err(Error::Validation("A recipe needs at least one ingredient".into()));
+    }
+    let mut items: Vec<Thing> = vec![];
+    let mut ingredients = vec![];
+    for ingredient in input.ingredients.iter() {
+        let item = item_thing(&ingredient.item)?;
+        if items.contains(&item) {
+            return Err(Error::Validation(format!("{} is in the recipe more than once", item)));
+        }
+        if !(units::valid_quantity(ingredient.quantity) && ingredient.quantity > 0.0) {
+            return Err(Error::Validation(format!("Quantity of {} must be a positive number, got {}", item, ingredient.quantity)));
+        }
+        let unit = match ingredient.unit.as_deref().map(str::trim) {
+            Some(unit) if !unit.is_empty() => Value::from(units::unit(unit)?.name),
+            _ => Value::None,
+        };
+        ingredients.push(Value::Object(Object::from(map!(
+            "item".to_owned() => Value::from(item.clone()),
+            "quantity".to_owned() => Value::from(units::round(ingredient.quantity)),
+            "unit".to_owned() => unit
+        ))));
+        items.push(item);
+    }
+    Ok((name, Value::Array(Array::from(ingredients))))
+}
+
+impl DB {
+    pub async fn get_recipes(&self) -> Result<Vec<Recipe>, Error> {
+        let sql = "SELECT * FROM recipes ORDER BY name ASC";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn get_recipe(&self, id: &str) -> Result<Recipe, Error> {
+        let sql = "SELECT * FROM $th";
+        let vars: BTreeMap<String, Value> = map!("th".into() => recipe_thing(id)?.into());
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No recipe with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    async fn check_recipe_name_free(&self, name: &str, except: Option<&Thing>) -> Result<(), Error> {
+        let sql = "SELECT id FROM recipes WHERE name = $name AND id != $except";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(name),
+            "except".into() => except.map_or(Value::None, |th| th.clone().into())
+        );
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::None => Ok(()),
+            _ => Err(Error::Conflict(format!("A recipe named {} already exists", name))),
+        }
+    }
+
+    /// Checks every ingredient is an item that exists.
+    async fn check_ingredients(&self, ingredients: &[Ingredient]) -> Result<(), Error> {
+        let things = ingredients.iter().map(|ingredient| item_thing(&ingredient.item)).collect::<Result<Vec<_>, _>>()?;
+        let levels = self.levels(things.clone()).await?;
+        if let Some(missing) = things.iter().find(|th| !levels.contains_key(&th.to_string())) {
+            return Err(Error::NotFound(format!("No item with id {}", missing)));
+        }
+        // Each ingredient is converted to the unit its item is kept in whenever the recipe is used
+        for (ingredient, th) in ingredients.iter().zip(things.iter()) {
+            let Some(unit) = ingredient.unit.as_deref().map(str::trim).filter(|unit| !unit.is_empty()) else { continue };
+            let kept_in = &levels[&th.to_string()].unit;
+            units::factor(unit, kept_in)
+                .map_err(|_| Error::Validation(format!("{} is kept in {}, which {} can't be converted to", th, kept_in, unit)))?;
+        }
+        Ok(())
+    }
+
+    pub async fn create_recipe(&self, input: RecipeInput) -> Result<Recipe, Error> {
+        let (name, ingredients) = validate_recipe(&input)?;
+        self.check_recipe_name_free(&name, None).await?;
+        self.check_ingredients(&input.ingredients).await?;
+        let sql = "CREATE recipes SET name = $name, servings = $servings, ingredients = $ingredients";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(name.as_str()),
+            "servings".into() => Value::from(units::round(input.servings)),
+            "ingredients".into() => ingredients
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        W(first_result(res)?.first()).try_into()
+    }
+
+    pub async fn update_recipe(&self, id: &str, input: RecipeInput) -> Result<Recipe, Error> {
+        let th = recipe_thing(id)?;
+        let (name, ingredients) = validate_recipe(&input)?;
+        self.check_recipe_name_free(&name, Some(&th)).await?;
+        self.check_ingredients(&input.ingredients).await?;
+        let sql = "UPDATE recipes SET name = $name, servings = $servings, ingredients = $ingredients WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => th.into(),
+            "name".into() => Value::from(name.as_str()),
+            "servings".into() => Value::from(units::round(input.servings)),
+            "ingredients".into() => ingredients
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No recipe with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    /// Deletes a recipe along with the meals it's planned for.
+    pub async fn delete_recipe(&self, id: &str) -> Result<AffectedRows, Error> {
+        let _ = self.get_recipe(id).await?;
+        let mut query = Query::transaction();
+        let th = query.bind(recipe_thing(id)?);
+        query.push(format!("DELETE meal_plan WHERE recipe = {th} RETURN NONE"));
+        query.push(format!("DELETE recipes WHERE id = {th} RETURN NONE"));
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        Ok(AffectedRows { rows_affected: 1 })
+    }
+
+    /// Checks every recipe, at its own servings, against the stock there is now. An item tracked generally
+    /// counts as enough whenever there's any left, since its stock is a level rather than an amount.
+    pub async fn cookable_recipes(&self, max_missing: Option<usize>) -> Result<Cookable, Error> {
+        let max_missing = max_missing.unwrap_or(DEFAULT_MAX_MISSING);
+        let items: BTreeMap<String, Item> = self.get_all_items().await?.into_iter()
+            .filter_map(|item| Some((item.id.clone()?, item)))
+            .collect();
+
+        let mut cookable = Cookable { ready: vec![], almost: vec![] };
+        for recipe in self.get_recipes().await? {
+            let mut missing: Vec<Missing> = recipe.needs(recipe.servings, &items).into_iter()
+                .filter_map(|(item, needed)| {
+                    let missing = |needed: f64, reason: Option<String>| Missing {
+                        item: item.id.clone().unwrap_or_default(),
+                        name: item.name.clone(),
+                        needed,
+                        stock: item.stock,
+                        unit: item.unit.clone(),
+                        reason,
+                    };
+                    match needed {
+                        Err(e) => Some(missing(0.0, Some(e.to_string()))),
+                        Ok(needed) if item.track_general.unwrap_or(false) => (item.stock <= 0.0).then(|| missing(needed, None)),
+                        Ok(needed) => (item.stock < needed).then(|| missing(needed, None)),
+                    }
+                })
+                .collect();
+            // An ingredient whose item was deleted since can't be had at all
+            for ingredient in recipe.ingredients.iter().filter(|ingredient| !items.contains_key(&ingredient.item)) {
+                missing.push(Missing {
+                    item: ingredient.item.clone(),
+                    name: ingredient.item.clone(),
+                    needed: ingredient.quantity,
+                    stock: 0.0,
+                    unit: ingredient.unit.clone().unwrap_or_else(units::default_unit),
+                    reason: Some("The item no longer exists".into()),
+                });
+            }
+            match missing.len() {
+                0 => cookable.ready.push(recipe),
+                n if n <= max_missing => cookable.almost.push(AlmostCookable { recipe, missing }),
+                _ => {},
+            }
+        }
+        // Recipes come by name, so a stable sort keeps those tied on missing in name order
+        cookable.almost.sort_by_key(|almost| almost.missing.len());
+
+        Ok(cookable)
+    }
+
+    /// Consumes every ingredient of a recipe in one transaction, scaled from the recipe's servings to `servings`.
+    /// Ingredients that have run out are handled by `policy` as in any other consumption.
+    pub async fn cook_recipe(&self, id: &str, servings: Option<f64>, policy: OverConsumption, source: &Source) -> Result<BatchResult, Error> {
+        let recipe = self.get_recipe(id).await?;
+        let servings = servings.unwrap_or(recipe.servings);
+        if !(units::valid_quantity(servings) && servings > 0.0) {
+            return Err(Error::Validation(format!("Servings must be a positive number, got {}", servings)));
+        }
+        let scale = servings / recipe.servings;
+        let data = recipe.ingredients.into_iter()
+            .map(|ingredient| RestockItem {
+                id: ingredient.item,
+                count: units::round(ingredient.quantity * scale),
+                unit: ingredient.unit,
+                package: None,
+                best_by: None,
+                price: None,
+                unit_price: None,
+                store: None,
+            })
+            .collect();
+
+        self.consume_items(data, None, policy, source).await
+    }
+}
@@ -0,0 +1,186 @@
+use log::warn;
+
+use crate::{db::{all_results, DB}, prelude::Error};
+
+/// Invariants the database enforces no matter which client is writing.
+const ITEMS_SCHEMA: &str = "
+    DEFINE FIELD stock ON TABLE items TYPE number ASSERT $value >= 0;
+    DEFINE FIELD desired_stock ON TABLE items TYPE number ASSERT $value >= 0;
+    DEFINE FIELD unit ON TABLE items TYPE string DEFAULT 'count';
+    DEFINE FIELD barcodes ON TABLE items TYPE array<string> DEFAULT [];
+    DEFINE INDEX items_barcodes ON TABLE items COLUMNS barcodes UNIQUE;
+    DEFINE FIELD store ON TABLE items TYPE option<record<stores>>;
+    DEFINE FIELD aisle ON TABLE items TYPE option<string>;
+";
+
+/// Every change to an item's stock, written by [`crate::ledger::recorded`] alongside the change itself.
+const STOCK_EVENTS_SCHEMA: &str = "
+    DEFINE TABLE stock_events SCHEMAFULL;
+    DEFINE FIELD item ON TABLE stock_events TYPE record<items>;
+    DEFINE FIELD name ON TABLE stock_events TYPE string;
+    DEFINE FIELD category ON TABLE stock_events TYPE string;
+    DEFINE FIELD unit ON TABLE stock_events TYPE option<string>;
+    DEFINE FIELD delta ON TABLE stock_events TYPE number;
+    DEFINE FIELD stock ON TABLE stock_events TYPE number;
+    DEFINE FIELD operation ON TABLE stock_events TYPE string;
+    DEFINE FIELD source ON TABLE stock_events TYPE string;
+    DEFINE FIELD time ON TABLE stock_events TYPE datetime;
+    DEFINE INDEX stock_events_item ON TABLE stock_events COLUMNS item;
+    DEFINE INDEX stock_events_time ON TABLE stock_events COLUMNS time;
+    DEFINE INDEX stock_events_category ON TABLE stock_events COLUMNS category;
+";
+
+/// Categories items can be grouped under, see [`crate::categories`].
+const CATEGORIES_SCHEMA: &str = "
+    DEFINE TABLE categories SCHEMAFULL;
+    DEFINE FIELD name ON TABLE categories TYPE string;
+    DEFINE FIELD position ON TABLE categories TYPE int;
+    DEFINE FIELD colour ON TABLE categories TYPE option<string>;
+    DEFINE FIELD icon ON TABLE categories TYPE option<string>;
+    DEFINE FIELD parent ON TABLE categories TYPE option<record<categories>>;
+    DEFINE INDEX categories_name ON TABLE categories COLUMNS name UNIQUE;
+";
+
+/// Where stock is kept, see [`crate::locations`]. `items.stock` is always the sum of an item's `stock_levels`.
+const LOCATIONS_SCHEMA: &str = "
+    DEFINE TABLE locations SCHEMAFULL;
+    DEFINE FIELD name ON TABLE locations TYPE string;
+    DEFINE FIELD position ON TABLE locations TYPE int;
+    DEFINE INDEX locations_name ON TABLE locations COLUMNS name UNIQUE;
+    DEFINE TABLE stock_levels SCHEMAFULL;
+    DEFINE FIELD item ON TABLE stock_levels TYPE record<items>;
+    DEFINE FIELD location ON TABLE stock_levels TYPE record<locations>;
+    DEFINE FIELD stock ON TABLE stock_levels TYPE number ASSERT $value >= 0;
+    DEFINE INDEX stock_levels_item ON TABLE stock_levels COLUMNS item;
+    DEFINE INDEX stock_levels_location ON TABLE stock_levels COLUMNS location;
+";
+
+/// What each level is made of, see [`crate::lots`]. An item's lots at a location add up to its `stock_levels` record there.
+const LOTS_SCHEMA: &str = "
+    DEFINE TABLE lots SCHEMAFULL;
+    DEFINE FIELD item ON TABLE lots TYPE record<items>;
+    DEFINE FIELD location ON TABLE lots TYPE record<locations>;
+    DEFINE FIELD stock ON TABLE lots TYPE number ASSERT $value >= 0;
+    DEFINE FIELD best_by ON TABLE lots TYPE option<datetime>;
+    DEFINE FIELD received ON TABLE lots TYPE datetime;
+    DEFINE INDEX lots_item ON TABLE lots COLUMNS item;
+    DEFINE INDEX lots_best_by ON TABLE lots COLUMNS best_by;
+";
+
+/// Meals made of items, see [`crate::recipes`].
+const RECIPES_SCHEMA: &str = "
+    DEFINE TABLE recipes SCHEMAFULL;
+    DEFINE FIELD name ON TABLE recipes TYPE string;
+    DEFINE FIELD servings ON TABLE recipes TYPE number ASSERT $value > 0;
+    DEFINE FIELD ingredients ON TABLE recipes TYPE array<object>;
+    DEFINE FIELD ingredients.*.item ON TABLE recipes TYPE record<items>;
+    DEFINE FIELD ingredients.*.quantity ON TABLE recipes TYPE number ASSERT $value > 0;
+    DEFINE FIELD ingredients.*.unit ON TABLE recipes TYPE option<string>;
+    DEFINE INDEX recipes_name ON TABLE recipes COLUMNS name UNIQUE;
+";
+
+const MEAL_PLAN_SCHEMA: &str = "
+    DEFINE TABLE meal_plan SCHEMAFULL;
+    DEFINE FIELD date ON TABLE meal_plan TYPE string;
+    DEFINE FIELD recipe ON TABLE meal_plan TYPE record<recipes>;
+    DEFINE FIELD servings ON TABLE meal_plan TYPE number ASSERT $value > 0;
+    DEFINE INDEX meal_plan_date ON TABLE meal_plan COLUMNS date;
+";
+
+const STORES_SCHEMA: &str = "
+    DEFINE TABLE stores SCHEMAFULL;
+    DEFINE FIELD name ON TABLE stores TYPE string;
+    DEFINE FIELD aisles ON TABLE stores TYPE array<string> DEFAULT [];
+    DEFINE INDEX stores_name ON TABLE stores COLUMNS name UNIQUE;
+";
+
+const SHOPPING_EXTRAS_SCHEMA: &str = "
+    DEFINE TABLE shopping_extras SCHEMAFULL;
+    DEFINE FIELD item ON TABLE shopping_extras TYPE option<record<items>>;
+    DEFINE FIELD name ON TABLE shopping_extras TYPE string;
+    DEFINE FIELD quantity ON TABLE shopping_extras TYPE option<number> ASSERT $value = NONE OR $value > 0;
+    DEFINE FIELD unit ON TABLE shopping_extras TYPE option<string>;
+    DEFINE FIELD category ON TABLE shopping_extras TYPE option<string>;
+    DEFINE FIELD added ON TABLE shopping_extras TYPE datetime;
+";
+
+const TRIPS_SCHEMA: &str = "
+    DEFINE TABLE trips SCHEMAFULL;
+    DEFINE FIELD store ON TABLE trips TYPE option<record<stores>>;
+    DEFINE FIELD store_name ON TABLE trips TYPE option<string>;
+    DEFINE FIELD started ON TABLE trips TYPE datetime;
+    DEFINE FIELD finished ON TABLE trips TYPE option<datetime>;
+    DEFINE INDEX trips_finished ON TABLE trips COLUMNS finished;
+
+    DEFINE TABLE trip_lines SCHEMAFULL;
+    DEFINE FIELD trip ON TABLE trip_lines TYPE record<trips>;
+    DEFINE FIELD item ON TABLE trip_lines TYPE option<record<items>>;
+    DEFINE FIELD name ON TABLE trip_lines TYPE string;
+    DEFINE FIELD unit ON TABLE trip_lines TYPE string;
+    DEFINE FIELD quantity ON TABLE trip_lines TYPE option<number>;
+    DEFINE FIELD bought ON TABLE trip_lines TYPE option<number> ASSERT $value = NONE OR $value >= 0;
+    DEFINE FIELD price ON TABLE trip_lines TYPE option<number> ASSERT $value = NONE OR $value >= 0;
+    DEFINE FIELD checked ON TABLE trip_lines TYPE bool DEFAULT false;
+    DEFINE FIELD aisle ON TABLE trip_lines TYPE option<string>;
+    DEFINE FIELD extras ON TABLE trip_lines TYPE array<record<shopping_extras>> DEFAULT [];
+    DEFINE FIELD position ON TABLE trip_lines TYPE int;
+    DEFINE INDEX trip_lines_trip ON TABLE trip_lines COLUMNS trip;
+";
+
+const PURCHASES_SCHEMA: &str = "
+    DEFINE TABLE purchases SCHEMAFULL;
+    DEFINE FIELD item ON TABLE purchases TYPE record<items>;
+    DEFINE FIELD name ON TABLE purchases TYPE string;
+    DEFINE FIELD category ON TABLE purchases TYPE string;
+    DEFINE FIELD quantity ON TABLE purchases TYPE number ASSERT $value > 0;
+    DEFINE FIELD unit ON TABLE purchases TYPE string;
+    DEFINE FIELD price ON TABLE purchases TYPE number ASSERT $value >= 0;
+    DEFINE FIELD store ON TABLE purchases TYPE option<record<stores>>;
+    DEFINE FIELD store_name ON TABLE purchases TYPE option<string>;
+    DEFINE FIELD time ON TABLE purchases TYPE datetime;
+    DEFINE INDEX purchases_item ON TABLE purchases COLUMNS item;
+    DEFINE INDEX purchases_time ON TABLE purchases COLUMNS time;
+";
+
+/// Monthly spending limits, see [`crate::budgets`]. A budget without a category is the overall one.
+const BUDGETS_SCHEMA: &str = "
+    DEFINE TABLE budgets SCHEMAFULL;
+    DEFINE FIELD category ON TABLE budgets TYPE option<record<categories>>;
+    DEFINE FIELD amount ON TABLE budgets TYPE number ASSERT $value >= 0;
+";
+
+impl DB {
+    /// Brings existing data in line with the schema, then (re)defines it. Safe to run on every start.
+    pub async fn define_schema(&self) -> Result<(), Error> {
+        // Records written before the assertions existed would otherwise fail every later update
+        let sql = "
+            UPDATE items SET stock = 0 WHERE stock < 0;
+            UPDATE items SET desired_stock = 0 WHERE desired_stock < 0;
+            UPDATE items SET unit = 'count' WHERE unit = NONE RETURN NONE;
+            UPDATE items SET barcodes = [] WHERE barcodes = NONE RETURN NONE;
+        ";
+        let fixed = all_results(self.execute(sql, None).await?)?;
+        for (field, value) in ["stock", "desired_stock"].iter().zip(fixed) {
+            if let surrealdb::sql::Value::Array(records) = value {
+                if !records.is_empty() {
+                    warn!(target: "database", "Reset negative {} to 0 on {} items", field, records.len());
+                }
+            }
+        }
+
+        let _ = all_results(self.execute(ITEMS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(STOCK_EVENTS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(CATEGORIES_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(LOCATIONS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(LOTS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(RECIPES_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(MEAL_PLAN_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(STORES_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(SHOPPING_EXTRAS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(TRIPS_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(PURCHASES_SCHEMA, None).await?)?;
+        let _ = all_results(self.execute(BUDGETS_SCHEMA, None).await?)?;
+        self.backfill_categories().await?;
+        Ok(())
+    }
+}
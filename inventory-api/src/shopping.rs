@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{db::{first_result, AffectedRows, Item, DB}, meal_plan::{MealNeed, PlannedNeed}, prelude::{Error, W}, query::{item_thing, record_thing}, units, utils::macros::map};
+
+/// Where extras that aren't an item go when no category is given.
+const OTHER_CATEGORY: &str = "Other";
+
+/// Something put on the shopping list by hand: more of an item than its desired stock calls for,
+/// or something that isn't tracked at all.
+#[derive(Debug, Clone, Serialize)]
+pub struct Extra {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    pub added: DateTime<Utc>,
+}
+
+impl TryFrom<W<Value>> for Extra {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let quantity = match map.get("quantity") {
+            None | Some(Value::None) | Some(Value::Null) => None,
+            Some(quantity) => Some(W(quantity.clone()).try_into()?),
+        };
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            item: W(map.get("item").cloned().unwrap_or_default()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            quantity,
+            unit: W(map.get("unit").cloned().unwrap_or_default()).try_into()?,
+            category: W(map.get("category").cloned().unwrap_or_default()).try_into()?,
+            added: W(map["added"].clone()).try_into()?,
+        })
+    }
+}
+
+/// Body of `POST /shopping-list/extras`. Either `item` or `name` is required; an item's own name and category are used.
+#[derive(Debug, Deserialize)]
+pub struct ExtraInput {
+    #[serde(default)]
+    pub item: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// A line of the shopping list. `quantity` is what to buy in `unit`, and is `None` only for an
+/// extra that wasn't given one. The reasons it's there are spelled out: the gap to `desired_stock`,
+/// the planned meals that use it, and any extras.
+#[derive(Debug, Serialize)]
+pub struct ShoppingEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    pub name: String,
+    pub quantity: Option<f64>,
+    pub unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stock: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desired_stock: Option<f64>,
+    /// Where the item is bought, so the list can be split by store and walked in aisle order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aisle: Option<String>,
+    pub meals: Vec<MealNeed>,
+    pub extras: Vec<Extra>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShoppingCategory {
+    pub name: String,
+    pub entries: Vec<ShoppingEntry>,
+}
+
+/// Response of `GET /shopping-list`: categories in the order they're shown, entries by name.
+#[derive(Debug, Serialize)]
+pub struct ShoppingList {
+    pub categories: Vec<ShoppingCategory>,
+}
+
+pub fn extra_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("shopping_extras", id)
+}
+
+/// What an item needs buying before extras. An item with planned meals needs what they do (which
+/// already includes the gap to desired stock); one tracked generally never needs less than that gap.
+fn base_quantity(item: &Item, planned: Option<&PlannedNeed>) -> f64 {
+    let gap = (item.desired_stock - item.stock).max(0.0);
+    match planned {
+        Some(need) if item.track_general.unwrap_or(false) => gap.max(need.to_buy),
+        Some(need) => need.to_buy,
+        None => gap,
+    }
+}
+
+impl DB {
+    pub async fn get_extras(&self) -> Result<Vec<Extra>, Error> {
+        let sql = "SELECT * FROM shopping_extras ORDER BY added ASC";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn add_extra(&self, input: ExtraInput) -> Result<Extra, Error> {
+        let trimmed = |value: Option<String>| value.map(|v| v.trim().to_owned()).filter(|v| !v.is_empty());
+        let quantity = match input.quantity {
+            Some(quantity) if !(units::valid_quantity(quantity) && quantity > 0.0) => {
+                return Err(Error::Validation(format!("Quantity must be a positive number, got {}", quantity)));
+            },
+            quantity => quantity.map(units::round),
+        };
+        let unit = match trimmed(input.unit) {
+            Some(unit) => Some(units::unit(&unit)?.name.to_owned()),
+            None => None,
+        };
+        let (item, name, category) = match trimmed(input.item) {
+            Some(id) => {
+                let item = self.get_item(&id).await?;
+                if let Some(unit) = unit.as_deref() {
+                    units::factor(unit, &item.unit)?;
+                }
+                (Value::from(item_thing(&id)?), item.name, Value::None)
+            },
+            None => match trimmed(input.name) {
+                Some(name) => (Value::None, name, trimmed(input.category).map_or(Value::None, Value::from)),
+                None => return Err(Error::Validation("An extra needs an item or a name".into())),
+            },
+        };
+        let sql = "CREATE shopping_extras SET item = $item, name = $name, quantity = $quantity, unit = $unit, category = $category, added = time::now()";
+        let vars: BTreeMap<String, Value> = map!(
+            "item".into() => item,
+            "name".into() => Value::from(name),
+            "quantity".into() => quantity.map_or(Value::None, Value::from),
+            "unit".into() => unit.map_or(Value::None, Value::from),
+            "category".into() => category
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        W(first_result(res)?.first()).try_into()
+    }
+
+    pub async fn delete_extra(&self, id: &str) -> Result<AffectedRows, Error> {
+        let sql = "DELETE shopping_extras WHERE id = $th RETURN BEFORE";
+        let vars: BTreeMap<String, Value> = map!("th".into() => extra_thing(id)?.into());
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::Object(_) => Ok(AffectedRows { rows_affected: 1 }),
+            _ => Err(Error::NotFound(format!("No shopping list extra with id {}", id))),
+        }
+    }
+
+    /// Removes every extra, e.g. once the shopping is done.
+    pub async fn clear_extras(&self) -> Result<AffectedRows, Error> {
+        let sql = "DELETE shopping_extras RETURN BEFORE";
+        let res = self.execute(sql, None).await?;
+        let array: Array = W(first_result(res)?).try_into()?;
+
+        Ok(AffectedRows { rows_affected: array.len() })
+    }
+
+    /// Everything there is to buy: each item short of its desired stock or needed for a meal planned in
+    /// the week from `from`, plus the extras. Quantities of an item are in its own unit, with an extra
+    /// in a unit that can't be converted counted as it is.
+    pub async fn shopping_list(&self, from: NaiveDate, to: NaiveDate) -> Result<ShoppingList, Error> {
+        let items = self.get_all_items().await?;
+        let mut planned: BTreeMap<String, PlannedNeed> = self.planned_needs(from, to).await?.into_iter()
+            .map(|need| (need.item.clone(), need))
+            .collect();
+        let mut extras: BTreeMap<Option<String>, Vec<Extra>> = BTreeMap::new();
+        for extra in self.get_extras().await? {
+            extras.entry(extra.item.clone()).or_default().push(extra);
+        }
+
+        let mut by_category: BTreeMap<String, Vec<ShoppingEntry>> = BTreeMap::new();
+        for item in items {
+            let Some(id) = item.id.clone() else { continue };
+            let planned = planned.remove(&id);
+            let extras = extras.remove(&Some(id.clone())).unwrap_or_default();
+            let extra_quantity: f64 = extras.iter()
+                .map(|extra| {
+                    let quantity = extra.quantity.unwrap_or(1.0);
+                    match extra.unit.as_deref() {
+                        Some(unit) => units::convert(quantity, unit, &item.unit).unwrap_or(quantity),
+                        None => quantity,
+                    }
+                })
+                .sum();
+            let quantity = units::round(base_quantity(&item, planned.as_ref()) + extra_quantity);
+            if quantity <= 0.0 {
+                continue;
+            }
+            by_category.entry(item.category.clone()).or_default().push(ShoppingEntry {
+                item: Some(id),
+                name: item.name,
+                quantity: Some(quantity),
+                unit: item.unit,
+                stock: Some(item.stock),
+                desired_stock: Some(item.desired_stock),
+                store: item.store,
+                aisle: item.aisle,
+                meals: planned.map(|need| need.meals).unwrap_or_default(),
+                extras,
+            });
+        }
+        // Extras of items deleted since are dropped along with the item, so only untracked ones are left
+        for extra in extras.into_values().flatten() {
+            by_category.entry(extra.category.clone().unwrap_or_else(|| OTHER_CATEGORY.to_owned())).or_default().push(ShoppingEntry {
+                item: None,
+                name: extra.name.clone(),
+                quantity: extra.quantity,
+                unit: extra.unit.clone().unwrap_or_else(units::default_unit),
+                stock: None,
+                desired_stock: None,
+                store: None,
+                aisle: None,
+                meals: vec![],
+                extras: vec![extra],
+            });
+        }
+
+        // Categories with a record go in their order, then any others by name
+        let mut categories = vec![];
+        for category in self.get_categories().await? {
+            if let Some(entries) = by_category.remove(&category.name) {
+                categories.push(ShoppingCategory { name: category.name, entries });
+            }
+        }
+        categories.extend(by_category.into_iter().map(|(name, entries)| ShoppingCategory { name, entries }));
+        for category in categories.iter_mut() {
+            category.entries.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        Ok(ShoppingList { categories })
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{db::{all_results, first_result, found_item, AffectedRows, Item, DB}, prelude::{Error, W}, query::{item_thing, record_thing, Query}, utils::macros::map};
+
+/// A shop, with its aisles in the order they're walked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Store {
+    pub id: String,
+    pub name: String,
+    pub aisles: Vec<String>,
+}
+
+impl TryFrom<W<Value>> for Store {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let aisles: Array = W(map.get("aisles").cloned().unwrap_or_default()).try_into().unwrap_or_default();
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            aisles: aisles.into_iter().map(|aisle| W(aisle).try_into()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Store {
+    /// Where an aisle comes in the walk through the store. Aisles the store doesn't list go last.
+    pub fn aisle_position(&self, aisle: Option<&str>) -> usize {
+        aisle.and_then(|aisle| self.aisles.iter().position(|a| a == aisle)).unwrap_or(self.aisles.len())
+    }
+}
+
+/// Body of `POST /store` and `PATCH /store/<id>`.
+#[derive(Debug, Deserialize)]
+pub struct StoreInput {
+    pub name: String,
+    #[serde(default)]
+    pub aisles: Vec<String>,
+}
+
+/// Body of `PATCH /item/<id>/store`. Leaving `store` out takes the item off every store.
+#[derive(Debug, Deserialize)]
+pub struct ItemStoreInput {
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(default)]
+    pub aisle: Option<String>,
+}
+
+pub fn store_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("stores", id)
+}
+
+/// Trims the name and aisles, dropping blank aisles, and checks no aisle is listed twice.
+fn validate_store(input: StoreInput) -> Result<StoreInput, Error> {
+    let name = input.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(Error::Validation("A store name is required".into()));
+    }
+    let mut aisles: Vec<String> = vec![];
+    for aisle in input.aisles.iter().map(|aisle| aisle.trim()).filter(|aisle| !aisle.is_empty()) {
+        if aisles.iter().any(|a| a == aisle) {
+            return Err(Error::Validation(format!("Aisle {} is listed more than once", aisle)));
+        }
+        aisles.push(aisle.to_owned());
+    }
+    Ok(StoreInput { name, aisles })
+}
+
+fn aisles_value(aisles: &[String]) -> Value {
+    Value::Array(Array::from(aisles.iter().map(|aisle| Value::from(aisle.as_str())).collect::<Vec<_>>()))
+}
+
+impl DB {
+    pub async fn get_stores(&self) -> Result<Vec<Store>, Error> {
+        let sql = "SELECT * FROM stores ORDER BY name ASC";
+        let res = self.execute(sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    pub async fn get_store(&self, id: &str) -> Result<Store, Error> {
+        let sql = "SELECT * FROM $th";
+        let vars: BTreeMap<String, Value> = map!("th".into() => store_thing(id)?.into());
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No store with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    async fn check_store_name_free(&self, name: &str, except: Option<&Thing>) -> Result<(), Error> {
+        let sql = "SELECT id FROM stores WHERE name = $name AND id != $except";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(name),
+            "except".into() => except.map_or(Value::None, |th| th.clone().into())
+        );
+        match first_result(self.execute(sql, Some(vars)).await?)?.first() {
+            Value::None => Ok(()),
+            _ => Err(Error::Conflict(format!("A store named {} already exists", name))),
+        }
+    }
+
+    pub async fn create_store(&self, input: StoreInput) -> Result<Store, Error> {
+        let input = validate_store(input)?;
+        self.check_store_name_free(&input.name, None).await?;
+        let sql = "CREATE stores SET name = $name, aisles = $aisles";
+        let vars: BTreeMap<String, Value> = map!(
+            "name".into() => Value::from(input.name.as_str()),
+            "aisles".into() => aisles_value(&input.aisles)
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        W(first_result(res)?.first()).try_into()
+    }
+
+    /// Renames a store or changes its aisles. Items in an aisle that's no longer listed keep it, and are
+    /// put after the listed aisles until they're moved.
+    pub async fn update_store(&self, id: &str, input: StoreInput) -> Result<Store, Error> {
+        let th = store_thing(id)?;
+        let input = validate_store(input)?;
+        self.check_store_name_free(&input.name, Some(&th)).await?;
+        let sql = "UPDATE stores SET name = $name, aisles = $aisles WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => th.into(),
+            "name".into() => Value::from(input.name.as_str()),
+            "aisles".into() => aisles_value(&input.aisles)
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No store with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    /// Deletes a store, taking its items off it.
+    pub async fn delete_store(&self, id: &str) -> Result<AffectedRows, Error> {
+        let _ = self.get_store(id).await?;
+        let mut query = Query::transaction();
+        let th = query.bind(store_thing(id)?);
+        query.push(format!("UPDATE items SET store = NONE, aisle = NONE WHERE store = {th} RETURN NONE"));
+        query.push(format!("DELETE stores WHERE id = {th} RETURN NONE"));
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        Ok(AffectedRows { rows_affected: 1 })
+    }
+
+    /// Sets the store an item is usually bought at, and the aisle it's in there.
+    pub async fn set_item_store(&self, id: &str, input: ItemStoreInput) -> Result<Item, Error> {
+        let store = match input.store.as_deref().map(str::trim).filter(|store| !store.is_empty()) {
+            Some(store) => Value::from(store_thing(&self.get_store(store).await?.id)?),
+            None => Value::None,
+        };
+        let aisle = match input.aisle.as_deref().map(str::trim).filter(|aisle| !aisle.is_empty()) {
+            Some(_) if store.is_none() => return Err(Error::Validation("An aisle needs a store".into())),
+            Some(aisle) => Value::from(aisle),
+            None => Value::None,
+        };
+        let sql = "UPDATE items SET store = $store, aisle = $aisle WHERE id = $th";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => item_thing(id)?.into(),
+            "store".into() => store,
+            "aisle".into() => aisle
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        found_item(res, id)
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Array, Object, Thing, Value};
+
+use crate::{
+    batch::BatchResult, db::{all_results, first_result, AffectedRows, DB}, ledger::Source, locations::default_location, lots::optional_time, meal_plan::plan_range, prelude::{Error, W}, query::{item_thing, record_thing, Query}, shopping::{extra_thing, ShoppingEntry}, stores::store_thing, units, utils::macros::map, RestockItem
+};
+
+/// A line of a shopping trip: something on the shopping list when the trip started, and whether it's
+/// in the basket yet. `bought` is what was actually picked up when that isn't `quantity`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TripLine {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    pub name: String,
+    pub unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bought: Option<f64>,
+    /// What was paid for what was bought, recorded as a purchase when the trip is finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    pub checked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aisle: Option<String>,
+    /// The shopping list extras the line covers, removed once it's bought
+    pub extras: Vec<String>,
+}
+
+impl TryFrom<W<Value>> for TripLine {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let number = |key: &str| -> Result<Option<f64>, Error> {
+            match map.get(key) {
+                None | Some(Value::None) | Some(Value::Null) => Ok(None),
+                Some(value) => Ok(Some(W(value.clone()).try_into()?)),
+            }
+        };
+        let extras: Array = W(map.get("extras").cloned().unwrap_or_default()).try_into().unwrap_or_default();
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            item: W(map.get("item").cloned().unwrap_or_default()).try_into()?,
+            name: W(map["name"].clone()).try_into()?,
+            unit: W(map["unit"].clone()).try_into()?,
+            quantity: number("quantity")?,
+            bought: number("bought")?,
+            price: number("price")?,
+            checked: W(map["checked"].clone()).try_into()?,
+            aisle: W(map.get("aisle").cloned().unwrap_or_default()).try_into()?,
+            extras: extras.into_iter().map(|extra| W(extra).try_into()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl TripLine {
+    /// What was put in the basket, which unless said otherwise is what the list asked for.
+    pub fn bought(&self) -> Option<f64> {
+        self.bought.or(self.quantity)
+    }
+}
+
+/// A trip to the shops. Until it's `finished` it's the trip in progress, which lines are checked off on.
+#[derive(Debug, Serialize)]
+pub struct Trip {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    /// Kept apart from `store` so a trip still says where it was after the store is deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_name: Option<String>,
+    pub started: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    pub lines: Vec<TripLine>,
+}
+
+impl TryFrom<W<Value>> for Trip {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Error> {
+        let map: Object = val.try_into()?;
+        let lines: Array = W(map.get("lines").cloned().unwrap_or_default()).try_into().unwrap_or_default();
+        Ok(Self {
+            id: W(map["id"].clone()).try_into()?,
+            store: W(map.get("store").cloned().unwrap_or_default()).try_into()?,
+            store_name: W(map.get("store_name").cloned().unwrap_or_default()).try_into()?,
+            started: W(map["started"].clone()).try_into()?,
+            finished: optional_time(map.get("finished"))?,
+            lines: lines.into_iter().map(|line| W(line).try_into()).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Body of `POST /trip`. With a store, the trip takes what's bought there and what isn't bought
+/// anywhere in particular, in aisle order.
+#[derive(Debug, Default, Deserialize)]
+pub struct TripInput {
+    #[serde(default)]
+    pub store: Option<String>,
+}
+
+/// Body of `PATCH /trip/<id>/line/<line>`. Leaving `bought` out means the line's quantity was bought.
+#[derive(Debug, Deserialize)]
+pub struct TripLineChange {
+    pub checked: bool,
+    #[serde(default)]
+    pub bought: Option<f64>,
+    /// What was paid for all of it
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+/// Response of `PATCH /trip/<id>/finish`: the trip as recorded and what restocking it did.
+#[derive(Debug, Serialize)]
+pub struct FinishedTrip {
+    pub trip: Trip,
+    pub result: BatchResult,
+}
+
+pub fn trip_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("trips", id)
+}
+
+pub fn trip_line_thing(id: &str) -> Result<Thing, Error> {
+    record_thing("trip_lines", id)
+}
+
+/// Trips with their lines in the order they're walked.
+const TRIP_SELECT: &str = "SELECT *, (SELECT * FROM trip_lines WHERE trip = $parent.id ORDER BY position ASC) AS lines FROM trips";
+
+impl DB {
+    /// The trip in progress, if there is one.
+    pub async fn current_trip(&self) -> Result<Option<Trip>, Error> {
+        let sql = format!("{TRIP_SELECT} WHERE finished = NONE LIMIT 1");
+        let res = self.execute(&sql, None).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Ok(None),
+            value => Ok(Some(W(value).try_into()?)),
+        }
+    }
+
+    pub async fn get_trip(&self, id: &str) -> Result<Trip, Error> {
+        let sql = format!("{TRIP_SELECT} WHERE id = $th");
+        let vars: BTreeMap<String, Value> = map!("th".into() => trip_thing(id)?.into());
+        let res = self.execute(&sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No trip with id {}", id))),
+            value => W(value).try_into(),
+        }
+    }
+
+    /// Finished trips, latest first.
+    pub async fn get_trips(&self) -> Result<Vec<Trip>, Error> {
+        let sql = format!("{TRIP_SELECT} WHERE finished != NONE ORDER BY finished DESC");
+        let res = self.execute(&sql, None).await?;
+
+        let array: Array = W(first_result(res)?).try_into()?;
+        array.into_iter().map(|value| W(value).try_into()).collect()
+    }
+
+    /// Starts a trip from the shopping list as it is now. Only one trip can be in progress at a time.
+    pub async fn start_trip(&self, input: TripInput) -> Result<Trip, Error> {
+        if let Some(trip) = self.current_trip().await? {
+            return Err(Error::Conflict(format!("Trip {} is still in progress", trip.id)));
+        }
+        let store = match input.store.as_deref().map(str::trim).filter(|store| !store.is_empty()) {
+            Some(id) => Some(self.get_store(id).await?),
+            None => None,
+        };
+        let (from, to) = plan_range(None, None)?;
+        let mut entries: Vec<ShoppingEntry> = self.shopping_list(from, to).await?
+            .categories
+            .into_iter()
+            .flat_map(|category| category.entries)
+            .collect();
+        if let Some(store) = store.as_ref() {
+            entries.retain(|entry| entry.store.is_none() || entry.store.as_ref() == Some(&store.id));
+            // Things not bought anywhere in particular have no aisle there, so they're picked up last
+            entries.sort_by(|a, b| {
+                let key = |entry: &ShoppingEntry| (entry.store.is_none(), store.aisle_position(entry.aisle.as_deref()));
+                key(a).cmp(&key(b)).then_with(|| a.aisle.cmp(&b.aisle)).then_with(|| a.name.cmp(&b.name))
+            });
+        }
+        if entries.is_empty() {
+            return Err(Error::Validation("There's nothing on the shopping list to buy".into()));
+        }
+
+        let mut query = Query::transaction();
+        let trip = query.local();
+        let store_th = query.bind(store.as_ref().map_or(Ok(Value::None), |store| store_thing(&store.id).map(Value::from))?);
+        let store_name = query.bind(store.as_ref().map_or(Value::None, |store| Value::from(store.name.as_str())));
+        query.push(format!("LET {trip} = array::first((CREATE trips SET store = {store_th}, store_name = {store_name}, started = time::now() RETURN id)).id"));
+        for (position, entry) in entries.into_iter().enumerate() {
+            let item = query.bind(entry.item.as_deref().map_or(Ok(Value::None), |id| item_thing(id).map(Value::from))?);
+            let name = query.bind(entry.name);
+            let unit = query.bind(entry.unit);
+            let quantity = query.bind(entry.quantity.map_or(Value::None, Value::from));
+            let aisle = query.bind(entry.aisle.map_or(Value::None, Value::from));
+            let extras = entry.extras.iter().map(|extra| extra_thing(&extra.id).map(Value::from)).collect::<Result<Vec<_>, _>>()?;
+            let extras = query.bind(Value::Array(Array::from(extras)));
+            let position = query.bind(position as i64);
+            query.push(format!("CREATE trip_lines SET trip = {trip}, item = {item}, name = {name}, unit = {unit}, quantity = {quantity}, aisle = {aisle}, extras = {extras}, checked = false, position = {position} RETURN NONE"));
+        }
+        let (sql, vars) = query.commit();
+        all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        self.current_trip().await?.ok_or_else(|| Error::NotFound("The trip was not started".into()))
+    }
+
+    /// Checks a line of the trip in progress off, or back on.
+    pub async fn check_trip_line(&self, trip: &str, id: &str, change: TripLineChange) -> Result<TripLine, Error> {
+        if let Some(bought) = change.bought {
+            if !units::valid_quantity(bought) {
+                return Err(Error::Validation(format!("Bought must be a number of zero or more, got {}", bought)));
+            }
+        }
+        if let Some(price) = change.price {
+            if !units::valid_quantity(price) {
+                return Err(Error::Validation(format!("Price must be a number of zero or more, got {}", price)));
+            }
+        }
+        let sql = "UPDATE trip_lines SET checked = $checked, bought = $bought, price = $price WHERE id = $th AND trip = $trip AND trip.finished = NONE";
+        let vars: BTreeMap<String, Value> = map!(
+            "th".into() => trip_line_thing(id)?.into(),
+            "trip".into() => trip_thing(trip)?.into(),
+            "checked".into() => Value::from(change.checked),
+            "bought".into() => change.bought.map_or(Value::None, |bought| Value::from(units::round(bought))),
+            "price".into() => change.price.map_or(Value::None, Value::from)
+        );
+        let res = self.execute(sql, Some(vars)).await?;
+
+        match first_result(res)?.first() {
+            Value::None => Err(Error::NotFound(format!("No line {} on trip {} in progress", id, trip))),
+            value => W(value).try_into(),
+        }
+    }
+
+    /// Drops a trip in progress without restocking anything.
+    pub async fn abandon_trip(&self, id: &str) -> Result<AffectedRows, Error> {
+        if self.get_trip(id).await?.finished.is_some() {
+            return Err(Error::NotFound(format!("No trip in progress with id {}", id)));
+        }
+        let mut query = Query::transaction();
+        let th = query.bind(trip_thing(id)?);
+        query.push(format!("DELETE trip_lines WHERE trip = {th} RETURN NONE"));
+        query.push(format!("DELETE trips WHERE id = {th} AND finished = NONE RETURN NONE"));
+        let (sql, vars) = query.commit();
+        let _ = all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        Ok(AffectedRows { rows_affected: 1 })
+    }
+
+    /// Records the trip as finished, then restocks everything checked off in one batch, with the prices paid
+    /// recorded as purchases at the trip's store, and takes the extras it bought off the shopping list. Lines
+    /// left unchecked stay on the list for next time. Finishing comes first so a trip finished twice at once
+    /// is only restocked once; it's taken back if the restock fails.
+    pub async fn finish_trip(&self, id: &str, source: &Source) -> Result<FinishedTrip, Error> {
+        let th = trip_thing(id)?;
+        let sql = "UPDATE trips SET finished = time::now() WHERE id = $th AND finished = NONE RETURN BEFORE";
+        let vars: BTreeMap<String, Value> = map!("th".into() => th.clone().into());
+        if let Value::None = first_result(self.execute(sql, Some(vars.clone())).await?)?.first() {
+            // Either there's no such trip, which getting it reports, or it was finished already
+            let _ = self.get_trip(id).await?;
+            return Err(Error::Conflict(format!("Trip {} is already finished", id)));
+        }
+        // Lines can't be checked off once the trip is finished, so these are the ones it was finished with
+        let trip = self.get_trip(id).await?;
+        // A store deleted since the trip started can't be recorded against
+        let store = match trip.store.as_deref() {
+            Some(store) => self.get_store(store).await.ok().map(|store| store.id),
+            None => None,
+        };
+        let checked: Vec<&TripLine> = trip.lines.iter().filter(|line| line.checked).collect();
+        let data: Vec<RestockItem> = checked.iter()
+            .filter_map(|line| Some(RestockItem {
+                id: line.item.clone()?,
+                count: line.bought().filter(|bought| *bought > 0.0)?,
+                unit: Some(line.unit.clone()),
+                package: None,
+                best_by: None,
+                price: line.price,
+                unit_price: None,
+                store: store.clone(),
+            }))
+            .collect();
+        let extras = checked.iter()
+            .flat_map(|line| line.extras.iter())
+            .map(|extra| extra_thing(extra).map(Value::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = match data.is_empty() {
+            true => BatchResult::default(),
+            false => match self.restock_items(data, &default_location(), source).await {
+                Ok(result) => result,
+                Err(e) => {
+                    let _ = self.execute("UPDATE trips SET finished = NONE WHERE id = $th RETURN NONE", Some(vars)).await;
+                    return Err(e);
+                },
+            },
+        };
+
+        let mut query = Query::transaction();
+        let th = query.bind(th);
+        let extras = query.bind(Value::Array(Array::from(extras)));
+        query.push(format!("UPDATE trip_lines SET bought = quantity WHERE trip = {th} AND checked = true AND bought = NONE RETURN NONE"));
+        query.push(format!("DELETE shopping_extras WHERE id INSIDE {extras}"));
+        let (sql, vars) = query.commit();
+        all_results(self.execute(&sql, Some(vars)).await?)?;
+
+        Ok(FinishedTrip { trip: self.get_trip(id).await?, result })
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::Error;
+
+/// The unit items are kept in unless given another one.
+pub const DEFAULT_UNIT: &str = "count";
+
+/// Decimal places quantities are kept to, here and in every SurrealQL `math::fixed`.
+pub const PRECISION: u32 = 3;
+
+/// What a unit measures. Only units measuring the same thing convert into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dimension {
+    Count,
+    Mass,
+    Volume,
+}
+
+/// A unit of measure, with how many of its dimension's base unit (one, a gram, a millilitre) it is.
+#[derive(Debug, Clone, Serialize)]
+pub struct Unit {
+    pub name: &'static str,
+    pub dimension: Dimension,
+    pub factor: f64,
+}
+
+/// Every unit an item can be kept in, served as the conversion table at `GET /units`.
+pub const UNITS: [Unit; 12] = [
+    Unit { name: "count", dimension: Dimension::Count, factor: 1.0 },
+    Unit { name: "dozen", dimension: Dimension::Count, factor: 12.0 },
+    Unit { name: "g", dimension: Dimension::Mass, factor: 1.0 },
+    Unit { name: "kg", dimension: Dimension::Mass, factor: 1000.0 },
+    Unit { name: "oz", dimension: Dimension::Mass, factor: 28.349523125 },
+    Unit { name: "lb", dimension: Dimension::Mass, factor: 453.59237 },
+    Unit { name: "ml", dimension: Dimension::Volume, factor: 1.0 },
+    Unit { name: "l", dimension: Dimension::Volume, factor: 1000.0 },
+    Unit { name: "tsp", dimension: Dimension::Volume, factor: 4.92892159375 },
+    Unit { name: "tbsp", dimension: Dimension::Volume, factor: 14.78676478125 },
+    Unit { name: "fl oz", dimension: Dimension::Volume, factor: 29.5735295625 },
+    Unit { name: "cup", dimension: Dimension::Volume, factor: 236.5882365 },
+];
+
+pub fn default_unit() -> String {
+    DEFAULT_UNIT.to_owned()
+}
+
+/// Looks up a unit by name, ignoring case and surrounding space.
+pub fn unit(name: &str) -> Result<&'static Unit, Error> {
+    let name = name.trim();
+    UNITS.iter()
+        .find(|unit| unit.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::Validation(format!("'{}' is not a known unit", name)))
+}
+
+/// How many `to` make one `from`, failing if the two don't measure the same thing.
+pub fn factor(from: &str, to: &str) -> Result<f64, Error> {
+    let (from, to) = (unit(from)?, unit(to)?);
+    if from.dimension != to.dimension {
+        return Err(Error::Validation(format!("Can not convert {} to {}", from.name, to.name)));
+    }
+    Ok(from.factor / to.factor)
+}
+
+/// Converts `quantity` of `from` into `to`, see [`factor`].
+pub fn convert(quantity: f64, from: &str, to: &str) -> Result<f64, Error> {
+    Ok(round(quantity * factor(from, to)?))
+}
+
+/// Rounds a quantity to [`PRECISION`] decimal places, so repeated changes don't collect float error.
+pub fn round(quantity: f64) -> f64 {
+    let scale = 10f64.powi(PRECISION as i32);
+    (quantity * scale).round() / scale
+}
+
+/// Whether `quantity` can be stored as a stock, i.e. is a number and not below zero.
+pub fn valid_quantity(quantity: f64) -> bool {
+    quantity.is_finite() && quantity >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_within_a_dimension() {
+        assert_eq!(convert(1.5, "kg", "g").unwrap(), 1500.0);
+        assert_eq!(convert(250.0, "ml", "l").unwrap(), 0.25);
+        assert_eq!(convert(2.0, "dozen", "count").unwrap(), 24.0);
+        assert_eq!(convert(1.0, "lb", "g").unwrap(), 453.592);
+        assert_eq!(convert(3.0, "tsp", "tbsp").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn refuses_to_convert_across_dimensions() {
+        assert!(matches!(convert(500.0, "g", "count"), Err(Error::Validation(_))));
+        assert!(matches!(factor("l", "kg"), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn looks_units_up_loosely() {
+        assert_eq!(unit(" KG ").unwrap().name, "kg");
+        assert_eq!(unit("Fl Oz").unwrap().name, "fl oz");
+        assert!(unit("furlong").is_err());
+    }
+
+    #[test]
+    fn rounds_to_precision() {
+        assert_eq!(round(0.1 + 0.2), 0.3);
+        assert_eq!(round(1.23456), 1.235);
+        assert_eq!(round(-1.0004), -1.0);
+    }
+
+    #[test]
+    fn only_finite_non_negative_quantities_are_valid() {
+        assert!(valid_quantity(0.0));
+        assert!(valid_quantity(2.5));
+        assert!(!valid_quantity(-0.001));
+        assert!(!valid_quantity(f64::NAN));
+        assert!(!valid_quantity(f64::INFINITY));
+    }
+}
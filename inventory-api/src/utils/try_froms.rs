@@ -35,6 +35,16 @@ impl TryFrom<W<Value>> for i64 {
     }
 }
 
+impl TryFrom<W<Value>> for f64 {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<f64, Error> {
+        match val.0 {
+            Value::Number(obj) => Ok(obj.as_float()),
+            _ => Err(Error::XValueNotOfType("f64")),
+        }
+    }
+}
+
 impl TryFrom<W<Value>> for bool {
     type Error = Error;
     fn try_from(val: W<Value>) -> Result<bool, Error> {
@@ -67,3 +77,13 @@ impl TryFrom<W<Value>> for DateTime<Utc> {
         }
     }
 }
+
+impl TryFrom<W<Value>> for Option<String> {
+    type Error = Error;
+    fn try_from(val: W<Value>) -> Result<Self, Self::Error> {
+        match val.0 {
+            Value::None | Value::Null => Ok(None),
+            value => Ok(Some(W(value).try_into()?)),
+        }
+    }
+}
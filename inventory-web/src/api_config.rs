@@ -0,0 +1,106 @@
+use std::cell::RefCell;
+
+use gloo_storage::{LocalStorage, Storage};
+use reqwasm::http::Request;
+use serde::Deserialize;
+use yew::AttrValue;
+
+use crate::items_api::{self, ApiError};
+
+const STORAGE_KEY: &str = "api_base_url";
+/// Port inventory-api listens on unless configured otherwise.
+const DEFAULT_API_PORT: u16 = 26530;
+
+/// Where the current api address came from, shown on the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UrlSource {
+    Settings,
+    ConfigFile,
+    Origin
+} impl std::fmt::Display for UrlSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlSource::Settings => write!(f, "saved settings"),
+            UrlSource::ConfigFile => write!(f, "config.json"),
+            UrlSource::Origin => write!(f, "this page's address"),
+        }
+    }
+}
+
+/// The optional `config.json` served alongside the page.
+#[derive(Deserialize)]
+struct ServedConfig {
+    api_base_url: Option<String>
+}
+
+thread_local! {
+    static BASE_URL: RefCell<(String, UrlSource)> = RefCell::new((origin_url(), UrlSource::Origin));
+}
+
+/// The address every request in items_api is sent to.
+pub fn base_url() -> String {
+    BASE_URL.with(|url| url.borrow().0.clone())
+}
+
+pub fn base_url_source() -> UrlSource {
+    BASE_URL.with(|url| url.borrow().1)
+}
+
+fn set_base_url(url: String, source: UrlSource) {
+    BASE_URL.with(|current| *current.borrow_mut() = (normalize(&url), source));
+}
+
+/// Works out which api to talk to, in order of preference:
+/// 1. An address saved from the settings tab
+/// 2. `api_base_url` in the served `config.json`
+/// 3. The host this page was served from, on the default api port
+pub async fn discover() {
+    if let Ok(url) = LocalStorage::get::<String>(STORAGE_KEY) {
+        set_base_url(url, UrlSource::Settings);
+        return;
+    }
+    match fetch_config_file().await {
+        Some(url) => set_base_url(url, UrlSource::ConfigFile),
+        None => set_base_url(origin_url(), UrlSource::Origin),
+    }
+}
+
+/// Saves `url` so it is used on every future visit from this browser.
+pub fn save(url: &str) {
+    let url = normalize(url);
+    let _ = LocalStorage::set(STORAGE_KEY, url.clone());
+    set_base_url(url, UrlSource::Settings);
+}
+
+/// Forgets any saved address and falls back to discovery.
+pub async fn reset() {
+    LocalStorage::delete(STORAGE_KEY);
+    discover().await;
+}
+
+/// Checks that `url` points at a working api, returning how many items it holds.
+pub async fn test_connection(url: AttrValue) -> Result<usize, ApiError> {
+    let items = items_api::fetch_items_from(&normalize(&url)).await?;
+    Ok(items.len())
+}
+
+async fn fetch_config_file() -> Option<String> {
+    let response = Request::get("config.json").send().await.ok()?;
+    if !response.ok() {
+        return None;
+    }
+    // Dev servers answer unknown paths with index.html, which simply fails to parse here
+    let config: ServedConfig = response.json().await.ok()?;
+    config.api_base_url.filter(|url| !url.trim().is_empty())
+}
+
+fn origin_url() -> String {
+    let location = web_sys::window().expect("no window found").location();
+    let protocol = location.protocol().unwrap_or("http:".into());
+    let hostname = location.hostname().unwrap_or("127.0.0.1".into());
+    format!("{protocol}//{hostname}:{DEFAULT_API_PORT}")
+}
+
+fn normalize(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_owned()
+}
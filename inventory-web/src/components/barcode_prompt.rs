@@ -0,0 +1,69 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{components::item_search::ItemSearch, error_message, models::{Item, DEFAULT_UNIT}, InvCont};
+
+#[derive(Properties, PartialEq)]
+pub struct BarcodePromptProps {
+    /// The barcode no item has yet
+    pub code: AttrValue,
+    /// Gets the item the barcode now belongs to
+    pub on_resolved: Callback<Item>,
+    pub on_dismiss: Callback<()>
+}
+
+/// Asks what to do with a scanned barcode that no item has: link it to an existing item, or create one for it.
+#[function_component]
+pub fn BarcodePrompt(props: &BarcodePromptProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_node = use_node_ref();
+    let category_node = use_node_ref();
+
+    let on_link = {
+        let inv_cont = inv_cont.clone();
+        let code = props.code.clone();
+        let on_resolved = props.on_resolved.clone();
+        Callback::from(move |item_id: AttrValue| {
+            inv_cont.add_barcode(item_id.to_string(), code.to_string(), on_resolved.clone());
+        })
+    };
+    let on_create = {
+        let inv_cont = inv_cont.clone();
+        let code = props.code.clone();
+        let on_resolved = props.on_resolved.clone();
+        let (name_node, category_node) = (name_node.clone(), category_node.clone());
+        Callback::from(move |_: MouseEvent| {
+            let name = name_node.cast::<HtmlInputElement>().unwrap().value().trim().to_owned();
+            let category = category_node.cast::<HtmlInputElement>().unwrap().value().trim().to_owned();
+            if name.is_empty() || category.is_empty() {
+                inv_cont.message.dispatch(error_message("A name and a category are required for a new item".into()));
+                return;
+            }
+            if inv_cont.state.inventory.name_to_id.contains_key(&AttrValue::from(name.clone())) {
+                inv_cont.message.dispatch(error_message("An item with that name already exists, link the barcode to it instead".into()));
+                return;
+            }
+            inv_cont.new_item_with_barcode(name, category, DEFAULT_UNIT.to_owned(), code.to_string(), on_resolved.clone());
+        })
+    };
+    let on_dismiss = {
+        let on_dismiss = props.on_dismiss.clone();
+        Callback::from(move |_: MouseEvent| on_dismiss.emit(()))
+    };
+
+    html!(<div class="barcode-prompt">
+        <p>{format!("No item has barcode {} yet.", props.code)}</p>
+        <label>{"Link it to:"}</label>
+        <ItemSearch selection_callback={on_link} />
+        <label>{"Or create:"}</label>
+        <div class="barcode-new-item">
+            <input type="text" placeholder="Name" ref={name_node} />
+            <input type="text" placeholder="Category" list="barcode-categories" ref={category_node} />
+            <datalist id="barcode-categories">
+                {for inv_cont.state.categories.iter().map(|category| html!(<option value={category.name.clone()} />))}
+            </datalist>
+            <button onclick={on_create}>{"Create"}</button>
+        </div>
+        <button onclick={on_dismiss}>{"Skip"}</button>
+    </div>)
+}
@@ -0,0 +1,93 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{error_message, models::{Budget, BudgetInput}, InvCont};
+
+/// Reads a monthly amount, which can't be negative.
+fn amount_value(node: &NodeRef) -> Option<f64> {
+    node.cast::<HtmlInputElement>()?.value().parse::<f64>().ok().filter(|amount| *amount >= 0.0)
+}
+
+#[derive(Properties, PartialEq)]
+struct BudgetRowProps {
+    budget: Budget
+}
+
+#[function_component]
+fn BudgetRow(props: &BudgetRowProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let amount_ref = use_node_ref();
+    let budget = &props.budget;
+
+    let save = {
+        let inv_cont = inv_cont.clone();
+        let amount_ref = amount_ref.clone();
+        let category = budget.category.clone();
+        Callback::from(move |_| match amount_value(&amount_ref) {
+            Some(amount) => inv_cont.set_budget(BudgetInput { category: category.clone(), amount }),
+            None => inv_cont.message.dispatch(error_message("A budget must be a number of zero or more".into())),
+        })
+    };
+    let delete = {
+        let inv_cont = inv_cont.clone();
+        let id = budget.id.clone();
+        Callback::from(move |_| inv_cont.delete_budget(id.clone()))
+    };
+
+    html!(<tr key={budget.id.clone()}>
+        <td>{budget.category_name.clone().unwrap_or_else(|| "Overall".into())}</td>
+        <td><input type="number" min="0" step="0.01" value={budget.amount.to_string()} ref={amount_ref}/></td>
+        <td><button onclick={save}>{"Save"}</button></td>
+        <td><button onclick={delete}>{"Remove"}</button></td>
+    </tr>)
+}
+
+/// Set how much can be spent a month, overall and on each category. Restocks with a price are
+/// counted against them, and the Reports tab shows what's left.
+#[function_component]
+pub fn BudgetManager() -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let category_ref = use_node_ref();
+    let amount_ref = use_node_ref();
+
+    let add = {
+        let inv_cont = inv_cont.clone();
+        let (category_ref, amount_ref) = (category_ref.clone(), amount_ref.clone());
+        Callback::from(move |_| {
+            let Some(amount) = amount_value(&amount_ref) else {
+                inv_cont.message.dispatch(error_message("A budget must be a number of zero or more".into()));
+                return;
+            };
+            let category = category_ref.cast::<HtmlSelectElement>().map(|node| node.value()).filter(|category| !category.is_empty());
+            inv_cont.set_budget(BudgetInput { category, amount });
+            if let Some(input) = amount_ref.cast::<HtmlInputElement>() {
+                input.set_value("");
+            }
+        })
+    };
+
+    let budgets = &inv_cont.state.budgets;
+    let has_overall = budgets.iter().any(|budget| budget.category.is_none());
+    let unbudgeted = inv_cont.state.categories.iter()
+        .filter(|category| !budgets.iter().any(|budget| budget.category.as_deref() == Some(category.id.as_str())));
+
+    html!(<div id="budget-manager">
+        <h3>{"Monthly budgets"}</h3>
+        <table>
+            <tr><th>{"Category"}</th><th>{"Budget"}</th></tr>
+            {for budgets.iter().map(|budget| html!(<BudgetRow key={budget.id.clone()} budget={budget.clone()} />))}
+            <tr>
+                <td>
+                    <select ref={category_ref}>
+                        if !has_overall {
+                            <option value="">{"Overall"}</option>
+                        }
+                        {for unbudgeted.map(|category| html!(<option value={category.id.clone()}>{category.name.clone()}</option>))}
+                    </select>
+                </td>
+                <td><input type="number" min="0" step="0.01" placeholder="A month" ref={amount_ref}/></td>
+                <td><button onclick={add}>{"Add"}</button></td>
+            </tr>
+        </table>
+    </div>)
+}
@@ -0,0 +1,158 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{error_message, models::{Category, CategoryInput}, InvCont};
+
+/// Reads a text input, with a blank input as `None`.
+fn input_value(node: &NodeRef) -> Option<String> {
+    let value = node.cast::<HtmlInputElement>()?.value();
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn select_value(node: &NodeRef) -> Option<String> {
+    let value = node.cast::<HtmlSelectElement>()?.value();
+    (!value.is_empty()).then_some(value)
+}
+
+#[derive(Properties, PartialEq)]
+struct CategoryRowProps {
+    category: Category
+}
+
+#[function_component]
+fn CategoryRow(props: &CategoryRowProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_ref = use_node_ref();
+    let colour_ref = use_node_ref();
+    let icon_ref = use_node_ref();
+    let parent_ref = use_node_ref();
+    let merge_ref = use_node_ref();
+
+    let category = &props.category;
+    let categories = &inv_cont.state.categories;
+    let index = categories.iter().position(|c| c.id == category.id).unwrap_or_default();
+    let others: Vec<&Category> = categories.iter().filter(|c| c.id != category.id).collect();
+
+    let move_by = |offset: isize| {
+        let inv_cont = inv_cont.clone();
+        let ids: Vec<String> = inv_cont.state.categories.iter().map(|c| c.id.clone()).collect();
+        Callback::from(move |_| {
+            let target = index as isize + offset;
+            if target < 0 || target as usize >= ids.len() {
+                return;
+            }
+            let mut ids = ids.clone();
+            ids.swap(index, target as usize);
+            inv_cont.reorder_categories(ids);
+        })
+    };
+    let save = {
+        let inv_cont = inv_cont.clone();
+        let (name_ref, colour_ref, icon_ref, parent_ref) = (name_ref.clone(), colour_ref.clone(), icon_ref.clone(), parent_ref.clone());
+        let id = category.id.clone();
+        Callback::from(move |_| {
+            let Some(name) = input_value(&name_ref) else {
+                inv_cont.message.dispatch(error_message("A category name is required".into()));
+                return;
+            };
+            inv_cont.update_category(id.clone(), CategoryInput {
+                name,
+                colour: input_value(&colour_ref),
+                icon: input_value(&icon_ref),
+                parent: select_value(&parent_ref)
+            });
+        })
+    };
+    let merge = {
+        let inv_cont = inv_cont.clone();
+        let merge_ref = merge_ref.clone();
+        let id = category.id.clone();
+        Callback::from(move |_| {
+            if let Some(into) = select_value(&merge_ref) {
+                inv_cont.merge_category(id.clone(), into);
+            }
+        })
+    };
+    let delete = {
+        let inv_cont = inv_cont.clone();
+        let id = category.id.clone();
+        Callback::from(move |_| inv_cont.delete_category(id.clone()))
+    };
+
+    html!(<tr key={category.id.clone()}>
+        <td>
+            <button disabled={index == 0} onclick={move_by(-1)}>{"▲"}</button>
+            <button disabled={index + 1 >= categories.len()} onclick={move_by(1)}>{"▼"}</button>
+        </td>
+        <td><input type="text" value={category.name.clone()} ref={name_ref}/></td>
+        <td><input type="text" size="7" placeholder="#rrggbb" value={category.colour.clone().unwrap_or_default()} ref={colour_ref}/></td>
+        <td><input type="text" size="3" value={category.icon.clone().unwrap_or_default()} ref={icon_ref}/></td>
+        <td><select ref={parent_ref}>
+            <option value="" selected={category.parent.is_none()}>{"No parent"}</option>
+            {for others.iter().map(|c| html!(<option value={c.id.clone()} selected={category.parent.as_ref() == Some(&c.id)}>{c.name.clone()}</option>))}
+        </select></td>
+        <td><button onclick={save}>{"Save"}</button></td>
+        <td>
+            <select ref={merge_ref}>
+                <option value="">{"Merge into..."}</option>
+                {for others.iter().map(|c| html!(<option value={c.id.clone()}>{c.name.clone()}</option>))}
+            </select>
+            <button onclick={merge}>{"Merge"}</button>
+        </td>
+        <td><button onclick={delete}>{"Delete"}</button></td>
+    </tr>)
+}
+
+/// Create, rename, recolour, nest, merge, reorder and delete categories. The order set here is the
+/// order categories are shown in on every tab.
+#[function_component]
+pub fn CategoryManager() -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_ref = use_node_ref();
+    let colour_ref = use_node_ref();
+    let icon_ref = use_node_ref();
+    let parent_ref = use_node_ref();
+
+    let add = {
+        let inv_cont = inv_cont.clone();
+        let (name_ref, colour_ref, icon_ref, parent_ref) = (name_ref.clone(), colour_ref.clone(), icon_ref.clone(), parent_ref.clone());
+        Callback::from(move |_| {
+            let Some(name) = input_value(&name_ref) else {
+                inv_cont.message.dispatch(error_message("A category name is required".into()));
+                return;
+            };
+            inv_cont.create_category(CategoryInput {
+                name,
+                colour: input_value(&colour_ref),
+                icon: input_value(&icon_ref),
+                parent: select_value(&parent_ref)
+            });
+            for node in [&name_ref, &colour_ref, &icon_ref] {
+                if let Some(input) = node.cast::<HtmlInputElement>() {
+                    input.set_value("");
+                }
+            }
+        })
+    };
+
+    let categories = &inv_cont.state.categories;
+    html!(<div id="category-manager">
+        <h3>{"Categories"}</h3>
+        <table>
+            <tr><th></th><th>{"Name"}</th><th>{"Colour"}</th><th>{"Icon"}</th><th>{"Inside"}</th></tr>
+            {for categories.iter().map(|category| html!(<CategoryRow key={category.id.clone()} category={category.clone()} />))}
+            <tr>
+                <td></td>
+                <td><input type="text" placeholder="New category" ref={name_ref}/></td>
+                <td><input type="text" size="7" placeholder="#rrggbb" ref={colour_ref}/></td>
+                <td><input type="text" size="3" ref={icon_ref}/></td>
+                <td><select ref={parent_ref}>
+                    <option value="">{"No parent"}</option>
+                    {for categories.iter().map(|c| html!(<option value={c.id.clone()}>{c.name.clone()}</option>))}
+                </select></td>
+                <td><button onclick={add}>{"Add"}</button></td>
+            </tr>
+        </table>
+    </div>)
+}
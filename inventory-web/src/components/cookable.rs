@@ -0,0 +1,108 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{items_api, models::{format_quantity, Cookable}, InvCont};
+
+/// How many missing ingredients are shown until told otherwise.
+const DEFAULT_MISSING: u32 = 2;
+
+pub enum CookableRecipesMsg {
+    Load,
+    Loaded(Cookable)
+}
+
+/// What can be cooked without shopping: recipes the stock covers, then those short of only a few ingredients.
+pub struct CookableRecipes {
+    cookable: Option<Cookable>,
+    missing: u32,
+    missing_node: NodeRef
+}
+
+impl Component for CookableRecipes {
+    type Message = CookableRecipesMsg;
+
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(CookableRecipesMsg::Load);
+        Self {
+            cookable: None,
+            missing: DEFAULT_MISSING,
+            missing_node: NodeRef::default()
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+
+        match msg {
+            CookableRecipesMsg::Load => {
+                if let Some(missing) = self.missing_node.cast::<HtmlInputElement>().and_then(|node| node.value().parse().ok()) {
+                    self.missing = missing;
+                }
+                let missing = self.missing;
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    match items_api::fetch_cookable(missing).await {
+                        Ok(cookable) => vec![CookableRecipesMsg::Loaded(cookable)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(CookableRecipesMsg::Load));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            CookableRecipesMsg::Loaded(cookable) => self.cookable = Some(cookable),
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let ready: Vec<Html> = self.cookable.iter().flat_map(|cookable| cookable.ready.iter()).map(|recipe| html!(
+            <tr key={recipe.id.clone()}>
+                <td class="name">{recipe.name.clone()}</td>
+                <td>{format!("serves {}", recipe.servings)}</td>
+            </tr>
+        )).collect();
+        let almost: Vec<Html> = self.cookable.iter().flat_map(|cookable| cookable.almost.iter()).map(|almost| {
+            let missing = almost.missing.iter()
+                .map(|missing| match &missing.reason {
+                    Some(reason) => format!("{} ({})", missing.name, reason),
+                    None => format!("{} (need {}, have {})", missing.name, format_quantity(missing.needed, &missing.unit), format_quantity(missing.stock, &missing.unit)),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            html!(<tr key={almost.recipe.id.clone()}>
+                <td class="name">{almost.recipe.name.clone()}</td>
+                <td class="missing">{missing}</td>
+            </tr>)
+        }).collect();
+
+        html!(<div class="cookable">
+            <h3>{"What can I cook?"}</h3>
+            <div class="cookable-filters">
+                <label>{"Show recipes missing up to"}</label>
+                <input type="number" size="3" min="0" value={self.missing.to_string()} ref={&self.missing_node}/>
+                <label>{"ingredients"}</label>
+                <button onclick={ctx.link().callback(|_| CookableRecipesMsg::Load)}>{"Check"}</button>
+            </div>
+            if self.cookable.is_some() {
+                if ready.is_empty() {
+                    <p>{"Nothing can be cooked from what's in stock."}</p>
+                } else {
+                    <table>
+                        <tr><th colspan="2">{"Ready to cook"}</th></tr>
+                        {for ready}
+                    </table>
+                }
+                if !almost.is_empty() {
+                    <table>
+                        <tr><th>{"Almost"}</th><th>{"Missing"}</th></tr>
+                        {for almost}
+                    </table>
+                }
+            }
+        </div>)
+    }
+}
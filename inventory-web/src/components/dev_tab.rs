@@ -3,12 +3,16 @@ use std::collections::BTreeMap;
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
-use crate::{error_message, models::Item, InvCont, ItemSearch};
+use crate::{error_message, models::{Item, ItemStoreInput, Package, DEFAULT_UNIT}, InvCont, ItemSearch};
 
 pub enum DevTabMsg {
     AddItem,
     AddFullItem,
     ChangeItem,
+    SetPackages,
+    AddBarcode,
+    RemoveBarcode,
+    SetStore,
     DeleteItem,
     SearchedItem(AttrValue)
 }
@@ -24,7 +28,7 @@ impl Component for DevTab {
 
     fn create(_ctx: &Context<Self>) -> Self {
         let mut input_nodes = BTreeMap::new();
-        let attrs = vec!["name", "category", "stock", "desired stock", "track generally", "ID"];
+        let attrs = vec!["name", "category", "stock", "desired stock", "unit", "packages", "barcode", "store", "aisle", "track generally", "ID"];
         for attr in attrs {
             input_nodes.insert(attr.into(), NodeRef::default());
         }
@@ -41,6 +45,7 @@ impl Component for DevTab {
             DevTabMsg::AddItem => {
                 let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
                 let category = self.input_nodes["category"].cast::<HtmlInputElement>().unwrap().value();
+                let unit = self.unit();
                 if name.is_empty() {
                     return false;
                 }
@@ -52,14 +57,15 @@ impl Component for DevTab {
                     message.dispatch(error_message("An item with that name already exists".into()));
                     return false;
                 }
-                controller.new_item(name, category);
+                controller.new_item(name, category, unit);
                 clear_inputs = true;
             },
             DevTabMsg::AddFullItem => {
                 let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
                 let category = self.input_nodes["category"].cast::<HtmlInputElement>().unwrap().value();
-                let stock = self.input_nodes["stock"].cast::<HtmlInputElement>().unwrap().value().parse().unwrap_or(0);
-                let desired_stock = self.input_nodes["desired stock"].cast::<HtmlInputElement>().unwrap().value().parse().unwrap_or(0);
+                let stock = self.input_nodes["stock"].cast::<HtmlInputElement>().unwrap().value().parse().unwrap_or(0.0);
+                let desired_stock = self.input_nodes["desired stock"].cast::<HtmlInputElement>().unwrap().value().parse().unwrap_or(0.0);
+                let unit = self.unit();
                 let track_generally = self.input_nodes["track generally"].cast::<HtmlInputElement>().unwrap().checked();
                 if name.is_empty() {
                     return false;
@@ -72,7 +78,7 @@ impl Component for DevTab {
                     message.dispatch(error_message("An item with that name already exists".into()));
                     return false;
                 }
-                controller.add_full_item(name, category, stock, desired_stock, track_generally);
+                controller.add_full_item(name, category, stock, desired_stock, unit, track_generally);
                 clear_inputs = true;
             },
             DevTabMsg::ChangeItem => {
@@ -81,6 +87,7 @@ impl Component for DevTab {
                 let stock_input = self.input_nodes["stock"].cast::<HtmlInputElement>().unwrap().value();
                 let desired_stock_input = self.input_nodes["desired stock"].cast::<HtmlInputElement>().unwrap().value();
                 let track_generally = self.input_nodes["track generally"].cast::<HtmlInputElement>().unwrap().checked();
+                let unit = self.unit();
                 let id = self.input_nodes["ID"].cast::<HtmlInputElement>().unwrap().value();
 
                 if name.is_empty() && id.is_empty() {
@@ -131,6 +138,11 @@ impl Component for DevTab {
                     category,
                     stock,
                     desired_stock,
+                    unit,
+                    packages: original.packages.clone(),
+                    barcodes: original.barcodes.clone(),
+                    store: original.store.clone(),
+                    aisle: original.aisle.clone(),
                     last_updated: original.last_updated.clone(),
                     track_general: track_generally
                 };
@@ -138,6 +150,65 @@ impl Component for DevTab {
                 controller.change_item(item_id.to_string(), item);
                 clear_inputs = true;
             },
+            DevTabMsg::SetPackages => {
+                let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
+                let input = self.input_nodes["packages"].cast::<HtmlInputElement>().unwrap().value();
+                let Some(item_id) = inventory.name_to_id.get(&AttrValue::from(name.clone())) else {
+                    message.dispatch(error_message(format!("Could not find an item with name {}", name)));
+                    return false;
+                };
+                let mut packages = vec![];
+                for package in input.split(',').map(str::trim).filter(|package| !package.is_empty()) {
+                    match package.split_once('=').map(|(name, size)| (name.trim(), size.trim().parse())) {
+                        Some((name, Ok(size))) => packages.push(Package { name: name.to_owned(), size }),
+                        _ => {
+                            message.dispatch(error_message(format!("Could not read package '{}', expected name=size", package)));
+                            return false;
+                        }
+                    }
+                }
+                controller.set_packages(item_id.to_string(), packages);
+                clear_inputs = false;
+            },
+            DevTabMsg::AddBarcode | DevTabMsg::RemoveBarcode => {
+                let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
+                let code = self.input_nodes["barcode"].cast::<HtmlInputElement>().unwrap().value();
+                let Some(item_id) = inventory.name_to_id.get(&AttrValue::from(name.clone())) else {
+                    message.dispatch(error_message(format!("Could not find an item with name {}", name)));
+                    return false;
+                };
+                if code.trim().is_empty() {
+                    return false;
+                }
+                match msg {
+                    DevTabMsg::AddBarcode => controller.add_barcode(item_id.to_string(), code.trim().to_owned(), Callback::noop()),
+                    _ => controller.remove_barcode(item_id.to_string(), code.trim().to_owned()),
+                }
+                clear_inputs = false;
+            },
+            DevTabMsg::SetStore => {
+                let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
+                let store_name = self.input_nodes["store"].cast::<HtmlInputElement>().unwrap().value();
+                let aisle = self.input_nodes["aisle"].cast::<HtmlInputElement>().unwrap().value();
+                let Some(item_id) = inventory.name_to_id.get(&AttrValue::from(name.clone())) else {
+                    message.dispatch(error_message(format!("Could not find an item with name {}", name)));
+                    return false;
+                };
+                // A blank store takes the item off its store
+                let store = match store_name.trim() {
+                    "" => None,
+                    store_name => match controller.state.stores.iter().find(|store| store.name == store_name) {
+                        Some(store) => Some(store.id.clone()),
+                        None => {
+                            message.dispatch(error_message(format!("Could not find a store with name {}", store_name)));
+                            return false;
+                        }
+                    }
+                };
+                let aisle = Some(aisle.trim().to_owned()).filter(|aisle| !aisle.is_empty());
+                controller.set_item_store(item_id.to_string(), ItemStoreInput { store, aisle });
+                clear_inputs = false;
+            },
             DevTabMsg::DeleteItem => {
                 let name = self.input_nodes["name"].cast::<HtmlInputElement>().unwrap().value();
                 let id = self.input_nodes["ID"].cast::<HtmlInputElement>().unwrap().value();
@@ -165,6 +236,13 @@ impl Component for DevTab {
                 self.input_nodes["category"].cast::<HtmlInputElement>().unwrap().set_value(&item.category);
                 self.input_nodes["stock"].cast::<HtmlInputElement>().unwrap().set_value(&item.stock.to_string());
                 self.input_nodes["desired stock"].cast::<HtmlInputElement>().unwrap().set_value(&item.desired_stock.to_string());
+                self.input_nodes["unit"].cast::<HtmlInputElement>().unwrap().set_value(&item.unit);
+                let packages: Vec<String> = item.packages.iter().map(|package| format!("{}={}", package.name, package.size)).collect();
+                self.input_nodes["packages"].cast::<HtmlInputElement>().unwrap().set_value(&packages.join(", "));
+                self.input_nodes["barcode"].cast::<HtmlInputElement>().unwrap().set_placeholder(&item.barcodes.join(", "));
+                let store = item.store.as_deref().and_then(|id| controller.state.store(id)).map(|store| store.name.as_str());
+                self.input_nodes["store"].cast::<HtmlInputElement>().unwrap().set_value(store.unwrap_or_default());
+                self.input_nodes["aisle"].cast::<HtmlInputElement>().unwrap().set_value(item.aisle.as_deref().unwrap_or_default());
                 self.input_nodes["ID"].cast::<HtmlInputElement>().unwrap().set_value(&item.id);
                 self.input_nodes["track generally"].cast::<HtmlInputElement>().unwrap().set_checked(item.track_general);
                 clear_inputs = false;
@@ -184,11 +262,16 @@ impl Component for DevTab {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let stores = &controller.state.stores;
+        let mut aisles: Vec<&String> = stores.iter().flat_map(|store| store.aisles.iter()).collect();
+        aisles.sort();
+        aisles.dedup();
+
         html!(<div id="dev-tab"><div class="container">
         <table>
             <tr>
-                <th rowspan="4"><div style="display: grid;">
+                <th rowspan="8"><div style="display: grid;">
                     <label>{"Name:"}</label>
                     <input type="text" ref={&self.input_nodes["name"]}/>
                     <label>{"Category:"}</label>
@@ -197,6 +280,22 @@ impl Component for DevTab {
                     <input type="text" ref={&self.input_nodes["stock"]}/>
                     <label>{"Desired stock:"}</label>
                     <input type="text" ref={&self.input_nodes["desired stock"]}/>
+                    <label>{"Unit:"}</label>
+                    <input type="text" placeholder={DEFAULT_UNIT} ref={&self.input_nodes["unit"]}/>
+                    <label>{"Packages:"}</label>
+                    <input type="text" placeholder="case=12, box=4" ref={&self.input_nodes["packages"]}/>
+                    <label>{"Barcode:"}</label>
+                    <input type="text" inputmode="numeric" ref={&self.input_nodes["barcode"]}/>
+                    <label>{"Store:"}</label>
+                    <input type="text" list="dev-stores" ref={&self.input_nodes["store"]}/>
+                    <datalist id="dev-stores">
+                        {for stores.iter().map(|store| html!(<option value={store.name.clone()} />))}
+                    </datalist>
+                    <label>{"Aisle:"}</label>
+                    <input type="text" list="dev-aisles" ref={&self.input_nodes["aisle"]}/>
+                    <datalist id="dev-aisles">
+                        {for aisles.into_iter().map(|aisle| html!(<option value={aisle.clone()} />))}
+                    </datalist>
                     <label>{"Track generally:"}</label>
                     <input type="checkbox" ref={&self.input_nodes["track generally"]}/>
                     <label>{"ID:"}</label>
@@ -212,6 +311,18 @@ impl Component for DevTab {
             <tr><td>
                 <button onclick={ctx.link().callback(|_| DevTabMsg::ChangeItem)}>{"Change Item"}</button>
             </td></tr>
+            <tr><td>
+                <button onclick={ctx.link().callback(|_| DevTabMsg::SetPackages)}>{"Set Packages"}</button>
+            </td></tr>
+            <tr><td>
+                <button onclick={ctx.link().callback(|_| DevTabMsg::AddBarcode)}>{"Add Barcode"}</button>
+            </td></tr>
+            <tr><td>
+                <button onclick={ctx.link().callback(|_| DevTabMsg::RemoveBarcode)}>{"Remove Barcode"}</button>
+            </td></tr>
+            <tr><td>
+                <button onclick={ctx.link().callback(|_| DevTabMsg::SetStore)}>{"Set Store"}</button>
+            </td></tr>
             <tr><td>
                 <button onclick={ctx.link().callback(|_| DevTabMsg::DeleteItem)}>{"Delete Item"}</button>
             </td></tr>
@@ -219,4 +330,15 @@ impl Component for DevTab {
         <ItemSearch selection_callback={ctx.link().callback(DevTabMsg::SearchedItem)}/>
         </div></div>)
     }
-}
\ No newline at end of file
+}
+
+impl DevTab {
+    /// The unit input, which is blank for items counted one by one.
+    fn unit(&self) -> String {
+        let unit = self.input_nodes["unit"].cast::<HtmlInputElement>().unwrap().value();
+        match unit.trim() {
+            "" => DEFAULT_UNIT.to_owned(),
+            unit => unit.to_owned(),
+        }
+    }
+}
@@ -5,32 +5,55 @@ use serde::{Deserialize, Serialize};
 use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
-use crate::{components::item_search::ItemSearch, models::RestockItem, InvCont};
+use crate::{components::{BarcodePrompt, item_search::ItemSearch, selected_unit, UnitSelect}, models::{Item, RestockItem}, InvCont};
 
 #[derive(Serialize,Deserialize,Clone)]
 pub struct DinnerlistStorage {
     items: BTreeMap<String, String>
 }
 
+/// Items to put on the list from outside it, e.g. a recipe's ingredients. A new `serial` marks a new load.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DinnerListLoad {
+    pub serial: u32,
+    pub items: Vec<(AttrValue, f64)>
+}
+
+#[derive(Properties, PartialEq)]
+pub struct DinnerListProps {
+    #[prop_or_default]
+    pub load: DinnerListLoad
+}
+
 pub enum DinnerListMsg {
     RetrieveStorage,
     UpdateStorage(AttrValue),
     AddItem(AttrValue),
+    ToggleScan,
+    Scanned(AttrValue),
+    Resolved(Item),
+    /// The api doesn't know a scanned barcode either
+    Unknown(AttrValue),
+    DismissCode,
     Submit
 }
 
 pub struct Dinnerlist {
     list_items: Vec<AttrValue>,
     item_nodes: BTreeMap<AttrValue, NodeRef>,
+    unit_nodes: BTreeMap<AttrValue, NodeRef>,
     general_nodes: BTreeMap<AttrValue, NodeRef>,
     storage: BTreeMap<String, String>,
-    init_callback: Vec<ContextHandle<InvCont>>
+    init_callback: Vec<ContextHandle<InvCont>>,
+    scan_mode: bool,
+    /// A scanned barcode no item has, waiting to be linked to one
+    unknown_code: Option<AttrValue>
 }
 
 impl Component for Dinnerlist {
     type Message = DinnerListMsg;
 
-    type Properties = ();
+    type Properties = DinnerListProps;
 
     fn create(ctx: &Context<Self>) -> Self {
         let mut storage = BTreeMap::new();
@@ -48,9 +71,12 @@ impl Component for Dinnerlist {
         Self { 
             list_items: vec![], 
             item_nodes: BTreeMap::new(), 
+            unit_nodes: BTreeMap::new(),
             general_nodes: BTreeMap::new(), 
             storage, 
-            init_callback 
+            init_callback,
+            scan_mode: false,
+            unknown_code: None
         }
     }
 
@@ -62,6 +88,7 @@ impl Component for Dinnerlist {
             DinnerListMsg::RetrieveStorage => {
                 self.list_items = vec![];
                 self.item_nodes = BTreeMap::new();
+                self.unit_nodes = BTreeMap::new();
                 self.general_nodes = BTreeMap::new();
                 for (item_id, _value) in self.storage.iter() {
                     let item_id = AttrValue::from(item_id.clone());
@@ -78,7 +105,8 @@ impl Component for Dinnerlist {
                     } else {
                         if !self.list_items.contains(&item_id) {
                             self.list_items.push(item_id.clone());
-                            self.item_nodes.insert(item_id, NodeRef::default());
+                            self.item_nodes.insert(item_id.clone(), NodeRef::default());
+                            self.unit_nodes.insert(item_id, NodeRef::default());
                         }
                     }
                 }
@@ -99,21 +127,26 @@ impl Component for Dinnerlist {
                 return false;
             },
             DinnerListMsg::AddItem(item_id) => {
-                let item = &inventory.item_id_map[&item_id];
-                if item.track_general {
-                    if !self.list_items.contains(&item_id) {
-                        self.list_items.push(item_id.clone());
-                        self.general_nodes.insert(item_id, NodeRef::default());
-                    }
-                } else {
-                    if !self.list_items.contains(&item_id) {
-                        self.list_items.push(item_id.clone());
-                        self.item_nodes.insert(item_id, NodeRef::default());
-                    }
-                }
-                self.storage.insert(item.id.clone(), "".to_string());
-                let _ = LocalStorage::set("dinner_list", self.storage.clone());
+                let item = inventory.item_id_map[&item_id].clone();
+                self.add(&item, "");
+            },
+            DinnerListMsg::ToggleScan => {
+                self.scan_mode = !self.scan_mode;
+                self.unknown_code = None;
             },
+            DinnerListMsg::Scanned(code) => match controller.state.item_by_barcode(&code).cloned() {
+                Some(item) => self.scan(&item),
+                None => {
+                    controller.find_barcode(code.to_string(), ctx.link().callback(DinnerListMsg::Resolved), ctx.link().callback(|code: String| DinnerListMsg::Unknown(code.into())));
+                    return false;
+                },
+            },
+            DinnerListMsg::Resolved(item) => {
+                self.unknown_code = None;
+                self.scan(&item);
+            },
+            DinnerListMsg::Unknown(code) => self.unknown_code = Some(code),
+            DinnerListMsg::DismissCode => self.unknown_code = None,
             DinnerListMsg::Submit => {
                 if self.item_nodes.len() == 0 && self.general_nodes.len() == 0 {
                     return false;
@@ -122,17 +155,23 @@ impl Component for Dinnerlist {
                 for (id, node) in self.item_nodes.iter() {
                     let value = node.cast::<HtmlInputElement>().unwrap().value();
                     let mut count = match value.is_empty() {
-                        false => value.parse().unwrap_or(1),
-                        true => 1
+                        false => value.parse().unwrap_or(1.0),
+                        true => 1.0
                     };
-                    if count == 0 {
+                    if count <= 0.0 {
                         continue;
                     }
                     let item = &inventory.item_id_map[id];
-                    if item.stock < count {
+                    let mut unit = selected_unit(&self.unit_nodes[id], &item.unit);
+                    let in_item_unit = match &unit {
+                        Some(unit) => controller.state.convert(count, unit, &item.unit).unwrap_or(count),
+                        None => count
+                    };
+                    if item.stock < in_item_unit {
                         count = item.stock.clone();
+                        unit = None;
                     }
-                    items.push(RestockItem { id: id.to_string(), count: count.try_into().unwrap() });
+                    items.push(RestockItem { id: id.to_string(), count, unit, package: None, best_by: None, price: None, unit_price: None, store: None });
                 }
                 for (id, node) in self.general_nodes.iter() {
                     let selected_general_count = node.cast::<HtmlSelectElement>().unwrap().selected_options().item(0).unwrap().id();
@@ -140,18 +179,19 @@ impl Component for Dinnerlist {
                     if selected_general_count.eq("none") {
                         continue;
                     } else if selected_general_count.eq("low") {
-                        count = 1;
+                        count = 1.0;
                     } else { // "out"
-                        if inventory.item_id_map[id].stock == 2 {
-                            count = 2;
+                        if inventory.item_id_map[id].stock == 2.0 {
+                            count = 2.0;
                         } else {
-                            count = 1;
+                            count = 1.0;
                         }
                     }
-                    items.push(RestockItem { id: id.to_string(), count });
+                    items.push(RestockItem { id: id.to_string(), count, unit: None, package: None, best_by: None, price: None, unit_price: None, store: None });
                 }
                 self.list_items = vec![];
                 self.item_nodes = BTreeMap::new();
+                self.unit_nodes = BTreeMap::new();
                 self.general_nodes = BTreeMap::new();
                 self.storage = BTreeMap::new();
                 LocalStorage::delete("dinner_list");
@@ -164,6 +204,20 @@ impl Component for Dinnerlist {
         true
     }
 
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        let load = &ctx.props().load;
+        if load.serial == old_props.load.serial {
+            return false;
+        }
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        for (item_id, count) in load.items.iter() {
+            if let Some(item) = controller.state.inventory.item_id_map.get(item_id) {
+                self.add_count(item, *count);
+            }
+        }
+        true
+    }
+
     fn view(&self, ctx: &Context<Self>) -> Html {
         let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
         let inventory = &controller.state.inventory;
@@ -171,7 +225,8 @@ impl Component for Dinnerlist {
 
         let mut item_list: Vec<Html> = vec![];
         for item_id in self.list_items.iter() {
-            let item = &id_map[item_id];
+            // An item created for a scanned barcode can get here before the items are reloaded
+            let Some(item) = id_map.get(item_id) else { continue };
             let item_name = &item.name;
             if item.track_general {
                 let stored = if self.storage.contains_key(&item_id.to_string()) {
@@ -180,10 +235,10 @@ impl Component for Dinnerlist {
                     "-1".to_string()
                 };
                 let mut general_options: Vec<Html> = vec![html!(<option value="none" id="none" selected={stored.eq("0")}>{"No Change"}</option>)];
-                if item.stock > 0 {
+                if item.stock > 0.0 {
                     general_options.push(html!(<option value="out" id="out" selected={stored.eq("1")}>{"Out"}</option>));
                 }
-                if item.stock > 1 {
+                if item.stock > 1.0 {
                     general_options.push(html!(<option value="low" id="low" selected={stored.eq("2")}>{"Low"}</option>));
                 }
                 let item_id = item_id.clone();
@@ -201,10 +256,13 @@ impl Component for Dinnerlist {
                     false => None,
                 };
                 let item_id = item_id.clone();
+                let unit = item.unit.clone();
+                let unit_node = self.unit_nodes[&item_id].clone();
                 item_list.push(html!(<tr key={item_id.to_string()}>
                     <td class="name">{item_name}</td>
                     <td class="stock">
-                        <input type="number" size="5" min="1" placeholder="1" ref={self.item_nodes.get(&item_id).unwrap()} value={value} onchange={ctx.link().callback(move |_| DinnerListMsg::UpdateStorage(item_id.clone()))} />
+                        <input type="number" size="5" min="0" step="any" placeholder="1" ref={self.item_nodes.get(&item_id).unwrap()} value={value} onchange={ctx.link().callback(move |_| DinnerListMsg::UpdateStorage(item_id.clone()))} />
+                        <UnitSelect unit={unit} node={unit_node} />
                     </td>
                 </tr>));
             }
@@ -212,7 +270,13 @@ impl Component for Dinnerlist {
 
         html!(<div id="dinner-list" class="item-stock-tab">
         <div class="container">
-            <ItemSearch selection_callback={ctx.link().callback(DinnerListMsg::AddItem)}/>
+            <div class="scan-toggle">
+                <label><input type="checkbox" checked={self.scan_mode} onchange={ctx.link().callback(|_| DinnerListMsg::ToggleScan)}/>{"Scan mode"}</label>
+            </div>
+            <ItemSearch selection_callback={ctx.link().callback(DinnerListMsg::AddItem)} scan_callback={self.scan_mode.then(|| ctx.link().callback(DinnerListMsg::Scanned))}/>
+            if let Some(code) = &self.unknown_code {
+                <BarcodePrompt code={code.clone()} on_resolved={ctx.link().callback(DinnerListMsg::Resolved)} on_dismiss={ctx.link().callback(|_| DinnerListMsg::DismissCode)}/>
+            }
             <table>
                 {for item_list}
             </table>
@@ -220,4 +284,51 @@ impl Component for Dinnerlist {
         </div>
         </div>)
     }
-}
\ No newline at end of file
+}
+
+impl Dinnerlist {
+    /// Puts an item on the list with `count` in its input, unless it's there already.
+    fn add(&mut self, item: &Item, count: &str) {
+        let item_id = AttrValue::from(item.id.clone());
+        if self.list_items.contains(&item_id) {
+            return;
+        }
+        self.list_items.push(item_id.clone());
+        if item.track_general {
+            self.general_nodes.insert(item_id, NodeRef::default());
+        } else {
+            self.item_nodes.insert(item_id.clone(), NodeRef::default());
+            self.unit_nodes.insert(item_id, NodeRef::default());
+        }
+        self.storage.insert(item.id.clone(), count.to_owned());
+        let _ = LocalStorage::set("dinner_list", self.storage.clone());
+    }
+
+    /// A scan of `item`: puts it on the list once, and counts it up by one on every scan after.
+    fn scan(&mut self, item: &Item) {
+        self.add_count(item, 1.0);
+    }
+
+    /// Puts `count` of an item on the list, or adds it to what's there already.
+    /// Items tracked generally just go on the list, there's no count to add to.
+    fn add_count(&mut self, item: &Item, count: f64) {
+        let item_id = AttrValue::from(item.id.clone());
+        if item.track_general {
+            self.add(item, "");
+            return;
+        }
+        let Some(node) = self.item_nodes.get(&item_id) else {
+            self.add(item, &count.to_string());
+            return;
+        };
+        let input = node.cast::<HtmlInputElement>();
+        let value = input.as_ref().map(|input| input.value()).or_else(|| self.storage.get(&item.id).cloned()).unwrap_or_default();
+        // An empty count means one, as the placeholder says
+        let count = ((value.parse::<f64>().unwrap_or(1.0) + count) * 1000.0).round() / 1000.0;
+        if let Some(input) = input {
+            input.set_value(&count.to_string());
+        }
+        self.storage.insert(item.id.clone(), count.to_string());
+        let _ = LocalStorage::set("dinner_list", self.storage.clone());
+    }
+}
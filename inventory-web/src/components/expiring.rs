@@ -0,0 +1,97 @@
+use chrono::{DateTime, Local, Utc};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{items_api, models::{format_quantity, Lot}, InvCont};
+
+/// How far ahead the tab looks until told otherwise.
+const DEFAULT_DAYS: u32 = 7;
+
+pub enum ExpiringTabMsg {
+    Load,
+    Loaded(Vec<Lot>)
+}
+
+/// Lots that are past or coming up on their best-by date, soonest first.
+pub struct ExpiringTab {
+    lots: Option<Vec<Lot>>,
+    days: u32,
+    days_node: NodeRef
+}
+
+impl Component for ExpiringTab {
+    type Message = ExpiringTabMsg;
+
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ExpiringTabMsg::Load);
+        Self {
+            lots: None,
+            days: DEFAULT_DAYS,
+            days_node: NodeRef::default()
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+
+        match msg {
+            ExpiringTabMsg::Load => {
+                if let Some(days) = self.days_node.cast::<HtmlInputElement>().and_then(|node| node.value().parse().ok()) {
+                    self.days = days;
+                }
+                let days = self.days;
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    match items_api::fetch_expiring(days).await {
+                        Ok(lots) => vec![ExpiringTabMsg::Loaded(lots)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(ExpiringTabMsg::Load));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            ExpiringTabMsg::Loaded(lots) => self.lots = Some(lots),
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let now = Utc::now();
+        let rows: Vec<Html> = self.lots.iter().flatten().map(|lot| {
+            let best_by = lot.best_by.map(|best_by| DateTime::<Local>::from(best_by).format("%Y-%m-%d").to_string()).unwrap_or_default();
+            let received: DateTime<Local> = lot.received.into();
+            let expired = lot.best_by.is_some_and(|best_by| best_by < now);
+            html!(<tr key={lot.id.clone()} class={classes!(expired.then_some("expired"))}>
+                <td class="name">{lot.name.clone()}</td>
+                <td>{lot.location_name.clone()}</td>
+                <td>{format_quantity(lot.stock, &lot.unit)}</td>
+                <td class="time">{best_by}</td>
+                <td class="time">{received.format("%Y-%m-%d").to_string()}</td>
+            </tr>)
+        }).collect();
+        let empty = self.lots.as_ref().is_some_and(Vec::is_empty);
+
+        html!(<div id="expiring-tab">
+        <div class="container">
+            <div class="expiring-filters">
+                <label>{"Best by within"}</label>
+                <input type="number" size="3" min="0" value={self.days.to_string()} ref={&self.days_node}/>
+                <label>{"days"}</label>
+                <button onclick={ctx.link().callback(|_| ExpiringTabMsg::Load)}>{"Search"}</button>
+            </div>
+            if empty {
+                <p>{"Nothing is close to its best-by date."}</p>
+            } else {
+                <table>
+                    <tr><th>{"Item"}</th><th>{"Location"}</th><th>{"Stock"}</th><th>{"Best by"}</th><th>{"Received"}</th></tr>
+                    {for rows}
+                </table>
+            }
+        </div>
+        </div>)
+    }
+}
@@ -4,21 +4,36 @@ use gloo_storage::{LocalStorage, Storage};
 use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
-use crate::{models::RestockItem, InvCont, ItemSearch};
+use crate::{components::{BarcodePrompt, selected_package, selected_unit, UnitSelect}, models::{Item, RestockItem}, InvCont, ItemSearch};
 
 pub enum GroceryBagMsg {
     RetrieveStorage,
     UpdateStorage(AttrValue),
     AddItem(AttrValue),
+    ToggleScan,
+    Scanned(AttrValue),
+    Resolved(Item),
+    /// The api doesn't know a scanned barcode either
+    Unknown(AttrValue),
+    DismissCode,
     Submit
 }
 
 pub struct GroceryBag {
     list_items: Vec<AttrValue>,
     item_nodes: BTreeMap<AttrValue, NodeRef>,
+    unit_nodes: BTreeMap<AttrValue, NodeRef>,
+    best_nodes: BTreeMap<AttrValue, NodeRef>,
+    /// What was paid for each counted item, all of it rather than each one
+    price_nodes: BTreeMap<AttrValue, NodeRef>,
     general_nodes: BTreeMap<AttrValue, NodeRef>,
     storage: BTreeMap<String, String>,
-    init_callback: Vec<ContextHandle<InvCont>>
+    /// The store the whole bag was bought at
+    store_node: NodeRef,
+    init_callback: Vec<ContextHandle<InvCont>>,
+    scan_mode: bool,
+    /// A scanned barcode no item has, waiting to be linked to one
+    unknown_code: Option<AttrValue>
 }
 
 impl Component for GroceryBag {
@@ -42,9 +57,15 @@ impl Component for GroceryBag {
         Self { 
             list_items: vec![], 
             item_nodes: BTreeMap::new(), 
+            unit_nodes: BTreeMap::new(),
+            best_nodes: BTreeMap::new(),
+            price_nodes: BTreeMap::new(),
             general_nodes: BTreeMap::new(),
             storage,
-            init_callback
+            store_node: NodeRef::default(),
+            init_callback,
+            scan_mode: false,
+            unknown_code: None
         }
     }
 
@@ -56,6 +77,9 @@ impl Component for GroceryBag {
             GroceryBagMsg::RetrieveStorage => {
                 self.list_items = vec![];
                 self.item_nodes = BTreeMap::new();
+                self.unit_nodes = BTreeMap::new();
+                self.best_nodes = BTreeMap::new();
+                self.price_nodes = BTreeMap::new();
                 self.general_nodes = BTreeMap::new();
                 for (item_id, _value) in self.storage.iter() {
                     let item_id = AttrValue::from(item_id.clone());
@@ -72,7 +96,10 @@ impl Component for GroceryBag {
                     } else {
                         if !self.list_items.contains(&item_id) {
                             self.list_items.push(item_id.clone());
-                            self.item_nodes.insert(item_id, NodeRef::default());
+                            self.item_nodes.insert(item_id.clone(), NodeRef::default());
+                            self.unit_nodes.insert(item_id.clone(), NodeRef::default());
+                            self.best_nodes.insert(item_id.clone(), NodeRef::default());
+                            self.price_nodes.insert(item_id, NodeRef::default());
                         }
                     }
                 }
@@ -93,36 +120,46 @@ impl Component for GroceryBag {
                 return false;
             },
             GroceryBagMsg::AddItem(item_id) => {
-                let item = &inventory.item_id_map[&item_id];
-                if item.track_general {
-                    if !self.list_items.contains(&item_id) {
-                        self.list_items.push(item_id.clone());
-                        self.general_nodes.insert(item_id, NodeRef::default());
-                    }
-                } else {
-                    if !self.list_items.contains(&item_id) {
-                        self.list_items.push(item_id.clone());
-                        self.item_nodes.insert(item_id, NodeRef::default());
-                    }
-                }
-                self.storage.insert(item.id.clone(), "".to_string());
-                let _ = LocalStorage::set("grocery_bag", self.storage.clone());
+                let item = inventory.item_id_map[&item_id].clone();
+                self.add(&item, "");
+            },
+            GroceryBagMsg::ToggleScan => {
+                self.scan_mode = !self.scan_mode;
+                self.unknown_code = None;
             },
+            GroceryBagMsg::Scanned(code) => match controller.state.item_by_barcode(&code).cloned() {
+                Some(item) => self.scan(&item),
+                None => {
+                    controller.find_barcode(code.to_string(), ctx.link().callback(GroceryBagMsg::Resolved), ctx.link().callback(|code: String| GroceryBagMsg::Unknown(code.into())));
+                    return false;
+                },
+            },
+            GroceryBagMsg::Resolved(item) => {
+                self.unknown_code = None;
+                self.scan(&item);
+            },
+            GroceryBagMsg::Unknown(code) => self.unknown_code = Some(code),
+            GroceryBagMsg::DismissCode => self.unknown_code = None,
             GroceryBagMsg::Submit => {
                 if self.item_nodes.len() == 0 && self.general_nodes.len() == 0 {
                     return false;
                 }
                 let mut items = vec![];
+                let store = self.store_node.cast::<HtmlSelectElement>().map(|node| node.value()).filter(|store| !store.is_empty());
                 for (id, node) in self.item_nodes.iter() {
                     let value = node.cast::<HtmlInputElement>().unwrap().value();
                     let count = match value.is_empty() {
-                        false => value.parse().unwrap_or(1),
-                        true => 1
+                        false => value.parse().unwrap_or(1.0),
+                        true => 1.0
                     };
-                    if count == 0 {
+                    if count <= 0.0 {
                         continue;
                     }
-                    items.push(RestockItem { id: id.to_string(), count });
+                    let unit = selected_unit(&self.unit_nodes[id], &inventory.item_id_map[id].unit);
+                    let package = selected_package(&self.unit_nodes[id]);
+                    let best_by = self.best_nodes[id].cast::<HtmlInputElement>().map(|node| node.value()).filter(|date| !date.is_empty());
+                    let price = self.price_nodes[id].cast::<HtmlInputElement>().and_then(|node| node.value().parse::<f64>().ok()).filter(|price| *price >= 0.0);
+                    items.push(RestockItem { id: id.to_string(), count, unit, package, best_by, price, unit_price: None, store: store.clone() });
                 }
                 for (id, node) in self.general_nodes.iter() {
                     let selected_general_count = node.cast::<HtmlSelectElement>().unwrap().selected_options().item(0).unwrap().id();
@@ -130,18 +167,21 @@ impl Component for GroceryBag {
                     if selected_general_count.eq("none") {
                         continue;
                     } else if selected_general_count.eq("low") {
-                        count = 1;
+                        count = 1.0;
                     } else { // "good"
-                        if inventory.item_id_map[id].stock == 0 {
-                            count = 2;
+                        if inventory.item_id_map[id].stock == 0.0 {
+                            count = 2.0;
                         } else {
-                            count = 1;
+                            count = 1.0;
                         }
                     }
-                    items.push(RestockItem { id: id.to_string(), count });
+                    items.push(RestockItem { id: id.to_string(), count, unit: None, package: None, best_by: None, price: None, unit_price: None, store: store.clone() });
                 }
                 self.list_items = vec![];
                 self.item_nodes = BTreeMap::new();
+                self.unit_nodes = BTreeMap::new();
+                self.best_nodes = BTreeMap::new();
+                self.price_nodes = BTreeMap::new();
                 self.general_nodes = BTreeMap::new();
                 self.storage = BTreeMap::new();
                 LocalStorage::delete("grocery_bag");
@@ -161,7 +201,8 @@ impl Component for GroceryBag {
 
         let mut item_list: Vec<Html> = vec![];
         for item_id in self.list_items.iter() {
-            let item = &id_map[item_id];
+            // An item created for a scanned barcode can get here before the items are reloaded
+            let Some(item) = id_map.get(item_id) else { continue };
             let item_name = &item.name;
             if item.track_general {
                 let stored = if self.storage.contains_key(&item_id.to_string()) {
@@ -170,10 +211,10 @@ impl Component for GroceryBag {
                     "-1".to_string()
                 };
                 let mut general_options: Vec<Html> = vec![html!(<option value="none" id="none" selected={stored.eq("0")}>{"No Change"}</option>)];
-                if item.stock < 1 {
+                if item.stock < 1.0 {
                     general_options.push(html!(<option value="low" id="low" selected={stored.eq("1")}>{"Low"}</option>));
                 }
-                if item.stock < 2 {
+                if item.stock < 2.0 {
                     general_options.push(html!(<option value="out" id="out" selected={stored.eq("2")}>{"Good"}</option>));
                 }
                 let item_id = item_id.clone();
@@ -191,10 +232,22 @@ impl Component for GroceryBag {
                     false => None,
                 };
                 let item_id = item_id.clone();
+                let unit = item.unit.clone();
+                let unit_node = self.unit_nodes[&item_id].clone();
+                let packages = item.packages.clone();
+                let best_node = self.best_nodes[&item_id].clone();
+                let price_node = self.price_nodes[&item_id].clone();
                 item_list.push(html!(<tr key={item_id.to_string()}>
                     <td class="name">{item_name}</td>
                     <td class="stock">
-                        <input type="number" size="5" min="1" placeholder="1" ref={self.item_nodes.get(&item_id).unwrap()} value={value} onchange={ctx.link().callback(move |_| GroceryBagMsg::UpdateStorage(item_id.clone()))} />
+                        <input type="number" size="5" min="0" step="any" placeholder="1" ref={self.item_nodes.get(&item_id).unwrap()} value={value} onchange={ctx.link().callback(move |_| GroceryBagMsg::UpdateStorage(item_id.clone()))} />
+                        <UnitSelect unit={unit} packages={packages} node={unit_node} />
+                    </td>
+                    <td class="best-by">
+                        <input type="date" title="Best by" ref={best_node} />
+                    </td>
+                    <td class="price">
+                        <input type="number" size="6" min="0" step="0.01" placeholder="Price" title="Price paid" ref={price_node} />
                     </td>
                 </tr>));
             }
@@ -202,7 +255,22 @@ impl Component for GroceryBag {
 
         html!(<div id="grocery-bag" class="item-stock-tab">
         <div class="container">
-            <ItemSearch selection_callback={ctx.link().callback(GroceryBagMsg::AddItem)}/>
+            <div class="scan-toggle">
+                <label><input type="checkbox" checked={self.scan_mode} onchange={ctx.link().callback(|_| GroceryBagMsg::ToggleScan)}/>{"Scan mode"}</label>
+            </div>
+            <ItemSearch selection_callback={ctx.link().callback(GroceryBagMsg::AddItem)} scan_callback={self.scan_mode.then(|| ctx.link().callback(GroceryBagMsg::Scanned))}/>
+            if let Some(code) = &self.unknown_code {
+                <BarcodePrompt code={code.clone()} on_resolved={ctx.link().callback(GroceryBagMsg::Resolved)} on_dismiss={ctx.link().callback(|_| GroceryBagMsg::DismissCode)}/>
+            }
+            if !controller.state.stores.is_empty() {
+                <div class="bag-store">
+                    <label>{"Bought at: "}</label>
+                    <select ref={&self.store_node}>
+                        <option value="">{"No store"}</option>
+                        {for controller.state.stores.iter().map(|store| html!(<option value={store.id.clone()}>{store.name.clone()}</option>))}
+                    </select>
+                </div>
+            }
             <table>
                 {for item_list}
             </table>
@@ -210,4 +278,43 @@ impl Component for GroceryBag {
         </div>
         </div>)
     }
-}
\ No newline at end of file
+}
+
+impl GroceryBag {
+    /// Puts an item on the list with `count` in its input, unless it's there already.
+    fn add(&mut self, item: &Item, count: &str) {
+        let item_id = AttrValue::from(item.id.clone());
+        if self.list_items.contains(&item_id) {
+            return;
+        }
+        self.list_items.push(item_id.clone());
+        if item.track_general {
+            self.general_nodes.insert(item_id, NodeRef::default());
+        } else {
+            self.item_nodes.insert(item_id.clone(), NodeRef::default());
+            self.unit_nodes.insert(item_id.clone(), NodeRef::default());
+            self.best_nodes.insert(item_id.clone(), NodeRef::default());
+            self.price_nodes.insert(item_id, NodeRef::default());
+        }
+        self.storage.insert(item.id.clone(), count.to_owned());
+        let _ = LocalStorage::set("grocery_bag", self.storage.clone());
+    }
+
+    /// A scan of `item`: puts it on the list once, and counts it up by one on every scan after.
+    fn scan(&mut self, item: &Item) {
+        let item_id = AttrValue::from(item.id.clone());
+        let Some(node) = self.item_nodes.get(&item_id) else {
+            self.add(item, "1");
+            return;
+        };
+        let input = node.cast::<HtmlInputElement>();
+        let value = input.as_ref().map(|input| input.value()).or_else(|| self.storage.get(&item.id).cloned()).unwrap_or_default();
+        // An empty count means one, as the placeholder says
+        let count = value.parse::<f64>().unwrap_or(1.0) + 1.0;
+        if let Some(input) = input {
+            input.set_value(&count.to_string());
+        }
+        self.storage.insert(item.id.clone(), count.to_string());
+        let _ = LocalStorage::set("grocery_bag", self.storage.clone());
+    }
+}
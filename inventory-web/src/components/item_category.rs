@@ -1,11 +1,15 @@
 use yew::prelude::*;
 
-use crate::models::Item;
+use crate::models::{format_quantity, Item};
 
 #[derive(Properties, PartialEq)]
 pub struct ItemCategoryProps {
     pub name: String,
-    pub items: Vec<Item>
+    pub items: Vec<Item>,
+    #[prop_or_default]
+    pub colour: Option<String>,
+    #[prop_or_default]
+    pub icon: Option<String>
 }
 
 #[function_component]
@@ -15,9 +19,9 @@ pub fn ItemCategory(props: &ItemCategoryProps) -> Html{
     items.sort_by(|a, b| a.name.cmp(&b.name));
     let mut item_rows: Vec<Html>= vec![];
     for item in items {
-        let row_class = if item.desired_stock == 0 {
+        let row_class = if item.desired_stock == 0.0 {
             None
-        } else if item.stock == 0 || item.stock < (item.desired_stock - 1) {
+        } else if item.stock == 0.0 || item.stock < (item.desired_stock - 1.0) {
             Some("stock-critical")
         } else if item.stock < item.desired_stock {
             Some("stock-low")
@@ -25,9 +29,9 @@ pub fn ItemCategory(props: &ItemCategoryProps) -> Html{
             None
         };
         if item.track_general {
-            let general_msg = if item.stock == 2 {
+            let general_msg = if item.stock == 2.0 {
                 "Good"
-            } else if item.stock == 1 {
+            } else if item.stock == 1.0 {
                 "Low"
             } else {
                 "Out"
@@ -39,13 +43,18 @@ pub fn ItemCategory(props: &ItemCategoryProps) -> Html{
         } else {
             item_rows.push(html!(<tr class={classes!(row_class)} key={item.name.clone()}>
                 <td class="name">{item.name}</td>
-                <td class="stock">{item.stock}</td>
-                <td class="desired-stock">{format!(": {}", item.desired_stock)}</td>
+                <td class="stock">{item.stock.to_string()}</td>
+                <td class="desired-stock">{format!(": {}", format_quantity(item.desired_stock, &item.unit))}</td>
             </tr>));
         }
     }
-    html!(<td class="category" key={name.clone()}>
-        <h3>{name}</h3>
+    let style = props.colour.as_ref().map(|colour| format!("border-top: 4px solid {}", colour));
+    let heading = match &props.icon {
+        Some(icon) => format!("{} {}", icon, name),
+        None => name.clone(),
+    };
+    html!(<td class="category" key={name.clone()} style={style}>
+        <h3>{heading}</h3>
         <hr />
         <table class="item-table">
             {for item_rows}
@@ -22,9 +22,12 @@ pub fn ItemList() -> Html {
     //     .collect();
 
     let mut categories: Vec<Html> = vec![];
-    for (name, cat_items) in &category_map {
+    for (name, cat_items) in inv_cont.state.ordered(category_map) {
+        let category = inv_cont.state.category(&name);
+        let colour = category.and_then(|category| category.colour.clone());
+        let icon = category.and_then(|category| category.icon.clone());
         categories.push(html!(
-            <ItemCategory name={name.clone()} items={cat_items.clone()} />
+            <ItemCategory name={name.clone()} items={cat_items} colour={colour} icon={icon} />
         ));
     }
 
@@ -5,21 +5,34 @@ use yew::prelude::*;
 
 use crate::InvCont;
 
+/// Most a scanner leaves between two keys. People typing take several times longer.
+const SCAN_KEY_GAP_MS: f64 = 50.0;
+/// The shortest barcode a scanner types, an EAN-8.
+const SCAN_MIN_LENGTH: usize = 8;
+
 pub enum ItemSearchMsg {
     SearchChange,
     SelectItem(AttrValue),
-    SelectFirst
+    SelectFirst,
+    KeyDown(String, f64)
 }
 
 #[derive(Properties, PartialEq)]
 pub struct ItemSearchProps {
-    pub selection_callback: Callback<AttrValue>
+    pub selection_callback: Callback<AttrValue>,
+    /// Turns on scan mode: digits typed as fast as a barcode scanner types them, followed by
+    /// Enter, go here as a barcode instead of selecting the first item found by name.
+    #[prop_or_default]
+    pub scan_callback: Option<Callback<AttrValue>>
 }
 
 pub struct ItemSearch {
     search_node: NodeRef,
     search_value: Option<String>,
-    filtered_items: Vec<(AttrValue, AttrValue)>
+    filtered_items: Vec<(AttrValue, AttrValue)>,
+    /// Digits typed in a quick burst so far, with when the last one was
+    burst: String,
+    last_key: f64
 }
 
 impl Component for ItemSearch {
@@ -28,7 +41,7 @@ impl Component for ItemSearch {
     type Properties = ItemSearchProps;
 
     fn create(_ctx: &Context<Self>) -> Self {
-        Self { search_node: NodeRef::default(), search_value: None, filtered_items: vec![] }
+        Self { search_node: NodeRef::default(), search_value: None, filtered_items: vec![], burst: String::new(), last_key: 0.0 }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -52,7 +65,30 @@ impl Component for ItemSearch {
                 node.set_value("");
                 self.search_value = None;
             },
+            ItemSearchMsg::KeyDown(key, time) => {
+                let digit = key.len() == 1 && key.chars().all(|c| c.is_ascii_digit());
+                if key != "Enter" {
+                    if !digit || time - self.last_key > SCAN_KEY_GAP_MS {
+                        self.burst.clear();
+                    }
+                    if digit {
+                        self.burst.push_str(&key);
+                    }
+                }
+                self.last_key = time;
+                return false;
+            },
             ItemSearchMsg::SelectFirst => {
+                let node = self.search_node.cast::<HtmlInputElement>().unwrap();
+                let burst = std::mem::take(&mut self.burst);
+                if let Some(scan_callback) = &props.scan_callback {
+                    if burst.len() >= SCAN_MIN_LENGTH && node.value().trim() == burst {
+                        scan_callback.emit(AttrValue::from(burst));
+                        node.set_value("");
+                        self.search_value = None;
+                        return true;
+                    }
+                }
                 if self.filtered_items.len() < 1 {
                     return false
                 }
@@ -80,8 +116,10 @@ impl Component for ItemSearch {
             }
         }
         
+        let scanning = ctx.props().scan_callback.is_some();
         html!(<div class="item-search">
-            <input class="search" placeholder="Search" ref={&self.search_node} 
+            <input class="search" placeholder={if scanning { "Search or scan" } else { "Search" }} ref={&self.search_node}
+                onkeydown={scanning.then(|| ctx.link().callback(|e: KeyboardEvent| ItemSearchMsg::KeyDown(e.key(), e.time_stamp())))}
                 onkeyup={ctx.link().callback(|e: KeyboardEvent| {
                     if e.key().eq("Enter") {
                         ItemSearchMsg::SelectFirst
@@ -1,22 +1,28 @@
-use chrono::{DateTime, Datelike, Local};
-use log::info;
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
-use crate::{items_api, InvCont};
+use crate::{items_api, models::{format_quantity, HistoryFilter, HistoryPage}, InvCont};
 
-pub struct LogItem {
-    pub time: DateTime<Local>,
-    pub level: AttrValue,
-    pub msg: AttrValue
-}
+const OPERATIONS: [&str; 5] = ["restock", "consume", "change", "reinventory", "delete"];
 
 pub enum LogTabMsg {
-    LoadLogs((String, String))
+    Search,
+    Load(usize),
+    Loaded(HistoryPage)
 }
 
+/// Stock history from the api's ledger, filtered by date, item, category and operation.
 pub struct LogTab {
-    pub current: Vec<LogItem>,
-    pub yesterday: Vec<LogItem>
+    pub history: Option<HistoryPage>,
+    filter: HistoryFilter,
+    from_node: NodeRef,
+    to_node: NodeRef,
+    item_node: NodeRef,
+    category_node: NodeRef,
+    operation_node: NodeRef
 }
 
 impl Component for LogTab {
@@ -24,102 +30,126 @@ impl Component for LogTab {
 
     type Properties=();
 
-    fn create(_ctx: &Context<Self>) -> Self {
-        Self { current: vec![], yesterday: vec![] }
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(LogTabMsg::Load(1));
+        Self {
+            history: None,
+            filter: HistoryFilter::default(),
+            from_node: NodeRef::default(),
+            to_node: NodeRef::default(),
+            item_node: NodeRef::default(),
+            category_node: NodeRef::default(),
+            operation_node: NodeRef::default()
+        }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
 
         match msg {
-            LogTabMsg::LoadLogs((current, yesterday)) => {
-                info!("{}, {}", current, yesterday);
-                self.current = parse_log(current, controller.clone());
-                self.current.sort_by(|a, b| b.time.cmp(&a.time));
-                self.yesterday = parse_log(yesterday, controller.clone());
-                self.yesterday.sort_by(|a, b| b.time.cmp(&a.time));
+            LogTabMsg::Search => {
+                self.filter = HistoryFilter {
+                    from: self.date(&self.from_node, NaiveTime::MIN),
+                    to: self.date(&self.to_node, NaiveTime::from_hms_opt(23, 59, 59).unwrap()),
+                    item: self.selected(&self.item_node),
+                    category: self.selected(&self.category_node),
+                    operation: self.selected(&self.operation_node),
+                    page: 1
+                };
+                ctx.link().send_message(LogTabMsg::Load(1));
+                return false;
             },
+            LogTabMsg::Load(page) => {
+                let mut filter = self.filter.clone();
+                filter.page = page;
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    match items_api::fetch_history(filter).await {
+                        Ok(history) => vec![LogTabMsg::Loaded(history)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(LogTabMsg::Load(page)));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            LogTabMsg::Loaded(history) => self.history = Some(history),
         }
         true
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        
-        let mut current_logs: Vec<Html> = vec![];
-        for log_item in self.current.iter() {
-            current_logs.push(html!(<tr>
-                <td class="time">{log_item.time.format("%r").to_string()}</td>
-                <td>{log_item.msg.clone()}</td>
-            </tr>))
-        }
-        let mut yesterday_logs: Vec<Html> = vec![];
-        for log_item in self.yesterday.iter() {
-            yesterday_logs.push(html!(<tr>
-                <td class="time">{log_item.time.format("%r").to_string()}</td>
-                <td>{log_item.msg.clone()}</td>
-            </tr>))
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let inventory = &controller.state.inventory;
+        let categories: BTreeSet<&String> = controller.state.items.iter().map(|item| &item.category).collect();
+
+        let mut rows: Vec<Html> = vec![];
+        let mut pages = html!();
+        if let Some(history) = &self.history {
+            for event in history.events.iter() {
+                let time: DateTime<Local> = event.time.into();
+                rows.push(html!(<tr>
+                    <td class="time">{time.format("%Y-%m-%d %r").to_string()}</td>
+                    <td>{event.name.clone()}</td>
+                    <td>{format!("{}{}", if event.delta > 0.0 { "+" } else { "" }, format_quantity(event.delta, &event.unit))}</td>
+                    <td>{format_quantity(event.stock, &event.unit)}</td>
+                    <td>{event.operation.clone()}</td>
+                    <td>{event.source.clone()}</td>
+                </tr>))
+            }
+            let page = history.page;
+            let page_count = history.page_count();
+            pages = html!(<div class="log-pages">
+                <button disabled={page <= 1} onclick={ctx.link().callback(move |_| LogTabMsg::Load(page - 1))}>{"Newer"}</button>
+                <span>{format!("Page {} of {} ({} changes)", page, page_count, history.total)}</span>
+                <button disabled={page >= page_count} onclick={ctx.link().callback(move |_| LogTabMsg::Load(page + 1))}>{"Older"}</button>
+            </div>);
         }
 
         html!(<div id="logs-tab">
         <div class="container">
-            <button class="log_reload" onclick={ctx.link().callback_future(|_| async {let logs = items_api::fetch_logs().await.unwrap_or_default(); LogTabMsg::LoadLogs(logs)})}>{"Reload Logs"}</button>
-            <h3>{"Today's logs:"}</h3>
+            <div class="log-filters">
+                <label>{"From:"}</label>
+                <input type="date" ref={&self.from_node}/>
+                <label>{"To:"}</label>
+                <input type="date" ref={&self.to_node}/>
+                <select ref={&self.item_node}>
+                    <option value="">{"All items"}</option>
+                    {for inventory.name_to_id.iter().map(|(name, id)| html!(<option value={id.clone()}>{name.clone()}</option>))}
+                </select>
+                <select ref={&self.category_node}>
+                    <option value="">{"All categories"}</option>
+                    {for categories.into_iter().map(|category| html!(<option value={category.to_string()}>{category.to_string()}</option>))}
+                </select>
+                <select ref={&self.operation_node}>
+                    <option value="">{"All changes"}</option>
+                    {for OPERATIONS.iter().map(|operation| html!(<option value={*operation}>{*operation}</option>))}
+                </select>
+                <button class="log_reload" onclick={ctx.link().callback(|_| LogTabMsg::Search)}>{"Search"}</button>
+            </div>
+            <h3>{"Stock history:"}</h3>
             <div class="log-container"><table>
-                {for current_logs}
-            </table></div>
-            <h3>{"Yesterday's logs:"}</h3>
-            <div class="log-container"><table>
-                {for yesterday_logs}
+                <tr><th>{"Time"}</th><th>{"Item"}</th><th>{"Change"}</th><th>{"Stock"}</th><th>{"Operation"}</th><th>{"Source"}</th></tr>
+                {for rows}
             </table></div>
+            {pages}
         </div>
         </div>)
     }
 }
 
-fn parse_log(log: String, inv_cont: InvCont) -> Vec<LogItem> {
-    if log.is_empty() {
-        return vec![];
+impl LogTab {
+    /// The value of a filter select, with the "All" option as `None`.
+    fn selected(&self, node: &NodeRef) -> Option<String> {
+        let value = node.cast::<HtmlSelectElement>()?.value();
+        (!value.is_empty()).then_some(value)
     }
-    let year = Local::now().year().to_string();
-    let mut log_items: Vec<LogItem> = vec![];
-    let search_value = "\n".to_owned() + &year;
-    let mut log = log;
 
-    loop {
-        let i = log.rfind(&search_value);
-        match i {
-            None => break,
-            Some(i) => {
-                let item = log.split_off(i+1);
-                log_items.push(parse_log_line(item, inv_cont.clone()));
-                log.pop();
-            }
-        }
+    /// A date input as a point in time on that day in the browser's time zone.
+    fn date(&self, node: &NodeRef, time: NaiveTime) -> Option<DateTime<Utc>> {
+        let value = node.cast::<HtmlInputElement>()?.value();
+        let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d").ok()?;
+        Local.from_local_datetime(&date.and_time(time)).earliest().map(|time| time.with_timezone(&Utc))
     }
-    log_items.push(parse_log_line(log, inv_cont));
-    log_items
 }
-
-fn parse_log_line(item: String, inv_cont: InvCont) -> LogItem {
-    let time_zone = Local::now().offset().to_string();
-    let id_map = &inv_cont.state.inventory.item_id_map;
-
-    let mut item_iter = item.split(" | ").into_iter();
-    let time = DateTime::parse_from_str(&(item_iter.next().unwrap().to_owned() + " " + &time_zone), "%Y-%m-%d %H:%M:%S %z").unwrap().into();
-    let level = AttrValue::from(item_iter.next().unwrap().to_string());
-    let mut msg = item_iter.next().unwrap().to_string();
-    if msg.contains("Consumed:") || msg.contains("Restocked") {
-        loop {
-            let i = msg.find("items:");
-            match i {
-                None => break,
-                Some(i) => {
-                    let item_id: String = msg.drain(i..i+26).collect();
-                    let item = id_map.get(&AttrValue::from(item_id)).unwrap();
-                    msg.insert_str(i, &item.name);
-                }
-            }
-        }
-    }
-    LogItem { time, level, msg: AttrValue::from(msg) }
-}
\ No newline at end of file
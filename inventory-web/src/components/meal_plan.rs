@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Local, NaiveDate};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{items_api, models::{PlannedMeal, PlannedMealInput}, InvCont};
+
+/// How many days the plan shows at once.
+const PLAN_DAYS: i64 = 7;
+
+pub enum MealPlanMsg {
+    Load,
+    Loaded(Vec<PlannedMeal>),
+    /// Moves the days shown by a number of weeks
+    Shift(i64),
+    Add(NaiveDate),
+    ChangeServings(AttrValue),
+    Remove(AttrValue)
+}
+
+/// A week of planned dinners. What they need shows up on the Shopping List.
+pub struct MealPlanTab {
+    start: NaiveDate,
+    meals: Vec<PlannedMeal>,
+    /// The recipe select and servings input of each day's add row
+    add_nodes: BTreeMap<NaiveDate, (NodeRef, NodeRef)>,
+    servings_nodes: BTreeMap<AttrValue, NodeRef>
+}
+
+impl Component for MealPlanTab {
+    type Message = MealPlanMsg;
+
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(MealPlanMsg::Load);
+        let start = Local::now().date_naive();
+        Self {
+            start,
+            meals: vec![],
+            add_nodes: days(start).map(|day| (day, (NodeRef::default(), NodeRef::default()))).collect(),
+            servings_nodes: BTreeMap::new()
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let reload = ctx.link().callback(|_| MealPlanMsg::Load);
+
+        match msg {
+            MealPlanMsg::Load => {
+                let (from, to) = (self.start, self.start + Duration::days(PLAN_DAYS - 1));
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    match items_api::fetch_meal_plan(from, to).await {
+                        Ok(meals) => vec![MealPlanMsg::Loaded(meals)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(MealPlanMsg::Load));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            MealPlanMsg::Loaded(meals) => {
+                self.servings_nodes = meals.iter().map(|meal| (AttrValue::from(meal.id.clone()), NodeRef::default())).collect();
+                self.meals = meals;
+            },
+            MealPlanMsg::Shift(weeks) => {
+                self.start += Duration::days(PLAN_DAYS * weeks);
+                self.add_nodes = days(self.start).map(|day| (day, (NodeRef::default(), NodeRef::default()))).collect();
+                self.meals = vec![];
+                ctx.link().send_message(MealPlanMsg::Load);
+            },
+            MealPlanMsg::Add(date) => {
+                let Some((recipe_node, servings_node)) = self.add_nodes.get(&date) else { return false };
+                let recipe = recipe_node.cast::<HtmlSelectElement>().map(|node| node.value()).unwrap_or_default();
+                if recipe.is_empty() {
+                    return false;
+                }
+                let servings = servings_node.cast::<HtmlInputElement>().and_then(|node| node.value().parse::<f64>().ok()).filter(|servings| *servings > 0.0);
+                controller.plan_meal(PlannedMealInput { date, recipe, servings }, reload);
+                return false;
+            },
+            MealPlanMsg::ChangeServings(id) => {
+                let servings = self.servings_nodes.get(&id).and_then(|node| node.cast::<HtmlInputElement>()).and_then(|node| node.value().parse::<f64>().ok());
+                if let Some(servings) = servings.filter(|servings| *servings > 0.0) {
+                    controller.change_planned_servings(id.to_string(), servings, reload);
+                }
+                return false;
+            },
+            MealPlanMsg::Remove(id) => {
+                controller.delete_planned_meal(id.to_string(), reload);
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let recipes = &controller.state.recipes;
+        let today = Local::now().date_naive();
+
+        let day_rows = days(self.start).map(|day| {
+            let meals = self.meals.iter().filter(|meal| meal.date == day).map(|meal| {
+                let id = AttrValue::from(meal.id.clone());
+                let (change_id, remove_id) = (id.clone(), id.clone());
+                html!(<li key={meal.id.clone()}>
+                    <span class="name">{meal.recipe_name.clone()}</span>
+                    <input type="number" size="3" min="0" step="any" value={meal.servings.to_string()} ref={&self.servings_nodes[&id]}
+                        onchange={ctx.link().callback(move |_| MealPlanMsg::ChangeServings(change_id.clone()))}/>
+                    <button onclick={ctx.link().callback(move |_| MealPlanMsg::Remove(remove_id.clone()))}>{"Remove"}</button>
+                </li>)
+            });
+            let (recipe_node, servings_node) = &self.add_nodes[&day];
+            html!(<tr key={day.to_string()} class={classes!((day == today).then_some("today"))}>
+                <td class="date">{day.format("%a %d %b").to_string()}</td>
+                <td>
+                    <ul class="planned-meals">{for meals}</ul>
+                    <div class="plan-add">
+                        <select ref={recipe_node}>
+                            <option value="">{"Add a recipe..."}</option>
+                            {for recipes.iter().map(|recipe| html!(<option value={recipe.id.clone()}>{recipe.name.clone()}</option>))}
+                        </select>
+                        <input type="number" size="3" min="0" step="any" placeholder="serves" ref={servings_node}/>
+                        <button onclick={ctx.link().callback(move |_| MealPlanMsg::Add(day))}>{"Add"}</button>
+                    </div>
+                </td>
+            </tr>)
+        });
+
+        html!(<div id="meal-plan-tab">
+        <div class="container">
+            <div class="plan-weeks">
+                <button onclick={ctx.link().callback(|_| MealPlanMsg::Shift(-1))}>{"◀ Previous week"}</button>
+                <button onclick={ctx.link().callback(|_| MealPlanMsg::Shift(1))}>{"Next week ▶"}</button>
+            </div>
+            if recipes.is_empty() {
+                <p>{"Add a recipe on the Recipes tab to start planning."}</p>
+            }
+            <table>
+                {for day_rows}
+            </table>
+        </div>
+        </div>)
+    }
+}
+
+/// The days shown from `start`.
+fn days(start: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    (0..PLAN_DAYS).map(move |offset| start + Duration::days(offset))
+}
@@ -1,21 +1,45 @@
+mod barcode_prompt;
+mod budgets;
+mod categories;
+mod cookable;
 mod dev_tab;
 mod dinner_list;
+mod expiring;
 mod grocery_bag;
 mod item_category;
 mod item_list;
 mod item_search;
 mod logs;
+mod meal_plan;
 mod message_box;
+mod recipes;
 mod reinventory;
+mod reports;
+mod settings;
 mod shopping_list;
+mod shopping_trip;
+mod stores;
+mod unit_select;
 
+pub use barcode_prompt::*;
+pub use budgets::*;
+pub use categories::*;
+pub use cookable::*;
 pub use dev_tab::*;
 pub use dinner_list::*;
+pub use expiring::*;
 pub use grocery_bag::*;
 pub use item_category::*;
 pub use item_list::*;
 pub use item_search::*;
 pub use logs::*;
+pub use meal_plan::*;
 pub use message_box::*;
+pub use recipes::*;
 pub use reinventory::*;
-pub use shopping_list::*;
\ No newline at end of file
+pub use reports::*;
+pub use settings::*;
+pub use shopping_list::*;
+pub use shopping_trip::*;
+pub use stores::*;
+pub use unit_select::*;
\ No newline at end of file
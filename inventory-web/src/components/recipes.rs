@@ -0,0 +1,262 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{components::{CookableRecipes, item_search::ItemSearch, selected_unit, UnitSelect}, error_message, models::{format_quantity, Ingredient, Recipe, RecipeInput}, InvCont};
+
+/// Reads a positive quantity out of a number input.
+fn quantity_value(node: &NodeRef) -> Option<f64> {
+    let value: f64 = node.cast::<HtmlInputElement>()?.value().parse().ok()?;
+    (value.is_finite() && value > 0.0).then_some(value)
+}
+
+#[derive(Properties, PartialEq)]
+struct RecipeRowProps {
+    recipe: Recipe,
+    on_load: Callback<Vec<(AttrValue, f64)>>,
+    on_edit: Callback<Recipe>
+}
+
+#[function_component]
+fn RecipeRow(props: &RecipeRowProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let servings_ref = use_node_ref();
+    let recipe = &props.recipe;
+
+    let servings = {
+        let servings_ref = servings_ref.clone();
+        let default = recipe.servings;
+        move || quantity_value(&servings_ref).unwrap_or(default)
+    };
+    let cook = {
+        let inv_cont = inv_cont.clone();
+        let servings = servings.clone();
+        let id = recipe.id.clone();
+        Callback::from(move |_| inv_cont.cook_recipe(id.clone(), servings()))
+    };
+    let load = {
+        let inv_cont = inv_cont.clone();
+        let on_load = props.on_load.clone();
+        let recipe = recipe.clone();
+        Callback::from(move |_| {
+            let servings = servings();
+            let items = recipe.ingredients.iter()
+                .filter_map(|ingredient| inv_cont.state.ingredient_need(&recipe, ingredient, servings))
+                .map(|(item, quantity)| (AttrValue::from(item.id.clone()), quantity))
+                .collect();
+            on_load.emit(items);
+        })
+    };
+    let edit = {
+        let on_edit = props.on_edit.clone();
+        let recipe = recipe.clone();
+        Callback::from(move |_| on_edit.emit(recipe.clone()))
+    };
+    let delete = {
+        let inv_cont = inv_cont.clone();
+        let id = recipe.id.clone();
+        Callback::from(move |_| inv_cont.delete_recipe(id.clone()))
+    };
+
+    let id_map = &inv_cont.state.inventory.item_id_map;
+    let ingredients = recipe.ingredients.iter().map(|ingredient| {
+        let item = id_map.get(&AttrValue::from(ingredient.item.clone()));
+        let name = item.map_or(ingredient.item.clone(), |item| item.name.clone());
+        let unit = ingredient.unit.clone().or_else(|| item.map(|item| item.unit.clone())).unwrap_or_default();
+        format!("{} {}", format_quantity(ingredient.quantity, &unit), name)
+    }).collect::<Vec<_>>().join(", ");
+
+    html!(<tr key={recipe.id.clone()}>
+        <td class="name">{recipe.name.clone()}</td>
+        <td class="ingredients">{ingredients}</td>
+        <td><input type="number" size="3" min="0" step="any" value={recipe.servings.to_string()} ref={servings_ref}/></td>
+        <td>
+            <button onclick={cook}>{"Cook"}</button>
+            <button onclick={load}>{"To Dinner List"}</button>
+            <button onclick={edit}>{"Edit"}</button>
+            <button onclick={delete}>{"Delete"}</button>
+        </td>
+    </tr>)
+}
+
+/// An ingredient row of the recipe being edited.
+struct IngredientRow {
+    item: AttrValue,
+    ingredient: Option<Ingredient>,
+    quantity_node: NodeRef,
+    unit_node: NodeRef
+}
+
+/// The recipe being written or changed. `id` is `None` for a new one.
+struct RecipeEditor {
+    id: Option<String>,
+    name: String,
+    servings: f64,
+    rows: Vec<IngredientRow>,
+    name_node: NodeRef,
+    servings_node: NodeRef
+}
+
+impl RecipeEditor {
+    fn new(recipe: Option<Recipe>) -> Self {
+        let recipe = recipe.unwrap_or_else(|| Recipe { id: String::new(), name: String::new(), servings: 4.0, ingredients: vec![] });
+        Self {
+            id: (!recipe.id.is_empty()).then_some(recipe.id),
+            name: recipe.name,
+            servings: recipe.servings,
+            rows: recipe.ingredients.into_iter().map(|ingredient| IngredientRow {
+                item: ingredient.item.clone().into(),
+                ingredient: Some(ingredient),
+                quantity_node: NodeRef::default(),
+                unit_node: NodeRef::default()
+            }).collect(),
+            name_node: NodeRef::default(),
+            servings_node: NodeRef::default()
+        }
+    }
+}
+
+pub enum RecipesTabMsg {
+    New,
+    Edit(Recipe),
+    AddIngredient(AttrValue),
+    RemoveIngredient(AttrValue),
+    Save,
+    Cancel
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RecipesTabProps {
+    /// Gets a recipe's ingredients as item ids and quantities in the item's unit, to go on the Dinner List
+    pub on_load: Callback<Vec<(AttrValue, f64)>>
+}
+
+/// Recipes: cook one straight away, send its ingredients to the Dinner List for adjusting first, or write and change them.
+pub struct RecipesTab {
+    editor: Option<RecipeEditor>
+}
+
+impl Component for RecipesTab {
+    type Message = RecipesTabMsg;
+
+    type Properties = RecipesTabProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self { editor: None }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+
+        match msg {
+            RecipesTabMsg::New => self.editor = Some(RecipeEditor::new(None)),
+            RecipesTabMsg::Edit(recipe) => self.editor = Some(RecipeEditor::new(Some(recipe))),
+            RecipesTabMsg::AddIngredient(item_id) => {
+                let Some(editor) = self.editor.as_mut() else { return false };
+                if editor.rows.iter().any(|row| row.item == item_id) {
+                    return false;
+                }
+                editor.rows.push(IngredientRow { item: item_id, ingredient: None, quantity_node: NodeRef::default(), unit_node: NodeRef::default() });
+            },
+            RecipesTabMsg::RemoveIngredient(item_id) => {
+                let Some(editor) = self.editor.as_mut() else { return false };
+                editor.rows.retain(|row| row.item != item_id);
+            },
+            RecipesTabMsg::Save => {
+                let Some(editor) = self.editor.as_ref() else { return false };
+                let name = editor.name_node.cast::<HtmlInputElement>().map(|node| node.value().trim().to_owned()).unwrap_or_default();
+                if name.is_empty() {
+                    controller.message.dispatch(error_message("A recipe name is required".into()));
+                    return false;
+                }
+                let Some(servings) = quantity_value(&editor.servings_node) else {
+                    controller.message.dispatch(error_message("Servings must be a positive number".into()));
+                    return false;
+                };
+                let mut ingredients = vec![];
+                for row in editor.rows.iter() {
+                    let Some(item) = controller.state.inventory.item_id_map.get(&row.item) else { continue };
+                    let Some(quantity) = quantity_value(&row.quantity_node) else {
+                        controller.message.dispatch(error_message(format!("Quantity of {} must be a positive number", item.name)));
+                        return false;
+                    };
+                    ingredients.push(Ingredient { item: item.id.clone(), quantity, unit: selected_unit(&row.unit_node, &item.unit) });
+                }
+                if ingredients.is_empty() {
+                    controller.message.dispatch(error_message("A recipe needs at least one ingredient".into()));
+                    return false;
+                }
+                let recipe = RecipeInput { name, servings, ingredients };
+                match &editor.id {
+                    Some(id) => controller.update_recipe(id.clone(), recipe),
+                    None => controller.create_recipe(recipe),
+                }
+                self.editor = None;
+            },
+            RecipesTabMsg::Cancel => self.editor = None,
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let recipes = &controller.state.recipes;
+        let on_edit = ctx.link().callback(RecipesTabMsg::Edit);
+
+        html!(<div id="recipes-tab">
+        <div class="container">
+            if let Some(editor) = &self.editor {
+                {self.view_editor(ctx, editor)}
+            } else {
+                <button onclick={ctx.link().callback(|_| RecipesTabMsg::New)}>{"New Recipe"}</button>
+            }
+            if recipes.is_empty() {
+                <p>{"No recipes yet."}</p>
+            } else {
+                <table>
+                    <tr><th>{"Recipe"}</th><th>{"Ingredients"}</th><th>{"Servings"}</th><th></th></tr>
+                    {for recipes.iter().map(|recipe| html!(
+                        <RecipeRow key={recipe.id.clone()} recipe={recipe.clone()} on_load={ctx.props().on_load.clone()} on_edit={on_edit.clone()} />
+                    ))}
+                </table>
+            }
+            <CookableRecipes />
+        </div>
+        </div>)
+    }
+}
+
+impl RecipesTab {
+    fn view_editor(&self, ctx: &Context<Self>, editor: &RecipeEditor) -> Html {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let id_map = &controller.state.inventory.item_id_map;
+
+        let rows = editor.rows.iter().filter_map(|row| {
+            let item = id_map.get(&row.item)?;
+            let item_id = row.item.clone();
+            let quantity = row.ingredient.as_ref().map(|ingredient| ingredient.quantity.to_string());
+            let selected = row.ingredient.as_ref().and_then(|ingredient| ingredient.unit.clone());
+            Some(html!(<tr key={row.item.to_string()}>
+                <td class="name">{item.name.clone()}</td>
+                <td class="stock">
+                    <input type="number" size="5" min="0" step="any" value={quantity} ref={&row.quantity_node}/>
+                    <UnitSelect unit={item.unit.clone()} selected={selected} node={row.unit_node.clone()} />
+                </td>
+                <td><button onclick={ctx.link().callback(move |_| RecipesTabMsg::RemoveIngredient(item_id.clone()))}>{"Remove"}</button></td>
+            </tr>))
+        });
+
+        html!(<div class="recipe-editor">
+            <div class="recipe-fields">
+                <input type="text" placeholder="Recipe name" value={editor.name.clone()} ref={&editor.name_node}/>
+                <label>{"Serves"}</label>
+                <input type="number" size="3" min="0" step="any" value={editor.servings.to_string()} ref={&editor.servings_node}/>
+            </div>
+            <ItemSearch selection_callback={ctx.link().callback(RecipesTabMsg::AddIngredient)} />
+            <table>
+                {for rows}
+            </table>
+            <button onclick={ctx.link().callback(|_| RecipesTabMsg::Save)}>{"Save"}</button>
+            <button onclick={ctx.link().callback(|_| RecipesTabMsg::Cancel)}>{"Cancel"}</button>
+        </div>)
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, HashMap};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
-use crate::{models::Item, InvCont};
+use crate::{models::{Item, DEFAULT_UNIT}, InvCont};
 
 pub enum ReInventoryMsg {
     Submit
@@ -81,9 +81,9 @@ impl Component for ReInventory {
         let inventory = &controller.state.inventory;
 
         let mut categories: Vec<Html> = vec![];
-        for (cat_name, item_ids) in self.category_map.iter() {
+        for (cat_name, item_ids) in controller.state.ordered(self.category_map.clone()) {
             let mut item_rows: Vec<Html> = vec![];
-            for item_id in item_ids {
+            for item_id in item_ids.iter() {
                 let item = &inventory.item_id_map[item_id];
                 let (stock_ref, desired_ref) = &self.inputs[item_id];
                 let item_name = 
@@ -92,10 +92,15 @@ impl Component for ReInventory {
                 } else {
                     item.name.clone()
                 };
+                let unit = match item.unit.as_str() {
+                    DEFAULT_UNIT => String::new(),
+                    unit => unit.to_owned(),
+                };
                 item_rows.push(html!(<tr key={item_name.clone()}>
                     <td class="name">{item_name.clone()}</td>
-                    <td class="stock"><input type="number" size="5" min="0" placeholder={item.stock.to_string()} ref={stock_ref} /></td>
-                    <td class="desired-stock"><input type="number" size="5" min="0" placeholder={item.desired_stock.to_string()} ref={desired_ref} /></td>
+                    <td class="stock"><input type="number" size="5" min="0" step="any" placeholder={item.stock.to_string()} ref={stock_ref} /></td>
+                    <td class="desired-stock"><input type="number" size="5" min="0" step="any" placeholder={item.desired_stock.to_string()} ref={desired_ref} /></td>
+                    <td class="unit">{unit}</td>
                 </tr>));
             }
 
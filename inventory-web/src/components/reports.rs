@@ -0,0 +1,245 @@
+use chrono::{Duration, Local, NaiveDate};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{components::item_search::ItemSearch, items_api, models::{format_quantity, per_unit, BudgetStatus, PriceComparison, Purchase, SpendingReport}, InvCont};
+
+/// How far back the tab looks until told otherwise, about a quarter.
+const DEFAULT_DAYS: i64 = 91;
+
+/// How many times the usual unit price a purchase has to cost to be flagged, the same as the api.
+const NOTABLY_ABOVE: f64 = 1.2;
+
+/// What spending can be totalled by, as the api calls it and as it's shown.
+const SPEND_BY: [(&str, &str); 4] = [("week", "Week"), ("month", "Month"), ("category", "Category"), ("store", "Store")];
+
+/// Money as it's shown, to the cent.
+fn money(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
+
+pub enum ReportsTabMsg {
+    Load,
+    Loaded(SpendingReport, BudgetStatus),
+    /// Shows the price history of an item picked in the search, and how its stores compare
+    SelectItem(AttrValue),
+    PricesLoaded(Vec<Purchase>, PriceComparison)
+}
+
+/// Where the money goes: what's left of this month's budgets, spending from recorded purchase prices
+/// totalled by week, month, category or store, and what's been paid for a single item over time and
+/// at each store.
+pub struct ReportsTab {
+    report: Option<SpendingReport>,
+    budgets: Option<BudgetStatus>,
+    by: AttrValue,
+    from: NaiveDate,
+    to: NaiveDate,
+    by_node: NodeRef,
+    from_node: NodeRef,
+    to_node: NodeRef,
+    prices: Vec<Purchase>,
+    comparison: Option<PriceComparison>
+}
+
+impl Component for ReportsTab {
+    type Message = ReportsTabMsg;
+
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ReportsTabMsg::Load);
+        let to = Local::now().date_naive();
+        Self {
+            report: None,
+            budgets: None,
+            by: SPEND_BY[0].0.into(),
+            from: to - Duration::days(DEFAULT_DAYS - 1),
+            to,
+            by_node: NodeRef::default(),
+            from_node: NodeRef::default(),
+            to_node: NodeRef::default(),
+            prices: vec![],
+            comparison: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+
+        match msg {
+            ReportsTabMsg::Load => {
+                if let Some(node) = self.by_node.cast::<HtmlSelectElement>() {
+                    self.by = node.value().into();
+                }
+                let date = |node: &NodeRef| node.cast::<HtmlInputElement>().and_then(|node| node.value().parse::<NaiveDate>().ok());
+                if let Some(from) = date(&self.from_node) {
+                    self.from = from;
+                }
+                if let Some(to) = date(&self.to_node) {
+                    self.to = to;
+                }
+                let (by, from, to) = (self.by.clone(), self.from, self.to);
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    let loaded = match items_api::fetch_spending(&by, from, to).await {
+                        Ok(report) => items_api::fetch_budget_status().await.map(|budgets| (report, budgets)),
+                        Err(e) => Err(e),
+                    };
+                    match loaded {
+                        Ok((report, budgets)) => vec![ReportsTabMsg::Loaded(report, budgets)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(ReportsTabMsg::Load));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            ReportsTabMsg::Loaded(report, budgets) => {
+                self.report = Some(report);
+                self.budgets = Some(budgets);
+            },
+            ReportsTabMsg::SelectItem(id) => {
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    let loaded = match items_api::fetch_prices(&id).await {
+                        Ok(prices) => items_api::fetch_price_comparison(&id).await.map(|comparison| (prices, comparison)),
+                        Err(e) => Err(e),
+                    };
+                    match loaded {
+                        Ok((prices, comparison)) => vec![ReportsTabMsg::PricesLoaded(prices, comparison)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(ReportsTabMsg::SelectItem(id.clone())));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            ReportsTabMsg::PricesLoaded(prices, comparison) => {
+                self.prices = prices;
+                self.comparison = Some(comparison);
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let spending = match &self.report {
+            None => html!(<p>{"Loading..."}</p>),
+            Some(report) if report.rows.iter().all(|row| row.purchases == 0) => html!(<p>{"Nothing with a price was bought in this time."}</p>),
+            Some(report) => {
+                let most = report.rows.iter().map(|row| row.total).fold(0.0, f64::max);
+                let periods = matches!(report.by.as_str(), "week" | "month");
+                html!(<>
+                    <p class="spend-total">
+                        {format!("Spent {} from {} to {}", money(report.total), report.from, report.to)}
+                        if periods && !report.rows.is_empty() {
+                            {format!(", {} a {} on average", money(report.total / report.rows.len() as f64), report.by)}
+                        }
+                    </p>
+                    <table class="spend-rows">
+                        {for report.rows.iter().map(|row| {
+                            let width = if most > 0.0 { row.total / most * 100.0 } else { 0.0 };
+                            html!(<tr key={row.key.clone()}>
+                                <td class="key">{row.key.clone()}</td>
+                                <td class="bar"><div class="spend-bar" style={format!("width: {:.1}%", width)}></div></td>
+                                <td class="total">{money(row.total)}</td>
+                                <td class="count">{format!("{} bought", row.purchases)}</td>
+                            </tr>)
+                        })}
+                    </table>
+                </>)
+            }
+        };
+
+        let budgets = match &self.budgets {
+            Some(status) if !status.lines.is_empty() => html!(<>
+                <h3>{format!("Budgets for {}", status.month)}</h3>
+                <table class="budget-lines">
+                    {for status.lines.iter().map(|line| {
+                        let used = if line.amount > 0.0 { (line.spent / line.amount * 100.0).min(100.0) } else { 100.0 };
+                        let left = match line.over {
+                            true => format!("{} over", money(-line.left)),
+                            false => format!("{} left", money(line.left)),
+                        };
+                        html!(<tr key={line.budget.clone()} class={classes!(line.over.then_some("over"))}>
+                            <td class="key">{line.category_name.clone().unwrap_or_else(|| "Overall".into())}</td>
+                            <td class="bar"><div class="spend-bar" style={format!("width: {:.1}%", used)}></div></td>
+                            <td class="total">{format!("{} of {}", money(line.spent), money(line.amount))}</td>
+                            <td class="count">{left}</td>
+                        </tr>)
+                    })}
+                </table>
+            </>),
+            _ => html!(),
+        };
+
+        html!(<div id="reports-tab">
+        <div class="container">
+            <div class="report-filters">
+                <label>{"Spending by"}</label>
+                <select ref={&self.by_node}>
+                    {for SPEND_BY.iter().map(|(value, label)| html!(<option value={*value} selected={self.by.as_str() == *value}>{*label}</option>))}
+                </select>
+                <label>{"from"}</label>
+                <input type="date" value={self.from.to_string()} ref={&self.from_node}/>
+                <label>{"to"}</label>
+                <input type="date" value={self.to.to_string()} ref={&self.to_node}/>
+                <button onclick={ctx.link().callback(|_| ReportsTabMsg::Load)}>{"Show"}</button>
+            </div>
+            {spending}
+            {budgets}
+            <h3>{"Price history"}</h3>
+            <div class="price-search">
+                <ItemSearch selection_callback={ctx.link().callback(ReportsTabMsg::SelectItem)} />
+            </div>
+            if let Some(comparison) = &self.comparison {
+                if self.prices.is_empty() {
+                    <p>{format!("No prices have been recorded for {}.", comparison.name)}</p>
+                } else {
+                    {self.view_prices(comparison)}
+                }
+            }
+        </div>
+        </div>)
+    }
+}
+
+impl ReportsTab {
+    /// The stores an item has been bought at, cheapest first, then every price paid for it with the ones
+    /// notably above its usual price marked.
+    fn view_prices(&self, comparison: &PriceComparison) -> Html {
+        let per = per_unit(&comparison.unit);
+        let above = |unit_price: f64| comparison.usual.is_some_and(|usual| unit_price > usual * NOTABLY_ABOVE);
+
+        html!(<>
+            <h4>{comparison.name.clone()}</h4>
+            if let Some(usual) = comparison.usual {
+                <p>{format!("Usually {} {}", money(usual), per)}</p>
+            }
+            <table class="store-prices">
+                <tr><th>{"Store"}</th><th>{format!("Latest {}", per)}</th><th>{format!("Average {}", per)}</th><th>{"Bought"}</th></tr>
+                {for comparison.stores.iter().enumerate().map(|(i, store)| html!(
+                    <tr key={store.store_name.clone()} class={classes!((i == 0 && comparison.stores.len() > 1).then_some("cheapest"))}>
+                        <td>{store.store_name.clone()}</td>
+                        <td>{money(store.latest)}</td>
+                        <td>{money(store.average)}</td>
+                        <td class="count">{format!("{} times, last {}", store.purchases, store.latest_time.with_timezone(&Local).format("%Y-%m-%d"))}</td>
+                    </tr>
+                ))}
+            </table>
+            <table class="price-history">
+                <tr><th>{"Date"}</th><th>{"Store"}</th><th>{"Bought"}</th><th>{"Price"}</th><th>{"Unit price"}</th></tr>
+                {for self.prices.iter().map(|purchase| html!(<tr key={purchase.id.clone()} class={classes!((purchase.price_unit == comparison.unit && above(purchase.unit_price)).then_some("above-usual"))}>
+                    <td class="time">{purchase.time.with_timezone(&Local).format("%Y-%m-%d").to_string()}</td>
+                    <td>{purchase.store_name.clone().unwrap_or_default()}</td>
+                    <td>{format_quantity(purchase.quantity, &purchase.unit)}</td>
+                    <td>{money(purchase.price)}</td>
+                    <td>{format!("{} {}", money(purchase.unit_price), per_unit(&purchase.price_unit))}</td>
+                </tr>))}
+            </table>
+        </>)
+    }
+}
@@ -0,0 +1,96 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{api_config, error_message, success_message, BudgetManager, CategoryManager, InvCont, StoreManager};
+
+pub enum SettingsMsg {
+    Test,
+    Tested(AttrValue, Result<usize, String>),
+    Save,
+    Reset,
+    Refresh
+}
+
+pub struct Settings {
+    url_node: NodeRef
+}
+
+impl Component for Settings {
+    type Message = SettingsMsg;
+
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self { url_node: NodeRef::default() }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let message = controller.message.clone();
+        let input_url = || -> AttrValue {
+            let value = self.url_node.cast::<HtmlInputElement>().unwrap().value();
+            if value.trim().is_empty() {
+                api_config::base_url().into()
+            } else {
+                value.into()
+            }
+        };
+
+        match msg {
+            SettingsMsg::Test => {
+                let url = input_url();
+                ctx.link().send_future(async move {
+                    let result = api_config::test_connection(url.clone()).await.map_err(|e| e.to_string());
+                    SettingsMsg::Tested(url, result)
+                });
+                return false;
+            },
+            SettingsMsg::Tested(url, result) => {
+                match result {
+                    Ok(count) => message.dispatch(success_message(format!("Connected to {} and found {} items", url, count))),
+                    Err(e) => message.dispatch(error_message(format!("Could not reach an inventory api at {}\n{}", url, e))),
+                }
+                return false;
+            },
+            SettingsMsg::Save => {
+                let url = input_url();
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    message.dispatch(error_message("The api address must start with http:// or https://".into()));
+                    return false;
+                }
+                api_config::save(&url);
+                self.url_node.cast::<HtmlInputElement>().unwrap().set_value("");
+                controller.init_items();
+            },
+            SettingsMsg::Reset => {
+                let controller = controller.clone();
+                ctx.link().send_future(async move {
+                    api_config::reset().await;
+                    controller.init_items();
+                    SettingsMsg::Refresh
+                });
+                return false;
+            },
+            SettingsMsg::Refresh => (),
+        }
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html!(<div id="settings-tab">
+        <div class="container">
+            <h3>{"Inventory API"}</h3>
+            <p>{format!("Currently using {} (from {})", api_config::base_url(), api_config::base_url_source())}</p>
+            <label>{"API address:"}</label>
+            <input type="text" placeholder={api_config::base_url()} ref={&self.url_node}/>
+            <button onclick={ctx.link().callback(|_| SettingsMsg::Test)}>{"Test Connection"}</button>
+            <button onclick={ctx.link().callback(|_| SettingsMsg::Save)}>{"Save"}</button>
+            <button onclick={ctx.link().callback(|_| SettingsMsg::Reset)}>{"Reset to Default"}</button>
+            <CategoryManager />
+            <StoreManager />
+            <BudgetManager />
+        </div>
+        </div>)
+    }
+}
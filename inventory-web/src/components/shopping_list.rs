@@ -1,35 +1,170 @@
-use std::collections::BTreeMap;
-
+use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
-use crate::{models::Item, InvCont, ItemCategory};
+use crate::{error_message, models::{format_quantity, ExtraInput, ShoppingCategory, ShoppingEntry, ShoppingList as List, Store}, InvCont};
+
+#[derive(Properties, PartialEq)]
+struct ShoppingCategoryViewProps {
+    category: ShoppingCategory
+}
+
+/// Why an entry is on the list, e.g. `stock 1 of 3, Tue Lasagne (500 g), extra 2`.
+fn reasons(entry: &ShoppingEntry) -> String {
+    let mut reasons = vec![];
+    if let (Some(stock), Some(desired_stock)) = (entry.stock, entry.desired_stock) {
+        if stock < desired_stock {
+            reasons.push(format!("stock {} of {}", stock, format_quantity(desired_stock, &entry.unit)));
+        }
+    }
+    for meal in entry.meals.iter() {
+        reasons.push(format!("{} {} ({})", meal.date.format("%a"), meal.recipe_name, format_quantity(meal.quantity, &entry.unit)));
+    }
+    if entry.item.is_some() {
+        for extra in entry.extras.iter() {
+            let unit = extra.unit.as_deref().unwrap_or(&entry.unit);
+            reasons.push(format!("extra {}", format_quantity(extra.quantity.unwrap_or(1.0), unit)));
+        }
+    }
+    reasons.join(", ")
+}
+
+fn entry_row(inv_cont: &InvCont, entry: &ShoppingEntry) -> Html {
+    let quantity = entry.quantity.map(|quantity| format_quantity(quantity, &entry.unit)).unwrap_or_default();
+    let removals = entry.extras.iter().map(|extra| {
+        let inv_cont = inv_cont.clone();
+        let id = extra.id.clone();
+        html!(<button class="remove-extra" title="Remove extra" onclick={move |_| inv_cont.delete_extra(id.clone())}>{"✕"}</button>)
+    });
+    html!(<tr key={entry.item.clone().unwrap_or_else(|| entry.name.clone())}>
+        <td class="name">{entry.name.clone()}<div class="reasons">{reasons(entry)}</div></td>
+        <td class="stock">{quantity}</td>
+        <td>{for removals}</td>
+    </tr>)
+}
 
 #[function_component]
-pub fn ShoppingList() -> Html {
+fn ShoppingCategoryView(props: &ShoppingCategoryViewProps) -> Html {
     let inv_cont = use_context::<InvCont>().expect("no ctx found");
-    let items = &inv_cont.state.items;
-    let mut category_map: BTreeMap<String, Vec<Item>> = BTreeMap::new();
-    for item in items {
-        if item.desired_stock == 0 || item.stock >= item.desired_stock {
-            continue
-        }
-        let cat_fetch = category_map.get_mut(&item.category);
-        match cat_fetch {
-            Some(cat) => {cat.push(item.clone());},
-            None => {category_map.insert(item.category.clone(), vec![item.clone()]);},
+    let name = &props.category.name;
+    let category = inv_cont.state.category(name);
+    let style = category.and_then(|category| category.colour.as_ref()).map(|colour| format!("border-top: 4px solid {}", colour));
+    let heading = match category.and_then(|category| category.icon.as_ref()) {
+        Some(icon) => format!("{} {}", icon, name),
+        None => name.clone(),
+    };
+
+    let rows = props.category.entries.iter().map(|entry| entry_row(&inv_cont, entry));
+
+    html!(<td class="category" key={name.clone()} style={style}>
+        <h3>{heading}</h3>
+        <hr />
+        <table class="item-table">
+            {for rows}
+        </table>
+    </td>)
+}
+
+#[derive(Properties, PartialEq)]
+struct ShoppingStoreViewProps {
+    /// `None` for the entries that aren't bought anywhere in particular
+    store: Option<Store>,
+    /// In the order they're walked past
+    entries: Vec<ShoppingEntry>
+}
+
+#[function_component]
+fn ShoppingStoreView(props: &ShoppingStoreViewProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name = props.store.as_ref().map_or("No store", |store| store.name.as_str());
+
+    let mut rows: Vec<Html> = vec![];
+    let mut aisle: Option<&str> = None;
+    for (i, entry) in props.entries.iter().enumerate() {
+        if props.store.is_some() && (i == 0 || entry.aisle.as_deref() != aisle) {
+            aisle = entry.aisle.as_deref();
+            let heading = aisle.unwrap_or("Anywhere").to_owned();
+            rows.push(html!(<tr key={format!("aisle {}", heading)}><th class="aisle" colspan="3">{heading}</th></tr>));
         }
+        rows.push(entry_row(&inv_cont, entry));
     }
-    // let categories: Html = category_map
-    //     .iter()
-    //     .map(|(name, items)| html!(<ItemCategory name={name.clone()} items={items.clone()} />))
-    //     .collect();
-
-    let mut categories: Vec<Html> = vec![];
-    for (name, cat_items) in &category_map {
-        categories.push(html!(
-            <ItemCategory name={name.clone()} items={cat_items.clone()} />
-        ));
+
+    html!(<td class="category" key={name.to_owned()}>
+        <h3>{name}</h3>
+        <hr />
+        <table class="item-table">
+            {rows}
+        </table>
+    </td>)
+}
+
+/// Splits the list by where things are bought, stores in their order with `None` last. A store's
+/// entries go in aisle order, then by name.
+fn by_store(list: &List, stores: &[Store]) -> Vec<(Option<Store>, Vec<ShoppingEntry>)> {
+    let mut groups: Vec<(Option<Store>, Vec<ShoppingEntry>)> = stores.iter().map(|store| (Some(store.clone()), vec![])).chain([(None, vec![])]).collect();
+    for entry in list.categories.iter().flat_map(|category| category.entries.iter()) {
+        let index = entry.store.as_deref()
+            .and_then(|id| stores.iter().position(|store| store.id == id))
+            .unwrap_or(stores.len());
+        groups[index].1.push(entry.clone());
     }
+    for (store, entries) in groups.iter_mut() {
+        match store {
+            Some(store) => entries.sort_by(|a, b| {
+                (store.aisle_position(a.aisle.as_deref()), &a.aisle, &a.name).cmp(&(store.aisle_position(b.aisle.as_deref()), &b.aisle, &b.name))
+            }),
+            None => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+    groups.retain(|(_, entries)| !entries.is_empty());
+    groups
+}
+
+/// The api's shopping list: what's short of desired stock, what the week's planned meals need, and
+/// anything added by hand.
+#[function_component]
+pub fn ShoppingList() -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_ref = use_node_ref();
+    let quantity_ref = use_node_ref();
+    let store_mode = use_state(|| false);
+
+    let add = {
+        let inv_cont = inv_cont.clone();
+        let (name_ref, quantity_ref) = (name_ref.clone(), quantity_ref.clone());
+        Callback::from(move |_| {
+            let (Some(name_node), Some(quantity_node)) = (name_ref.cast::<HtmlInputElement>(), quantity_ref.cast::<HtmlInputElement>()) else { return };
+            let name = name_node.value().trim().to_owned();
+            if name.is_empty() {
+                inv_cont.message.dispatch(error_message("Say what to add to the shopping list".into()));
+                return;
+            }
+            let quantity = quantity_node.value().parse::<f64>().ok().filter(|quantity| *quantity > 0.0);
+            // A name that's an item adds to that item, anything else is listed as it is
+            let extra = match inv_cont.state.inventory.name_to_id.get(&AttrValue::from(name.clone())) {
+                Some(id) => ExtraInput { item: Some(id.to_string()), quantity, ..Default::default() },
+                None => ExtraInput { name: Some(name), quantity, ..Default::default() },
+            };
+            inv_cont.add_extra(extra);
+            name_node.set_value("");
+            quantity_node.set_value("");
+        })
+    };
+
+    let categories: Vec<Html> = match *store_mode {
+        true => by_store(&inv_cont.state.shopping, &inv_cont.state.stores).into_iter()
+            .map(|(store, entries)| {
+                let key = store.as_ref().map(|store| store.id.clone()).unwrap_or_default();
+                html!(<ShoppingStoreView key={key} store={store} entries={entries} />)
+            })
+            .collect(),
+        false => inv_cont.state.shopping.categories.iter()
+            .map(|category| html!(<ShoppingCategoryView key={category.name.clone()} category={category.clone()} />))
+            .collect(),
+    };
+    let set_mode = |by_store: bool| {
+        let store_mode = store_mode.clone();
+        Callback::from(move |_| store_mode.set(by_store))
+    };
 
     let mut rows: Vec<Html> = vec![];
     let mut row: Vec<Html> = vec![];
@@ -46,7 +181,22 @@ pub fn ShoppingList() -> Html {
 
     html!(
         <div id="item-list">
+            <div class="shopping-extra">
+                <input type="text" placeholder="Add to the list" list="shopping-items" ref={name_ref}/>
+                <datalist id="shopping-items">
+                    {for inv_cont.state.items.iter().map(|item| html!(<option value={item.name.clone()} />))}
+                </datalist>
+                <input type="number" size="5" min="0" step="any" placeholder="1" ref={quantity_ref}/>
+                <button onclick={add}>{"Add"}</button>
+                <span class="shopping-mode">
+                    <button disabled={!*store_mode} onclick={set_mode(false)}>{"By category"}</button>
+                    <button disabled={*store_mode} onclick={set_mode(true)}>{"By store"}</button>
+                </span>
+            </div>
+            if rows.is_empty() {
+                <p>{"Nothing to buy."}</p>
+            }
             {rows}
         </div>
     )
-}
\ No newline at end of file
+}
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::{items_api, models::{format_quantity, Trip, TripLine, TripLineChange}, InvCont};
+
+pub enum ShoppingTripMsg {
+    Load,
+    Loaded(Option<Trip>, Vec<Trip>),
+    Start,
+    Started(Trip),
+    /// Checks a line off, or back on when it already is
+    Toggle(AttrValue),
+    /// Saves a changed bought quantity or price of a line that's checked off
+    ChangeBought(AttrValue),
+    LineSaved(TripLine),
+    Finish,
+    Abandon
+}
+
+/// Shopping with the list in hand: start a trip from the shopping list, check things off with what was
+/// actually bought, then finish it to restock everything at once. Progress is saved as it's made, so a
+/// trip can be picked up on another device.
+pub struct ShoppingTripTab {
+    trip: Option<Trip>,
+    past: Vec<Trip>,
+    loaded: bool,
+    store_node: NodeRef,
+    bought_nodes: BTreeMap<AttrValue, NodeRef>,
+    price_nodes: BTreeMap<AttrValue, NodeRef>
+}
+
+impl Component for ShoppingTripTab {
+    type Message = ShoppingTripMsg;
+
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ShoppingTripMsg::Load);
+        Self { trip: None, past: vec![], loaded: false, store_node: NodeRef::default(), bought_nodes: BTreeMap::new(), price_nodes: BTreeMap::new() }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+        let reload = ctx.link().callback(|_| ShoppingTripMsg::Load);
+
+        match msg {
+            ShoppingTripMsg::Load => {
+                let link = ctx.link().clone();
+                ctx.link().send_future_batch(async move {
+                    let loaded = match items_api::fetch_current_trip().await {
+                        Ok(trip) => items_api::fetch_trips().await.map(|past| (trip, past)),
+                        Err(e) => Err(e),
+                    };
+                    match loaded {
+                        Ok((trip, past)) => vec![ShoppingTripMsg::Loaded(trip, past)],
+                        Err(e) => {
+                            controller.report_error(e, move || link.send_message(ShoppingTripMsg::Load));
+                            vec![]
+                        }
+                    }
+                });
+                return false;
+            },
+            ShoppingTripMsg::Loaded(trip, past) => {
+                self.set_trip(trip);
+                self.past = past;
+                self.loaded = true;
+            },
+            ShoppingTripMsg::Start => {
+                let store = self.store_node.cast::<HtmlSelectElement>().map(|node| node.value()).filter(|store| !store.is_empty());
+                controller.start_trip(store, ctx.link().callback(ShoppingTripMsg::Started));
+                return false;
+            },
+            ShoppingTripMsg::Started(trip) => self.set_trip(Some(trip)),
+            ShoppingTripMsg::Toggle(id) => {
+                let Some(line) = self.line(&id) else { return false };
+                let change = TripLineChange { checked: !line.checked, bought: self.bought(&id), price: self.price(&id) };
+                self.save_line(&controller, ctx, id, change);
+                return false;
+            },
+            ShoppingTripMsg::ChangeBought(id) => {
+                let Some(line) = self.line(&id) else { return false };
+                if !line.checked {
+                    return false;
+                }
+                let change = TripLineChange { checked: true, bought: self.bought(&id), price: self.price(&id) };
+                self.save_line(&controller, ctx, id, change);
+                return false;
+            },
+            ShoppingTripMsg::LineSaved(saved) => {
+                let Some(trip) = self.trip.as_mut() else { return false };
+                if let Some(line) = trip.lines.iter_mut().find(|line| line.id == saved.id) {
+                    *line = saved;
+                }
+            },
+            ShoppingTripMsg::Finish => {
+                let Some(trip) = self.trip.as_ref() else { return false };
+                controller.finish_trip(trip.id.clone(), reload);
+                return false;
+            },
+            ShoppingTripMsg::Abandon => {
+                let Some(trip) = self.trip.as_ref() else { return false };
+                controller.abandon_trip(trip.id.clone(), reload);
+                return false;
+            },
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let (controller, _) = ctx.link().context::<InvCont>(Callback::noop()).expect("no ctx found");
+
+        let content = match (&self.trip, self.loaded) {
+            (_, false) => html!(<p>{"Loading..."}</p>),
+            (Some(trip), true) => self.view_trip(ctx, trip),
+            (None, true) => html!(<>
+                <div class="trip-start">
+                    <select ref={&self.store_node}>
+                        <option value="">{"Any store"}</option>
+                        {for controller.state.stores.iter().map(|store| html!(<option value={store.id.clone()}>{store.name.clone()}</option>))}
+                    </select>
+                    <button onclick={ctx.link().callback(|_| ShoppingTripMsg::Start)}>{"Start trip"}</button>
+                </div>
+                <h3>{"Past trips"}</h3>
+                if self.past.is_empty() {
+                    <p>{"No trips yet."}</p>
+                }
+                <table class="past-trips">
+                    {for self.past.iter().map(|trip| {
+                        let bought = trip.lines.iter().filter(|line| line.checked).count();
+                        html!(<tr key={trip.id.clone()}>
+                            <td>{trip.finished.unwrap_or(trip.started).with_timezone(&chrono::Local).format("%a %d %b %Y").to_string()}</td>
+                            <td>{trip.store_name.clone().unwrap_or_else(|| "Any store".into())}</td>
+                            <td>{format!("{} of {} bought", bought, trip.lines.len())}</td>
+                        </tr>)
+                    })}
+                </table>
+            </>),
+        };
+
+        html!(<div id="shopping-trip-tab">
+        <div class="container">
+            {content}
+        </div>
+        </div>)
+    }
+}
+
+impl ShoppingTripTab {
+    fn set_trip(&mut self, trip: Option<Trip>) {
+        self.bought_nodes = trip.iter()
+            .flat_map(|trip| trip.lines.iter())
+            .map(|line| (AttrValue::from(line.id.clone()), NodeRef::default()))
+            .collect();
+        self.price_nodes = self.bought_nodes.keys().map(|id| (id.clone(), NodeRef::default())).collect();
+        self.trip = trip;
+    }
+
+    fn line(&self, id: &AttrValue) -> Option<&TripLine> {
+        self.trip.as_ref()?.lines.iter().find(|line| line.id == id.as_str())
+    }
+
+    /// The bought input of a line, with a blank one meaning the line's quantity.
+    fn bought(&self, id: &AttrValue) -> Option<f64> {
+        let node = self.bought_nodes.get(id)?.cast::<HtmlInputElement>()?;
+        node.value().parse::<f64>().ok().filter(|bought| *bought >= 0.0)
+    }
+
+    /// The price input of a line, with a blank one meaning no price is recorded.
+    fn price(&self, id: &AttrValue) -> Option<f64> {
+        let node = self.price_nodes.get(id)?.cast::<HtmlInputElement>()?;
+        node.value().parse::<f64>().ok().filter(|price| *price >= 0.0)
+    }
+
+    fn save_line(&self, controller: &InvCont, ctx: &Context<Self>, id: AttrValue, change: TripLineChange) {
+        let Some(trip) = self.trip.as_ref() else { return };
+        controller.check_trip_line(trip.id.clone(), id.to_string(), change, ctx.link().callback(ShoppingTripMsg::LineSaved));
+    }
+
+    fn view_trip(&self, ctx: &Context<Self>, trip: &Trip) -> Html {
+        let checked = trip.lines.iter().filter(|line| line.checked).count();
+
+        let mut rows: Vec<Html> = vec![];
+        let mut aisle: Option<&str> = None;
+        for (i, line) in trip.lines.iter().enumerate() {
+            if trip.store.is_some() && (i == 0 || line.aisle.as_deref() != aisle) {
+                aisle = line.aisle.as_deref();
+                let heading = aisle.unwrap_or("Anywhere").to_owned();
+                rows.push(html!(<tr key={format!("aisle {}", heading)}><th class="aisle" colspan="4">{heading}</th></tr>));
+            }
+            let id = AttrValue::from(line.id.clone());
+            let (toggle_id, bought_id, price_id) = (id.clone(), id.clone(), id.clone());
+            let quantity = line.quantity.map(|quantity| format_quantity(quantity, &line.unit)).unwrap_or_default();
+            rows.push(html!(<tr key={line.id.clone()} class={classes!("trip-line", line.checked.then_some("checked"))}>
+                <td><input type="checkbox" checked={line.checked} onclick={ctx.link().callback(move |_| ShoppingTripMsg::Toggle(toggle_id.clone()))}/></td>
+                <td class="name">{line.name.clone()}<div class="reasons">{quantity}</div></td>
+                <td>
+                    <input type="number" size="5" min="0" step="any" inputmode="decimal"
+                        placeholder={line.quantity.map(|quantity| quantity.to_string()).unwrap_or_default()}
+                        value={line.bought.map(|bought| bought.to_string()).unwrap_or_default()}
+                        ref={&self.bought_nodes[&id]}
+                        onchange={ctx.link().callback(move |_| ShoppingTripMsg::ChangeBought(bought_id.clone()))}/>
+                </td>
+                <td class="price">
+                    <input type="number" size="6" min="0" step="0.01" inputmode="decimal" placeholder="Price" title="Price paid"
+                        value={line.price.map(|price| price.to_string()).unwrap_or_default()}
+                        ref={&self.price_nodes[&id]}
+                        onchange={ctx.link().callback(move |_| ShoppingTripMsg::ChangeBought(price_id.clone()))}/>
+                </td>
+            </tr>));
+        }
+
+        html!(<>
+            <div class="trip-header">
+                <h3>{format!("Shopping at {}", trip.store_name.as_deref().unwrap_or("any store"))}</h3>
+                <span>{format!("{} of {} in the basket", checked, trip.lines.len())}</span>
+            </div>
+            <table class="trip-lines">
+                {rows}
+            </table>
+            <div class="trip-actions">
+                <button onclick={ctx.link().callback(|_| ShoppingTripMsg::Finish)}>{"Finish trip"}</button>
+                <button onclick={ctx.link().callback(|_| ShoppingTripMsg::Abandon)}>{"Abandon trip"}</button>
+            </div>
+        </>)
+    }
+}
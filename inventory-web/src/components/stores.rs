@@ -0,0 +1,90 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::{error_message, models::{Store, StoreInput}, InvCont};
+
+/// Reads a store's name and its comma separated aisles, in the order they're walked.
+fn store_input(name_ref: &NodeRef, aisles_ref: &NodeRef) -> Option<StoreInput> {
+    let name = name_ref.cast::<HtmlInputElement>()?.value().trim().to_owned();
+    let aisles = aisles_ref.cast::<HtmlInputElement>()?.value()
+        .split(',')
+        .map(str::trim)
+        .filter(|aisle| !aisle.is_empty())
+        .map(str::to_owned)
+        .collect();
+    (!name.is_empty()).then_some(StoreInput { name, aisles })
+}
+
+#[derive(Properties, PartialEq)]
+struct StoreRowProps {
+    store: Store
+}
+
+#[function_component]
+fn StoreRow(props: &StoreRowProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_ref = use_node_ref();
+    let aisles_ref = use_node_ref();
+    let store = &props.store;
+
+    let save = {
+        let inv_cont = inv_cont.clone();
+        let (name_ref, aisles_ref) = (name_ref.clone(), aisles_ref.clone());
+        let id = store.id.clone();
+        Callback::from(move |_| match store_input(&name_ref, &aisles_ref) {
+            Some(input) => inv_cont.update_store(id.clone(), input),
+            None => inv_cont.message.dispatch(error_message("A store name is required".into())),
+        })
+    };
+    let delete = {
+        let inv_cont = inv_cont.clone();
+        let id = store.id.clone();
+        Callback::from(move |_| inv_cont.delete_store(id.clone()))
+    };
+
+    html!(<tr key={store.id.clone()}>
+        <td><input type="text" value={store.name.clone()} ref={name_ref}/></td>
+        <td><input type="text" class="aisles" value={store.aisles.join(", ")} ref={aisles_ref}/></td>
+        <td><button onclick={save}>{"Save"}</button></td>
+        <td><button onclick={delete}>{"Delete"}</button></td>
+    </tr>)
+}
+
+/// Create, rename and delete stores, and set the order their aisles are walked in. The shopping list
+/// can be split by store and sorted in that order.
+#[function_component]
+pub fn StoreManager() -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let name_ref = use_node_ref();
+    let aisles_ref = use_node_ref();
+
+    let add = {
+        let inv_cont = inv_cont.clone();
+        let (name_ref, aisles_ref) = (name_ref.clone(), aisles_ref.clone());
+        Callback::from(move |_| {
+            let Some(input) = store_input(&name_ref, &aisles_ref) else {
+                inv_cont.message.dispatch(error_message("A store name is required".into()));
+                return;
+            };
+            inv_cont.create_store(input);
+            for node in [&name_ref, &aisles_ref] {
+                if let Some(input) = node.cast::<HtmlInputElement>() {
+                    input.set_value("");
+                }
+            }
+        })
+    };
+
+    html!(<div id="store-manager">
+        <h3>{"Stores"}</h3>
+        <table>
+            <tr><th>{"Name"}</th><th>{"Aisles, in walking order"}</th></tr>
+            {for inv_cont.state.stores.iter().map(|store| html!(<StoreRow key={store.id.clone()} store={store.clone()} />))}
+            <tr>
+                <td><input type="text" placeholder="New store" ref={name_ref}/></td>
+                <td><input type="text" class="aisles" placeholder="Produce, Bakery, Dairy" ref={aisles_ref}/></td>
+                <td><button onclick={add}>{"Add"}</button></td>
+            </tr>
+        </table>
+    </div>)
+}
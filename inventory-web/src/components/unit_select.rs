@@ -0,0 +1,57 @@
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::{models::{format_quantity, Package, DEFAULT_UNIT}, InvCont};
+
+/// Marks the options of a [`UnitSelect`] that are packages rather than units.
+const PACKAGE_PREFIX: &str = "package:";
+
+#[derive(Properties, PartialEq)]
+pub struct UnitSelectProps {
+    /// The unit the item is kept in
+    pub unit: String,
+    /// The unit picked to start with, when it isn't the item's own
+    #[prop_or_default]
+    pub selected: Option<String>,
+    /// The item's packages, offered after the units
+    #[prop_or_default]
+    pub packages: Vec<Package>,
+    pub node: NodeRef
+}
+
+/// Picks what a quantity is entered in: any unit the api can convert into the item's, or one of the
+/// item's packages. When there's no choice, e.g. before the unit table has loaded, the item's unit is just shown.
+#[function_component]
+pub fn UnitSelect(props: &UnitSelectProps) -> Html {
+    let inv_cont = use_context::<InvCont>().expect("no ctx found");
+    let units = inv_cont.state.compatible_units(&props.unit);
+    if units.len() + props.packages.len() < 2 {
+        let label = match props.unit.as_str() {
+            DEFAULT_UNIT => String::new(),
+            unit => unit.to_owned(),
+        };
+        return html!(<span class="unit">{label}</span>);
+    }
+    let selected = props.selected.as_deref().unwrap_or(&props.unit);
+    html!(<select class="unit" ref={props.node.clone()}>
+        {for units.iter().map(|unit| html!(<option value={unit.name.clone()} selected={unit.name == selected}>{unit.name.clone()}</option>))}
+        {for props.packages.iter().map(|package| html!(
+            <option value={format!("{}{}", PACKAGE_PREFIX, package.name)}>{format!("{} ({})", package.name, format_quantity(package.size, &props.unit))}</option>
+        ))}
+    </select>)
+}
+
+fn selected(node: &NodeRef) -> Option<String> {
+    let selected = node.cast::<HtmlSelectElement>()?.value();
+    (!selected.is_empty()).then_some(selected)
+}
+
+/// The unit picked in a [`UnitSelect`], or `None` when it's the item's own or a package was picked.
+pub fn selected_unit(node: &NodeRef, unit: &str) -> Option<String> {
+    selected(node).filter(|selected| !selected.starts_with(PACKAGE_PREFIX) && selected != unit)
+}
+
+/// The package picked in a [`UnitSelect`], if one was.
+pub fn selected_package(node: &NodeRef) -> Option<String> {
+    selected(node)?.strip_prefix(PACKAGE_PREFIX).map(str::to_owned)
+}
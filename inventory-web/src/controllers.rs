@@ -1,7 +1,7 @@
 use log::info;
-use yew::{Callback, UseReducerHandle};
+use yew::{AttrValue, Callback, UseReducerHandle};
 
-use crate::{error_message, items_api, models::{Item, RestockItem}, state::{ItemAction, ItemsState}, success_message, MessageContainer, MessageContainerAction};
+use crate::{items_api::{self, ApiError}, models::{format_quantity, per_unit, BatchResult, BudgetInput, CategoryInput, ExtraInput, Item, ItemStoreInput, Package, PlannedMealInput, RecipeInput, RestockItem, StoreInput, Trip, TripLine, TripLineChange}, state::{ItemAction, ItemsState}, success_message, MessageContainer, MessageContainerAction};
 
 #[derive(Clone, PartialEq)]
 pub struct InventoryController {
@@ -26,19 +26,33 @@ impl InventoryController {
         wasm_bindgen_futures::spawn_local(async move {
             let response = items_api::fetch_items().await;
             match response {
-                Ok(fetched_items) => items.dispatch(ItemAction::Set(fetched_items)),
-                Err(_) => {
+                Ok(fetched_items) => {
+                    items.dispatch(ItemAction::Set(fetched_items));
+                    inv_conv.init_categories();
+                    inv_conv.init_units();
+                    inv_conv.init_recipes();
+                    inv_conv.init_shopping();
+                    inv_conv.init_stores();
+                    inv_conv.init_budgets();
+                },
+                Err(e @ ApiError::Network(_)) => {
+                    info!("{}", e);
                     // let inv_conv = std::rc::Rc::new(self.clone());
                     message.dispatch(MessageContainerAction::Change { 
                         name: "Error contacting database".into(),
                         message: "This web interface has successfully loaded, but can not make contact with the database.\n
                         There could be a number of reasons for this, but the most likely one is that the database failed to start or recently crashed.\n
-                        Please ensure the database is running before pressing retry or reloading this page.".into(),
+                        Please ensure the database is running before pressing retry or reloading this page.\n
+                        If the database has moved, its address can be changed from the Settings tab.".into(),
                         additional_actions: Some(vec![
                             (Callback::from(move |_| inv_conv.init_items()), "Retry database connection".into())
                         ])
                     });
                 },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_items());
+                },
             };
             info!("Init completed");
         });
@@ -49,32 +63,40 @@ impl InventoryController {
         // });
     }
 
-    pub fn new_item(&self, name: String, category: String) {
+    pub fn new_item(&self, name: String, category: String, unit: String) {
         let items = self.state.clone();
         let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::new_item(&name, &category).await;
+            let response = items_api::new_item(&name, &category, &unit).await;
             match response {
                 Ok(item) => {
                     message.dispatch(success_message(format!("Item {} added successfully", item.name)));
                     items.dispatch(ItemAction::Add(item));
                 },
-                Err(e) => message.dispatch(error_message(e.to_string())),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.new_item(name.clone(), category.clone(), unit.clone()));
+                },
             }
         });
     }
 
-    pub fn add_full_item(&self, name: String, category: String, stock: i64, desired_stock: i64, track_generally: bool) {
+    pub fn add_full_item(&self, name: String, category: String, stock: f64, desired_stock: f64, unit: String, track_generally: bool) {
         let items = self.state.clone();
         let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::add_full_item(&name, &category, stock, desired_stock, track_generally).await;
+            let response = items_api::add_full_item(&name, &category, stock, desired_stock, &unit, track_generally).await;
             match response {
                 Ok(item) => {
                     message.dispatch(success_message(format!("Full item {} added successfully", item.name)));
                     items.dispatch(ItemAction::Add(item));
                 },
-                Err(e) => message.dispatch(error_message(e.to_string())),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.add_full_item(name.clone(), category.clone(), stock, desired_stock, unit.clone(), track_generally));
+                },
             }
         });
     }
@@ -83,18 +105,137 @@ impl InventoryController {
         let message = self.message.clone();
         let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::change_item(&id, item).await;
+            let response = items_api::change_item(&id, item.clone()).await;
             match response {
                 Ok(item) => {
                     message.dispatch(success_message(format!(
-                        "Item {} changed successfully. Should now look like: category: {}; stock: {}; desired_stock: {}; track_generally: {};", 
-                        item.name, item.category, item.stock, item.desired_stock, item.track_general)));
+                        "Item {} changed successfully. Should now look like: category: {}; stock: {}; desired_stock: {}; unit: {}; track_generally: {};", 
+                        item.name, item.category, item.stock, item.desired_stock, item.unit, item.track_general)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.change_item(id.clone(), item.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn set_packages(&self, id: String, packages: Vec<Package>) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::set_packages(&id, packages.clone()).await {
+                Ok(item) => {
+                    message.dispatch(success_message(format!("Packages of {} saved", item.name)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.set_packages(id.clone(), packages.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn set_item_store(&self, id: String, store: ItemStoreInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::set_item_store(&id, store.clone()).await {
+                Ok(item) => {
+                    message.dispatch(success_message(format!("Store of {} saved", item.name)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.set_item_store(id.clone(), store.clone()));
+                },
+            }
+        });
+    }
+
+    /// Attaches `code` to item `id`, then hands the updated item to `on_added`.
+    pub fn add_barcode(&self, id: String, code: String, on_added: Callback<Item>) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::add_barcode(&id, &code).await {
+                Ok(item) => {
+                    message.dispatch(success_message(format!("Barcode {} now finds {}", code, item.name)));
+                    inv_conv.init_items();
+                    on_added.emit(item);
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.add_barcode(id.clone(), code.clone(), on_added.clone()));
+                },
+            }
+        });
+    }
+
+    /// Asks the api for the item a scanned barcode belongs to when the items as last loaded don't have it,
+    /// as when it was linked on another device. Hands it to `on_found`, or the code to `on_unknown` if no item has it.
+    pub fn find_barcode(&self, code: String, on_found: Callback<Item>, on_unknown: Callback<String>) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_item_by_barcode(&code).await {
+                Ok(item) => {
                     inv_conv.init_items();
+                    on_found.emit(item);
+                },
+                Err(ApiError::NotFound(_)) => on_unknown.emit(code),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.find_barcode(code.clone(), on_found.clone(), on_unknown.clone()));
                 },
+            }
+        });
+    }
+
+    /// Creates an item for a barcode no item has yet, then hands it to `on_added`.
+    pub fn new_item_with_barcode(&self, name: String, category: String, unit: String, code: String, on_added: Callback<Item>) {
+        let items = self.state.clone();
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            let created = match items_api::new_item(&name, &category, &unit).await {
+                Ok(created) => created,
                 Err(e) => {
-                    message.dispatch(error_message(e.to_string()));
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.new_item_with_barcode(name.clone(), category.clone(), unit.clone(), code.clone(), on_added.clone()));
                     return;
                 },
+            };
+            match items_api::add_barcode(&created.id, &code).await {
+                Ok(item) => {
+                    message.dispatch(success_message(format!("Item {} added with barcode {}", item.name, code)));
+                    items.dispatch(ItemAction::Add(item.clone()));
+                    on_added.emit(item);
+                },
+                // The item exists by now, so only the barcode is tried again
+                Err(e) => {
+                    items.dispatch(ItemAction::Add(created.clone()));
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.add_barcode(created.id.clone(), code.clone(), on_added.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn remove_barcode(&self, id: String, code: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::remove_barcode(&id, &code).await {
+                Ok(item) => {
+                    message.dispatch(success_message(format!("Barcode {} removed from {}", code, item.name)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.remove_barcode(id.clone(), code.clone()));
+                },
             }
         });
     }
@@ -103,15 +244,15 @@ impl InventoryController {
         let message = self.message.clone();
         let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::change_items(items).await;
+            let response = items_api::change_items(items.clone()).await;
             match response {
-                Ok(rows) => {
-                    message.dispatch(success_message(format!("{} items were successfully changed", rows.rows_affected)));
+                Ok(result) => {
+                    message.dispatch(inv_conv.batch_message("changed", &result));
                     inv_conv.init_items();
                 },
                 Err(e) => {
-                    message.dispatch(error_message(e.to_string()));
-                    return;
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.change_items(items.clone()));
                 },
             }
         });
@@ -128,26 +269,29 @@ impl InventoryController {
                     inv_conv.init_items();
                 },
                 Err(e) => {
-                    message.dispatch(error_message(e.to_string()));
-                    return;
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_item(id.clone()));
                 },
             }
         });
     }
 
+    /// Restocks a Grocery Bag or Dinner List submission. When it paid for anything and budgets are set,
+    /// the message also warns about budgets it went over.
     pub fn restock_items(&self, restock: Vec<RestockItem>) {
         let message = self.message.clone();
         let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::restock_items(restock).await;
+            let response = items_api::restock_items(restock.clone()).await;
             match response {
-                Ok(rows) => {
-                    message.dispatch(success_message(format!("{} items were successfully restocked", rows.rows_affected)));
+                Ok(result) => {
+                    let done = inv_conv.batch_message("restocked", &result);
+                    message.dispatch(inv_conv.budget_warning(done, &result));
                     inv_conv.init_items();
                 },
                 Err(e) => {
-                    message.dispatch(error_message(e.to_string()));
-                    return;
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.restock_items(restock.clone()));
                 },
             }
         });
@@ -157,21 +301,583 @@ impl InventoryController {
         let message = self.message.clone();
         let inv_conv = std::rc::Rc::new(self.clone());
         wasm_bindgen_futures::spawn_local(async move {
-            let response = items_api::consume_items(consume).await;
+            let response = items_api::consume_items(consume.clone()).await;
             match response {
-                Ok(rows) => {
-                    message.dispatch(success_message(format!("{} items were successfully consumed", rows.rows_affected)));
+                Ok(result) => {
+                    message.dispatch(inv_conv.batch_message("consumed", &result));
                     inv_conv.init_items();
                 },
                 Err(e) => {
-                    message.dispatch(error_message(e.to_string()));
-                    return;
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.consume_items(consume.clone()));
                 },
             }
         });
         self.init_items();
     }
 
+    pub fn init_categories(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_categories().await {
+                Ok(categories) => items.dispatch(ItemAction::SetCategories(categories)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_categories());
+                },
+            }
+        });
+    }
+
+    pub fn init_units(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_units().await {
+                Ok(units) => items.dispatch(ItemAction::SetUnits(units)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_units());
+                },
+            }
+        });
+    }
+
+    pub fn init_recipes(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_recipes().await {
+                Ok(recipes) => items.dispatch(ItemAction::SetRecipes(recipes)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_recipes());
+                },
+            }
+        });
+    }
+
+    pub fn create_recipe(&self, recipe: RecipeInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::create_recipe(recipe.clone()).await {
+                Ok(created) => {
+                    message.dispatch(success_message(format!("Recipe {} added", created.name)));
+                    inv_conv.init_recipes();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.create_recipe(recipe.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn update_recipe(&self, id: String, recipe: RecipeInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::update_recipe(&id, recipe.clone()).await {
+                Ok(updated) => {
+                    message.dispatch(success_message(format!("Recipe {} saved", updated.name)));
+                    inv_conv.init_recipes();
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.update_recipe(id.clone(), recipe.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn delete_recipe(&self, id: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_recipe(&id).await {
+                Ok(_) => {
+                    message.dispatch(success_message("Recipe deleted".into()));
+                    inv_conv.init_recipes();
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_recipe(id.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn cook_recipe(&self, id: String, servings: f64) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::cook_recipe(&id, servings).await {
+                Ok(result) => {
+                    message.dispatch(inv_conv.batch_message("consumed", &result));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.cook_recipe(id.clone(), servings));
+                },
+            }
+        });
+    }
+
+    pub fn init_shopping(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            // The api's day can differ from ours around midnight
+            let today = chrono::Local::now().date_naive();
+            match items_api::fetch_shopping_list(today).await {
+                Ok(list) => items.dispatch(ItemAction::SetShopping(list)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_shopping());
+                },
+            }
+        });
+    }
+
+    pub fn add_extra(&self, extra: ExtraInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::add_extra(extra.clone()).await {
+                Ok(added) => {
+                    message.dispatch(success_message(format!("{} added to the shopping list", added.name)));
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.add_extra(extra.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn delete_extra(&self, id: String) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_extra(&id).await {
+                Ok(_) => inv_conv.init_shopping(),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_extra(id.clone()));
+                },
+            }
+        });
+    }
+
+    /// Starts a shopping trip from the shopping list, handing it to `on_done`.
+    pub fn start_trip(&self, store: Option<String>, on_done: Callback<Trip>) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::start_trip(store.clone()).await {
+                Ok(trip) => {
+                    message.dispatch(success_message(format!("Trip started with {} things to buy", trip.lines.len())));
+                    on_done.emit(trip);
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.start_trip(store.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    /// Saves a line being checked off, or back on, handing the saved line to `on_done`.
+    pub fn check_trip_line(&self, trip: String, line: String, change: TripLineChange, on_done: Callback<TripLine>) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::check_trip_line(&trip, &line, change.clone()).await {
+                Ok(line) => on_done.emit(line),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.check_trip_line(trip.clone(), line.clone(), change.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    /// Restocks everything checked off on a trip, then reloads the inventory and shopping list.
+    pub fn finish_trip(&self, id: String, on_done: Callback<()>) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::finish_trip(&id).await {
+                Ok(finished) => {
+                    message.dispatch(inv_conv.batch_message("restocked", &finished.result));
+                    on_done.emit(());
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.finish_trip(id.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn abandon_trip(&self, id: String, on_done: Callback<()>) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::abandon_trip(&id).await {
+                Ok(_) => {
+                    message.dispatch(success_message("The trip was abandoned, nothing was restocked".into()));
+                    on_done.emit(());
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.abandon_trip(id.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    /// Plans a meal, then lets `on_done` reload whatever shows the plan.
+    pub fn plan_meal(&self, meal: PlannedMealInput, on_done: Callback<()>) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::plan_meal(meal.clone()).await {
+                Ok(_) => {
+                    on_done.emit(());
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.plan_meal(meal.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn change_planned_servings(&self, id: String, servings: f64, on_done: Callback<()>) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::change_planned_servings(&id, servings).await {
+                Ok(_) => {
+                    on_done.emit(());
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.change_planned_servings(id.clone(), servings, on_done.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn delete_planned_meal(&self, id: String, on_done: Callback<()>) {
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_planned_meal(&id).await {
+                Ok(_) => {
+                    on_done.emit(());
+                    inv_conv.init_shopping();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_planned_meal(id.clone(), on_done.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn init_stores(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_stores().await {
+                Ok(stores) => items.dispatch(ItemAction::SetStores(stores)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_stores());
+                },
+            }
+        });
+    }
+
+    pub fn create_store(&self, store: StoreInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::create_store(store.clone()).await {
+                Ok(created) => {
+                    message.dispatch(success_message(format!("Store {} added", created.name)));
+                    inv_conv.init_stores();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.create_store(store.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn update_store(&self, id: String, store: StoreInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::update_store(&id, store.clone()).await {
+                Ok(updated) => {
+                    message.dispatch(success_message(format!("Store {} saved", updated.name)));
+                    inv_conv.init_stores();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.update_store(id.clone(), store.clone()));
+                },
+            }
+        });
+    }
+
+    /// Deleting a store takes its items off it, so items are reloaded too.
+    pub fn delete_store(&self, id: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_store(&id).await {
+                Ok(_) => {
+                    message.dispatch(success_message("Store deleted".into()));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_store(id.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn init_budgets(&self) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::fetch_budgets().await {
+                Ok(budgets) => items.dispatch(ItemAction::SetBudgets(budgets)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.init_budgets());
+                },
+            }
+        });
+    }
+
+    pub fn set_budget(&self, budget: BudgetInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::set_budget(budget.clone()).await {
+                Ok(set) => {
+                    let name = set.category_name.unwrap_or_else(|| "overall".into());
+                    message.dispatch(success_message(format!("The {} budget is now {:.2} a month", name, set.amount)));
+                    inv_conv.init_budgets();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.set_budget(budget.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn delete_budget(&self, id: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_budget(&id).await {
+                Ok(_) => {
+                    message.dispatch(success_message("Budget removed".into()));
+                    inv_conv.init_budgets();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_budget(id.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn create_category(&self, category: CategoryInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::create_category(category.clone()).await {
+                Ok(created) => {
+                    message.dispatch(success_message(format!("Category {} added successfully", created.name)));
+                    inv_conv.init_categories();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.create_category(category.clone()));
+                },
+            }
+        });
+    }
+
+    /// Renaming a category renames it on its items too, so items are reloaded along with the categories.
+    pub fn update_category(&self, id: String, category: CategoryInput) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::update_category(&id, category.clone()).await {
+                Ok(updated) => {
+                    message.dispatch(success_message(format!("Category {} changed successfully", updated.name)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.update_category(id.clone(), category.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn merge_category(&self, id: String, into: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::merge_category(&id, &into).await {
+                Ok(target) => {
+                    message.dispatch(success_message(format!("Category merged into {}", target.name)));
+                    inv_conv.init_items();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.merge_category(id.clone(), into.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn reorder_categories(&self, ids: Vec<String>) {
+        let items = self.state.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::reorder_categories(ids.clone()).await {
+                Ok(categories) => items.dispatch(ItemAction::SetCategories(categories)),
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.reorder_categories(ids.clone()));
+                },
+            }
+        });
+    }
+
+    pub fn delete_category(&self, id: String) {
+        let message = self.message.clone();
+        let inv_conv = std::rc::Rc::new(self.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            match items_api::delete_category(&id).await {
+                Ok(_rows) => {
+                    message.dispatch(success_message("A category was successfully deleted".into()));
+                    inv_conv.init_categories();
+                },
+                Err(e) => {
+                    let retry = inv_conv.clone();
+                    inv_conv.report_error(e, move || retry.delete_category(id.clone()));
+                },
+            }
+        });
+    }
+
+    /// Lists which entries of a Grocery Bag, Dinner List or ReInventory submission applied and which didn't.
+    fn batch_message(&self, verb: &str, result: &BatchResult) -> MessageContainerAction {
+        let id_map = &self.state.inventory.item_id_map;
+        let name_of = |id: &String| match id_map.get(&AttrValue::from(id.clone())) {
+            Some(item) => item.name.clone(),
+            None => id.clone(),
+        };
+        let unit_of = |id: &String| id_map.get(&AttrValue::from(id.clone())).map_or("count", |item| item.unit.as_str());
+
+        let mut msg = format!("{} items were successfully {}", result.applied.len(), verb);
+        for applied in result.applied.iter() {
+            msg += &format!("\n\t{}: {} in stock", applied.name, format_quantity(applied.stock, unit_of(&applied.id)));
+        }
+        if !result.clamped.is_empty() {
+            msg += "\nThere wasn't enough in stock for these, so only what was left was used:";
+            for clamped in result.clamped.iter() {
+                let unit = unit_of(&clamped.id);
+                msg += &format!("\n\t{}: asked for {}, used {}", name_of(&clamped.id), format_quantity(clamped.requested, unit), format_quantity(clamped.consumed, unit));
+            }
+        }
+        if !result.above_usual.is_empty() {
+            msg += "\nThese cost notably more than usual:";
+            for above in result.above_usual.iter() {
+                msg += &format!("\n\t{}: {:.2} {}, usually {:.2}", name_of(&above.id), above.unit_price, per_unit(&above.unit), above.usual);
+            }
+        }
+        if result.is_complete() {
+            return success_message(msg);
+        }
+        if !result.not_found.is_empty() {
+            msg += "\nThese items no longer exist and were skipped:";
+            for id in result.not_found.iter() {
+                msg += &format!("\n\t{}", name_of(id));
+            }
+        }
+        if !result.rejected.is_empty() {
+            msg += "\nThese entries were rejected:";
+            for rejected in result.rejected.iter() {
+                msg += &format!("\n\t{}: {}", name_of(&rejected.id), rejected.reason);
+            }
+        }
+        let inv_conv = std::rc::Rc::new(self.clone());
+        MessageContainerAction::Change {
+            name: "Some items were not applied".into(),
+            message: msg.into(),
+            additional_actions: Some(vec![
+                (Callback::from(move |_| inv_conv.init_items()), "Reload inventory".into())
+            ])
+        }
+    }
+
+    /// Adds the budgets a restock put over, as the api reports them, to its message. Otherwise the message is left as it was.
+    fn budget_warning(&self, done: MessageContainerAction, result: &BatchResult) -> MessageContainerAction {
+        let over: Vec<String> = result.over_budget.iter()
+            .map(|over| format!("\n\t{}: spent {:.2} of {:.2}", over.category_name.as_deref().unwrap_or("Overall"), over.spent, over.amount))
+            .collect();
+        if over.is_empty() {
+            return done;
+        }
+
+        let (msg, additional_actions) = match done {
+            MessageContainerAction::Change { message, additional_actions, .. } => (message, additional_actions),
+            MessageContainerAction::Make(container) => (container.message, container.additional_actions),
+        };
+        MessageContainerAction::Change {
+            name: "Over budget".into(),
+            message: format!("{}\nThis puts these budgets over for the month:{}", msg, over.concat()).into(),
+            additional_actions
+        }
+    }
+
+    /// Shows `error` in the message box. Errors that could go away on their own get a button
+    /// to run `retry`, and errors hinting that the inventory shown is stale get one to reload it.
+    pub fn report_error(&self, error: ApiError, retry: impl Fn() + 'static) {
+        let mut actions = vec![];
+        if error.is_retryable() {
+            actions.push((Callback::from(move |_| retry()), "Retry".into()));
+        }
+        if matches!(error, ApiError::NotFound(_) | ApiError::Conflict(_)) {
+            let inv_conv = std::rc::Rc::new(self.clone());
+            actions.push((Callback::from(move |_| inv_conv.init_items()), "Reload inventory".into()));
+        }
+        self.message.dispatch(MessageContainerAction::Change {
+            name: error.title().into(),
+            message: error.user_message().into(),
+            additional_actions: if actions.is_empty() { None } else { Some(actions) }
+        });
+    }
+
     // pub fn test_request(&self) {
     //     let items = self.state.clone();
     //     wasm_bindgen_futures::spawn_local(async move {
@@ -1,90 +1,220 @@
-use reqwasm::{http::Request, Error};
+use chrono::NaiveDate;
+use reqwasm::{http::{Request, Response}, Error};
+use serde::{de::DeserializeOwned, Deserialize};
+use web_sys::UrlSearchParams;
 
-use crate::models::*;
+use crate::{api_config::base_url, models::*};
 
-const BASE_URL: &str = "http://192.168.1.229:26530";
-// const BASE_URL: &str = "http://192.168.1.11:26530";
-// const BASE_URL: &str = "http://127.0.0.1:26530";
+/// Why a request to the api failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// The api couldn't be reached at all
+    Network(String),
+    /// 404, usually an item that has since been deleted
+    NotFound(String),
+    /// 400 or 422, the api refused what was sent
+    Validation(String),
+    /// 409, e.g. a store or recipe name that is already taken
+    Conflict(String),
+    /// 5xx, something went wrong on the api's side
+    Server(String),
+    /// The api answered, but not with anything we understand
+    Decode(String)
+} impl ApiError {
+    fn from_status(status: u16, message: String) -> Self {
+        match status {
+            404 => ApiError::NotFound(message),
+            409 => ApiError::Conflict(message),
+            400..=499 => ApiError::Validation(message),
+            _ => ApiError::Server(message),
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ApiError::Network(_) => "Error contacting database",
+            ApiError::NotFound(_) => "Item not found",
+            ApiError::Validation(_) => "Request rejected",
+            ApiError::Conflict(_) => "Conflict",
+            ApiError::Server(_) => "Database error",
+            ApiError::Decode(_) => "Unexpected response",
+        }
+    }
+
+    /// A message that makes sense to someone who isn't looking at the code.
+    pub fn user_message(&self) -> String {
+        match self {
+            ApiError::Network(e) => format!("Could not reach the database at {}. Check that it is running and that the address in the Settings tab is correct.\n({})", base_url(), e),
+            ApiError::NotFound(e) => format!("{}. It may have been changed or deleted from another device; reloading the inventory should fix this.", e),
+            ApiError::Validation(e) => format!("The database refused this change: {}", e),
+            ApiError::Conflict(e) => e.clone(),
+            ApiError::Server(e) => format!("The database ran into a problem and nothing was changed. Trying again may help.\n({})", e),
+            ApiError::Decode(e) => format!("The database responded, but the response could not be read. The web page and database may be different versions.\n({})", e),
+        }
+    }
+
+    /// Whether sending the exact same request again could succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ApiError::Network(_) | ApiError::Server(_))
+    }
+} impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Network(e) => write!(f, "Network error: {}", e),
+            ApiError::NotFound(e) => write!(f, "Not found: {}", e),
+            ApiError::Validation(e) => write!(f, "Invalid request: {}", e),
+            ApiError::Conflict(e) => write!(f, "Conflict: {}", e),
+            ApiError::Server(e) => write!(f, "Server error: {}", e),
+            ApiError::Decode(e) => write!(f, "Could not read response: {}", e),
+        }
+    }
+}
+
+/// The body inventory-api sends with every error response.
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String
+}
 
-pub async fn fetch_items() -> Result<Vec<Item>, Error> {
-    Request::get(&format!("{BASE_URL}/items"))
+/// Checks the status of a response before reading its body as `T`.
+async fn decode<T: DeserializeOwned>(response: Result<Response, Error>) -> Result<T, ApiError> {
+    let response = response.map_err(|e| ApiError::Network(e.to_string()))?;
+    if !response.ok() {
+        let status = response.status();
+        let message = match response.json::<ErrorBody>().await {
+            Ok(body) => body.message,
+            Err(_) => format!("The database responded with {} {}", status, response.status_text()),
+        };
+        return Err(ApiError::from_status(status, message));
+    }
+    response.json().await.map_err(|e| ApiError::Decode(e.to_string()))
+}
+
+/// Tells the api where stock changes came from, so its history can tell the web client apart from other tools.
+const SOURCE_HEADER: &str = "X-Inventory-Source";
+const SOURCE: &str = "web";
+
+pub async fn fetch_items() -> Result<Vec<Item>, ApiError> {
+    fetch_items_from(&base_url()).await
+}
+
+/// Fetches items from an api other than the current one, used to test a new address.
+pub async fn fetch_items_from(url: &str) -> Result<Vec<Item>, ApiError> {
+    let response = Request::get(&format!("{}/items", url))
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn new_item(name: &str, category: &str) -> Result<Item, Error> {
-    Request::post(&format!("{BASE_URL}/item"))
-        .body(format!("[\"{name}\", \"{category}\"]"))
+pub async fn new_item(name: &str, category: &str, unit: &str) -> Result<Item, ApiError> {
+    let response = Request::post(&format!("{}/item", base_url()))
+        .body(format!("[\"{name}\", \"{category}\", \"{unit}\"]"))
         // .body(vec![name, category])
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn add_full_item(name: &str, category: &str, stock: i64, desired_stock: i64, track_generally: bool) -> Result<Item, Error> {
-    Request::post(&format!("{BASE_URL}/dev/item/{name}"))
-        .body(format!("[\"{category}\", \"{track_generally}\", \"{stock}\", \"{desired_stock}\"]"))
+pub async fn add_full_item(name: &str, category: &str, stock: f64, desired_stock: f64, unit: &str, track_generally: bool) -> Result<Item, ApiError> {
+    let response = Request::post(&format!("{}/dev/item/{name}", base_url()))
+        .body(format!("[\"{category}\", \"{track_generally}\", \"{stock}\", \"{desired_stock}\", \"{unit}\"]"))
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn change_item(id: &str, item: Item) -> Result<Item, Error> {
-    Request::patch(&format!("{BASE_URL}/item/update/{id}"))
+pub async fn change_item(id: &str, item: Item) -> Result<Item, ApiError> {
+    let response = Request::patch(&format!("{}/item/update/{id}", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
         .body(item.to_json())
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn change_items(items: Vec<Item>) -> Result<AffectedRows, Error> {
-    Request::patch(&format!("{BASE_URL}/items/update"))
+pub async fn change_items(items: Vec<Item>) -> Result<BatchResult, ApiError> {
+    let response = Request::patch(&format!("{}/items/update", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
         .body(serde_json::to_string(&items).unwrap())
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
+}
+
+pub async fn set_packages(id: &str, packages: Vec<Package>) -> Result<Item, ApiError> {
+    let response = Request::patch(&format!("{}/item/{id}/packages", base_url()))
+        .body(serde_json::to_string(&packages).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
 }
 
-pub async fn delete_item(id: &str) -> Result<AffectedRows, Error> {
-    Request::delete(&format!("{BASE_URL}/item/{id}"))
+pub async fn set_item_store(id: &str, store: ItemStoreInput) -> Result<Item, ApiError> {
+    let response = Request::patch(&format!("{}/item/{id}/store", base_url()))
+        .body(serde_json::to_string(&store).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// The item a UPC/EAN belongs to, [`ApiError::NotFound`] when no item has it yet.
+pub async fn fetch_item_by_barcode(code: &str) -> Result<Item, ApiError> {
+    let response = Request::get(&format!("{}/item/by-barcode/{code}", base_url()))
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn restock_items(items: Vec<RestockItem>) -> Result<AffectedRows, Error> {
-    Request::patch(&format!("{BASE_URL}/items/restock"))
-        .body(restock_items_to_json(items))
+pub async fn add_barcode(id: &str, code: &str) -> Result<Item, ApiError> {
+    let response = Request::post(&format!("{}/item/{id}/barcodes/{code}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn remove_barcode(id: &str, code: &str) -> Result<Item, ApiError> {
+    let response = Request::delete(&format!("{}/item/{id}/barcodes/{code}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_item(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/item/{id}", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn restock_items(items: Vec<RestockItem>) -> Result<BatchResult, ApiError> {
+    let response = Request::patch(&format!("{}/items/restock", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
+        .body(serde_json::to_string(&items).unwrap())
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-pub async fn consume_items(items: Vec<RestockItem>) -> Result<AffectedRows, Error> {
-    Request::patch(&format!("{BASE_URL}/items/consume"))
-        .body(restock_items_to_json(items))
+pub async fn consume_items(items: Vec<RestockItem>) -> Result<BatchResult, ApiError> {
+    let response = Request::patch(&format!("{}/items/consume", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
+        .body(serde_json::to_string(&items).unwrap())
         .header("Content-Type", "application/json")
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
 // pub async fn test_request() -> Result<String, Error> {
-//     Request::get(&format!("{BASE_URL}/items"))
+//     Request::get(&format!("{}/items", base_url()))
 //         .send()
 //         .await
 //         .unwrap()
@@ -92,22 +222,334 @@ pub async fn consume_items(items: Vec<RestockItem>) -> Result<AffectedRows, Erro
 //         .await
 // }
 
-pub async fn fetch_logs() -> Result<(String, String), Error> {
-    Request::get(&format!("{BASE_URL}/logs"))
+pub async fn fetch_units() -> Result<Vec<Unit>, ApiError> {
+    let response = Request::get(&format!("{}/units", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Lots whose best-by date is at most `days` away, including those already past it.
+pub async fn fetch_expiring(days: u32) -> Result<Vec<Lot>, ApiError> {
+    let response = Request::get(&format!("{}/lots/expiring?days={}", base_url(), days))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn fetch_categories() -> Result<Vec<Category>, ApiError> {
+    let response = Request::get(&format!("{}/categories", base_url()))
         .send()
-        .await?
-        .json()
-        .await
+        .await;
+    decode(response).await
 }
 
-fn restock_items_to_json(items: Vec<RestockItem>) -> String {
-    let mut result = "[".to_owned();
-    for item in items {
-        result += "{";
-        result += &format!("\"id\": \"{}\",\"count\": {}", item.id, item.count);
-        result += "},";
+pub async fn create_category(category: CategoryInput) -> Result<Category, ApiError> {
+    let response = Request::post(&format!("{}/category", base_url()))
+        .body(serde_json::to_string(&category).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn update_category(id: &str, category: CategoryInput) -> Result<Category, ApiError> {
+    let response = Request::patch(&format!("{}/category/{id}", base_url()))
+        .body(serde_json::to_string(&category).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn merge_category(id: &str, into: &str) -> Result<Category, ApiError> {
+    let response = Request::patch(&format!("{}/category/{id}/merge/{into}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn reorder_categories(ids: Vec<String>) -> Result<Vec<Category>, ApiError> {
+    let response = Request::patch(&format!("{}/categories/order", base_url()))
+        .body(serde_json::to_string(&ids).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_category(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/category/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn fetch_recipes() -> Result<Vec<Recipe>, ApiError> {
+    let response = Request::get(&format!("{}/recipes", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn create_recipe(recipe: RecipeInput) -> Result<Recipe, ApiError> {
+    let response = Request::post(&format!("{}/recipe", base_url()))
+        .body(serde_json::to_string(&recipe).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn update_recipe(id: &str, recipe: RecipeInput) -> Result<Recipe, ApiError> {
+    let response = Request::patch(&format!("{}/recipe/{id}", base_url()))
+        .body(serde_json::to_string(&recipe).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_recipe(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/recipe/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Recipes that can be cooked from stock, and those short of at most `missing` ingredients.
+pub async fn fetch_cookable(missing: u32) -> Result<Cookable, ApiError> {
+    let response = Request::get(&format!("{}/recipes/cookable?missing={}", base_url(), missing))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Consumes every ingredient of a recipe, scaled to `servings`.
+pub async fn cook_recipe(id: &str, servings: f64) -> Result<BatchResult, ApiError> {
+    let response = Request::patch(&format!("{}/recipe/{id}/cook?servings={servings}", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Meals planned from `from` to `to`, both included.
+pub async fn fetch_meal_plan(from: NaiveDate, to: NaiveDate) -> Result<Vec<PlannedMeal>, ApiError> {
+    let response = Request::get(&format!("{}/meal-plan?from={}&to={}", base_url(), from, to))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn plan_meal(meal: PlannedMealInput) -> Result<PlannedMeal, ApiError> {
+    let response = Request::post(&format!("{}/meal-plan", base_url()))
+        .body(serde_json::to_string(&meal).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn change_planned_servings(id: &str, servings: f64) -> Result<PlannedMeal, ApiError> {
+    let response = Request::patch(&format!("{}/meal-plan/{id}", base_url()))
+        .body(serde_json::json!({ "servings": servings }).to_string())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_planned_meal(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/meal-plan/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn fetch_stores() -> Result<Vec<Store>, ApiError> {
+    let response = Request::get(&format!("{}/stores", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn create_store(store: StoreInput) -> Result<Store, ApiError> {
+    let response = Request::post(&format!("{}/store", base_url()))
+        .body(serde_json::to_string(&store).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn update_store(id: &str, store: StoreInput) -> Result<Store, ApiError> {
+    let response = Request::patch(&format!("{}/store/{id}", base_url()))
+        .body(serde_json::to_string(&store).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_store(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/store/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Everything to buy, counting the meals planned for the week starting `from`.
+pub async fn fetch_shopping_list(from: NaiveDate) -> Result<ShoppingList, ApiError> {
+    let response = Request::get(&format!("{}/shopping-list?from={}", base_url(), from))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn add_extra(extra: ExtraInput) -> Result<Extra, ApiError> {
+    let response = Request::post(&format!("{}/shopping-list/extras", base_url()))
+        .body(serde_json::to_string(&extra).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_extra(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/shopping-list/extras/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// The trip in progress, if there is one.
+pub async fn fetch_current_trip() -> Result<Option<Trip>, ApiError> {
+    let response = Request::get(&format!("{}/trip", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Finished trips, latest first.
+pub async fn fetch_trips() -> Result<Vec<Trip>, ApiError> {
+    let response = Request::get(&format!("{}/trips", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Starts a trip from the shopping list, with a store only taking what's bought there.
+pub async fn start_trip(store: Option<String>) -> Result<Trip, ApiError> {
+    let response = Request::post(&format!("{}/trip", base_url()))
+        .body(serde_json::json!({ "store": store }).to_string())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn check_trip_line(trip: &str, line: &str, change: TripLineChange) -> Result<TripLine, ApiError> {
+    let response = Request::patch(&format!("{}/trip/{trip}/line/{line}", base_url()))
+        .body(serde_json::to_string(&change).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Restocks everything checked off and records the trip.
+pub async fn finish_trip(id: &str) -> Result<FinishedTrip, ApiError> {
+    let response = Request::patch(&format!("{}/trip/{id}/finish", base_url()))
+        .header(SOURCE_HEADER, SOURCE)
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn abandon_trip(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/trip/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Every price paid for an item, latest first.
+pub async fn fetch_prices(item: &str) -> Result<Vec<Purchase>, ApiError> {
+    let response = Request::get(&format!("{}/prices?item={}", base_url(), item))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// The stores an item has been bought at, cheapest first by unit price.
+pub async fn fetch_price_comparison(item: &str) -> Result<PriceComparison, ApiError> {
+    let response = Request::get(&format!("{}/prices/compare?item={}", base_url(), item))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// Spending totalled by `week`, `month`, `category` or `store`.
+pub async fn fetch_spending(by: &str, from: NaiveDate, to: NaiveDate) -> Result<SpendingReport, ApiError> {
+    let response = Request::get(&format!("{}/reports/spending?by={}&from={}&to={}", base_url(), by, from, to))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn fetch_budgets() -> Result<Vec<Budget>, ApiError> {
+    let response = Request::get(&format!("{}/budgets", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+/// How much of each budget has gone this month.
+pub async fn fetch_budget_status() -> Result<BudgetStatus, ApiError> {
+    let response = Request::get(&format!("{}/budgets/status", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn set_budget(budget: BudgetInput) -> Result<Budget, ApiError> {
+    let response = Request::put(&format!("{}/budget", base_url()))
+        .body(serde_json::to_string(&budget).unwrap())
+        .header("Content-Type", "application/json")
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn delete_budget(id: &str) -> Result<AffectedRows, ApiError> {
+    let response = Request::delete(&format!("{}/budget/{id}", base_url()))
+        .send()
+        .await;
+    decode(response).await
+}
+
+pub async fn fetch_history(filter: HistoryFilter) -> Result<HistoryPage, ApiError> {
+    let params = UrlSearchParams::new().map_err(|_| ApiError::Decode("Could not build the history query".into()))?;
+    if let Some(from) = filter.from {
+        params.append("from", &from.to_rfc3339());
+    }
+    if let Some(to) = filter.to {
+        params.append("to", &to.to_rfc3339());
+    }
+    if let Some(item) = filter.item.as_deref() {
+        params.append("item", item);
     }
-    let _ = result.pop();
-    result += "]";
-    result
-}
\ No newline at end of file
+    if let Some(category) = filter.category.as_deref() {
+        params.append("category", category);
+    }
+    if let Some(operation) = filter.operation.as_deref() {
+        params.append("operation", operation);
+    }
+    params.append("page", &filter.page.max(1).to_string());
+
+    let response = Request::get(&format!("{}/history?{}", base_url(), String::from(params.to_string())))
+        .send()
+        .await;
+    decode(response).await
+}
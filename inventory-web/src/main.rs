@@ -1,6 +1,7 @@
 use std::rc::Rc;
 use yew::prelude::*;
 
+mod api_config;
 mod components;
 mod models;
 mod state;
@@ -20,9 +21,15 @@ pub enum Tabs {
     DinnerList,
     GroceryBag,
     ShoppingList,
+    ShoppingTrip,
+    Recipes,
+    MealPlan,
+    Expiring,
+    Reports,
     Logs,
     Dev,
-    ReInventory
+    ReInventory,
+    Settings
 }
 
 #[derive(Default, PartialEq, Clone)]
@@ -40,13 +47,24 @@ fn app() -> Html {
         let inv_controller = inv_controller.clone();
         use_effect_with((), 
             move |_| {
-                inv_controller.init_items();
+                wasm_bindgen_futures::spawn_local(async move {
+                    api_config::discover().await;
+                    inv_controller.init_items();
+                });
                 || ()
             }
         )
     }
 
     let tab = use_state_eq(Tabs::default);
+    let dinner_load = use_state(DinnerListLoad::default);
+    let on_recipe_load = {
+        let (tab, dinner_load) = (tab.clone(), dinner_load.clone());
+        Callback::from(move |items| {
+            dinner_load.set(DinnerListLoad { serial: dinner_load.serial + 1, items });
+            tab.set(Tabs::DinnerList);
+        })
+    };
 
     // let on_create_task = {
     //     let inv_controller = inv_controller.clone();
@@ -55,15 +73,21 @@ fn app() -> Html {
     //     })
     // };
 
-    let (mut home_tab, mut dinner_tab, mut grocery_tab, mut shopping_tab, mut log_tab, mut dev_tab, mut reinv_tab) = (None,None,None,None,None,None,None);
+    let (mut home_tab, mut dinner_tab, mut grocery_tab, mut shopping_tab, mut trip_tab, mut recipes_tab, mut plan_tab, mut expiring_tab, mut reports_tab, mut log_tab, mut dev_tab, mut reinv_tab, mut settings_tab) = (None,None,None,None,None,None,None,None,None,None,None,None,None);
     match *tab {
     Tabs::Home => home_tab = Some("active"),
     Tabs::DinnerList => dinner_tab = Some("active"),
     Tabs::GroceryBag => grocery_tab = Some("active"),
     Tabs::ShoppingList => shopping_tab = Some("active"),
+    Tabs::ShoppingTrip => trip_tab = Some("active"),
+    Tabs::Recipes => recipes_tab = Some("active"),
+    Tabs::MealPlan => plan_tab = Some("active"),
+    Tabs::Expiring => expiring_tab = Some("active"),
+    Tabs::Reports => reports_tab = Some("active"),
     Tabs::Logs => log_tab = Some("active"),
     Tabs::Dev => dev_tab = Some("active"),
-    Tabs::ReInventory => reinv_tab = Some("active")
+    Tabs::ReInventory => reinv_tab = Some("active"),
+    Tabs::Settings => settings_tab = Some("active")
     }
 
     html!(<>
@@ -72,13 +96,18 @@ fn app() -> Html {
             <button class={classes!("tab_button", dinner_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::DinnerList)}}>{"Dinner List"}</button>
             <button class={classes!("tab_button", grocery_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::GroceryBag)}}>{"Grocery Bag"}</button>
             <button class={classes!("tab_button", shopping_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::ShoppingList)}}>{"Shopping List"}</button>
+            <button class={classes!("tab_button", trip_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::ShoppingTrip)}}>{"Shopping Trip"}</button>
+            <button class={classes!("tab_button", recipes_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::Recipes)}}>{"Recipes"}</button>
+            <button class={classes!("tab_button", plan_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::MealPlan)}}>{"Meal Plan"}</button>
+            <button class={classes!("tab_button", expiring_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::Expiring)}}>{"Expiring"}</button>
+            <button class={classes!("tab_button", reports_tab)} onclick={{let tab = tab.clone(); move |_| tab.set(Tabs::Reports)}}>{"Reports"}</button>
         </div>
         <ContextProvider<InvCont> context={inv_controller.clone()}>
         <div class={classes!("tab", home_tab)}>
             <ItemList />//controller={inv_controller.clone()} items={items.items.clone()}/>
         </div>
         <div class={classes!("tab", dinner_tab)}>
-            <Dinnerlist />
+            <Dinnerlist load={(*dinner_load).clone()} />
         </div>
         <div class={classes!("tab", grocery_tab)}>
             <GroceryBag />
@@ -86,6 +115,31 @@ fn app() -> Html {
         <div class={classes!("tab", shopping_tab)}>
             <ShoppingList />
         </div>
+        <div class={classes!("tab", trip_tab)}>
+            if trip_tab.is_some() {
+                <ShoppingTripTab />
+            }
+        </div>
+        <div class={classes!("tab", recipes_tab)}>
+            if recipes_tab.is_some() {
+                <RecipesTab on_load={on_recipe_load} />
+            }
+        </div>
+        <div class={classes!("tab", plan_tab)}>
+            if plan_tab.is_some() {
+                <MealPlanTab />
+            }
+        </div>
+        <div class={classes!("tab", expiring_tab)}>
+            if expiring_tab.is_some() {
+                <ExpiringTab />
+            }
+        </div>
+        <div class={classes!("tab", reports_tab)}>
+            if reports_tab.is_some() {
+                <ReportsTab />
+            }
+        </div>
         <div class={classes!("tab", log_tab)}>
             <LogTab />
         </div>
@@ -97,9 +151,13 @@ fn app() -> Html {
                 <ReInventory />
             }
         </div>
+        <div class={classes!("tab", settings_tab)}>
+            <Settings />
+        </div>
         </ContextProvider<InvCont>>
         <div class="logs">
             <button class={classes!("tab_button", log_tab)} onclick={{let tab=tab.clone(); move |_| tab.set(Tabs::Logs)}}>{"Logs"}</button>
+            <button class={classes!("tab_button", settings_tab)} onclick={{let tab=tab.clone(); move |_| tab.set(Tabs::Settings)}}>{"Settings"}</button>
         </div>
         <div class="dev">
             <button class={classes!("tab_button", dev_tab)} onclick={{let tab=tab.clone(); move |_| tab.set(Tabs::Dev)}}>{"Dev"}</button>
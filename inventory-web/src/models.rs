@@ -1,13 +1,47 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
+/// The unit an item is counted in when the api doesn't say.
+pub const DEFAULT_UNIT: &str = "count";
+
+fn default_unit() -> String {
+    DEFAULT_UNIT.to_owned()
+}
+
+/// A quantity as it's shown next to an item, e.g. `2.5 kg`. Plain counts are shown without a unit.
+pub fn format_quantity(quantity: f64, unit: &str) -> String {
+    match unit {
+        DEFAULT_UNIT => quantity.to_string(),
+        unit => format!("{} {}", quantity, unit),
+    }
+}
+
+/// What a unit price is per, e.g. `per kg`. Plain counts are priced `each`.
+pub fn per_unit(unit: &str) -> String {
+    match unit {
+        DEFAULT_UNIT => "each".to_owned(),
+        unit => format!("per {}", unit),
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub id: String,
     pub name: String,
     pub category: String,
-    pub stock: isize,
-    pub desired_stock: isize,
+    pub stock: f64,
+    pub desired_stock: f64,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub packages: Vec<Package>,
+    #[serde(default)]
+    pub barcodes: Vec<String>,
+    /// The store it's usually bought at, and the aisle there
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(default)]
+    pub aisle: Option<String>,
     pub track_general: bool,
     pub last_updated: DateTime<Utc>
 } impl Item {
@@ -19,21 +53,534 @@ pub struct Item {
             \"category\": \"{}\",
             \"stock\": {},
             \"desired_stock\": {},
+            \"unit\": \"{}\",
             \"track_general\": {},
             \"last_updated\": \"{}\"
-        ", self.id, self.name, self.category, self.stock, self.desired_stock, self.track_general, self.last_updated.to_string());
+        ", self.id, self.name, self.category, self.stock, self.desired_stock, self.unit, self.track_general, self.last_updated.to_string());
         result += "}";
         result
     }
 }
 
+/// What the api answers a delete with. Only whether it succeeded matters here, so the count of rows it reports is ignored.
 #[derive(Deserialize)]
-pub struct AffectedRows {
-    pub rows_affected: u64
+pub struct AffectedRows {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AppliedItem {
+    pub id: String,
+    pub name: String,
+    pub stock: f64
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RejectedEntry {
+    pub id: String,
+    pub reason: String
+}
+
+/// A consumption the api cut down to what was actually in stock.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClampedEntry {
+    pub id: String,
+    pub requested: f64,
+    pub consumed: f64
 }
 
-#[derive(Serialize, Deserialize)]
+/// A restock that cost notably more than the item usually does, both per one of `unit`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AboveUsual {
+    pub id: String,
+    pub unit: String,
+    pub unit_price: f64,
+    pub usual: f64
+}
+
+/// A budget a restock put over for the month. `category_name` is `None` for the overall budget.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OverBudget {
+    pub budget: String,
+    #[serde(default)]
+    pub category_name: Option<String>,
+    pub amount: f64,
+    pub spent: f64
+}
+
+/// What happened to each entry of a batch restock, consume or update.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchResult {
+    pub rows_affected: u64,
+    pub applied: Vec<AppliedItem>,
+    pub not_found: Vec<String>,
+    pub rejected: Vec<RejectedEntry>,
+    #[serde(default)]
+    pub clamped: Vec<ClampedEntry>,
+    #[serde(default)]
+    pub above_usual: Vec<AboveUsual>,
+    #[serde(default)]
+    pub over_budget: Vec<OverBudget>
+} impl BatchResult {
+    pub fn is_complete(&self) -> bool {
+        self.not_found.is_empty() && self.rejected.is_empty()
+    }
+}
+
+/// A category record from the api. Items still name their category, this adds how it is shown.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+    #[serde(default)]
+    pub colour: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub parent: Option<String>
+}
+
+/// A meal and what goes into it, for `servings` people.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    pub servings: f64,
+    pub ingredients: Vec<Ingredient>
+}
+
+/// `quantity` of `item` per recipe, in the item's own unit unless `unit` says otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ingredient {
+    pub item: String,
+    pub quantity: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>
+}
+
+/// An ingredient there isn't enough of, or that can't be used at all, in the unit its item is kept in.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Missing {
+    pub item: String,
+    pub name: String,
+    pub needed: f64,
+    pub stock: f64,
+    pub unit: String,
+    /// Why it can't be had however much there is, e.g. its unit can't be converted to the item's
+    #[serde(default)]
+    pub reason: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlmostCookable {
+    pub recipe: Recipe,
+    pub missing: Vec<Missing>
+}
+
+/// Which recipes the stock there is now covers, and which it nearly does.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Cookable {
+    pub ready: Vec<Recipe>,
+    pub almost: Vec<AlmostCookable>
+}
+
+/// Body for creating or changing a recipe.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RecipeInput {
+    pub name: String,
+    pub servings: f64,
+    pub ingredients: Vec<Ingredient>
+} impl From<&Recipe> for RecipeInput {
+    fn from(recipe: &Recipe) -> Self {
+        Self {
+            name: recipe.name.clone(),
+            servings: recipe.servings,
+            ingredients: recipe.ingredients.clone()
+        }
+    }
+}
+
+/// A recipe planned for a day.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PlannedMeal {
+    pub id: String,
+    pub date: NaiveDate,
+    pub recipe: String,
+    pub recipe_name: String,
+    pub servings: f64
+}
+
+/// Body for planning a meal. Without `servings` the recipe is planned for as many as it serves.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedMealInput {
+    pub date: NaiveDate,
+    pub recipe: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servings: Option<f64>
+}
+
+/// One planned meal's share of what an item is needed for.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MealNeed {
+    pub meal: String,
+    pub date: NaiveDate,
+    pub recipe_name: String,
+    pub quantity: f64
+}
+
+/// Something put on the shopping list by hand, either more of an item or something that isn't tracked.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Extra {
+    pub id: String,
+    #[serde(default)]
+    pub item: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub unit: Option<String>
+}
+
+/// Body for adding an extra. Either `item` or `name` is needed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExtraInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>
+}
+
+/// A line of the shopping list: how much to buy, and the meals and extras it's for.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShoppingEntry {
+    #[serde(default)]
+    pub item: Option<String>,
+    pub name: String,
+    pub quantity: Option<f64>,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub stock: Option<f64>,
+    #[serde(default)]
+    pub desired_stock: Option<f64>,
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(default)]
+    pub aisle: Option<String>,
+    pub meals: Vec<MealNeed>,
+    pub extras: Vec<Extra>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ShoppingCategory {
+    pub name: String,
+    pub entries: Vec<ShoppingEntry>
+}
+
+/// What to buy, by category in the order they're shown.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ShoppingList {
+    pub categories: Vec<ShoppingCategory>
+}
+
+/// A shop, with its aisles in the order they're walked.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Store {
+    pub id: String,
+    pub name: String,
+    pub aisles: Vec<String>
+} impl Store {
+    /// Where an aisle comes in the walk through the store. Aisles the store doesn't list go last.
+    pub fn aisle_position(&self, aisle: Option<&str>) -> usize {
+        aisle.and_then(|aisle| self.aisles.iter().position(|a| a == aisle)).unwrap_or(self.aisles.len())
+    }
+}
+
+/// Body for creating or changing a store.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreInput {
+    pub name: String,
+    pub aisles: Vec<String>
+}
+
+/// Body for setting where an item is bought. No store takes it off every store.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemStoreInput {
+    pub store: Option<String>,
+    pub aisle: Option<String>
+}
+
+/// A line of a shopping trip, checked off once it's in the basket. `bought` is set when what was
+/// picked up isn't `quantity`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TripLine {
+    pub id: String,
+    #[serde(default)]
+    pub item: Option<String>,
+    pub name: String,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub bought: Option<f64>,
+    #[serde(default)]
+    pub price: Option<f64>,
+    pub checked: bool,
+    #[serde(default)]
+    pub aisle: Option<String>
+}
+
+/// A trip to the shops, in progress until it's `finished`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Trip {
+    pub id: String,
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(default)]
+    pub store_name: Option<String>,
+    pub started: DateTime<Utc>,
+    #[serde(default)]
+    pub finished: Option<DateTime<Utc>>,
+    pub lines: Vec<TripLine>
+}
+
+/// Response of finishing a trip: the trip as recorded and what restocking it did.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FinishedTrip {
+    pub trip: Trip,
+    pub result: BatchResult
+}
+
+/// Body for checking a line of a trip off, or back on. No `bought` means the line's quantity was bought.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TripLineChange {
+    pub checked: bool,
+    pub bought: Option<f64>,
+    /// What was paid for all of it
+    pub price: Option<f64>
+}
+
+/// What was paid for some of an item when it was restocked, with `unit_price` per one of `price_unit`,
+/// the item's unit now when `unit` converts into it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Purchase {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    pub category: String,
+    pub quantity: f64,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    pub price: f64,
+    pub unit_price: f64,
+    #[serde(default = "default_unit")]
+    pub price_unit: String,
+    #[serde(default)]
+    pub store: Option<String>,
+    #[serde(default)]
+    pub store_name: Option<String>,
+    pub time: DateTime<Utc>
+}
+
+/// What a store has charged for an item, per one of the item's unit.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StorePrice {
+    #[serde(default)]
+    pub store: Option<String>,
+    pub store_name: String,
+    pub latest: f64,
+    pub latest_time: DateTime<Utc>,
+    pub average: f64,
+    pub purchases: usize
+}
+
+/// Response of `GET /prices/compare`, with stores cheapest first by what they charged last.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PriceComparison {
+    pub item: String,
+    pub name: String,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    #[serde(default)]
+    pub usual: Option<f64>,
+    pub stores: Vec<StorePrice>
+}
+
+/// What was spent in a week (keyed by its Monday), a month (`YYYY-MM`), a category or a store.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SpendRow {
+    pub key: String,
+    pub total: f64,
+    pub purchases: usize
+}
+
+/// Response of `GET /reports/spending`. `by` is one of `week`, `month`, `category` or `store`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SpendingReport {
+    pub by: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total: f64,
+    pub rows: Vec<SpendRow>
+}
+
+/// A monthly spending limit on a category, or on everything when there's no category.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Budget {
+    pub id: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub category_name: Option<String>,
+    pub amount: f64
+}
+
+/// Body for setting a budget, which changes the amount when the category already has one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetInput {
+    pub category: Option<String>,
+    pub amount: f64
+}
+
+/// How much of a budget has gone this month. `left` is negative once it's overspent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BudgetLine {
+    pub budget: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub category_name: Option<String>,
+    pub amount: f64,
+    pub spent: f64,
+    pub left: f64,
+    pub over: bool
+}
+
+/// Response of `GET /budgets/status`, the overall budget first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BudgetStatus {
+    /// As `YYYY-MM`
+    pub month: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub lines: Vec<BudgetLine>
+}
+
+/// Body for creating or changing a category.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CategoryInput {
+    pub name: String,
+    pub colour: Option<String>,
+    pub icon: Option<String>,
+    pub parent: Option<String>
+} impl From<&Category> for CategoryInput {
+    fn from(category: &Category) -> Self {
+        Self {
+            name: category.name.clone(),
+            colour: category.colour.clone(),
+            icon: category.icon.clone(),
+            parent: category.parent.clone()
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RestockItem {
     pub id: String,
-    pub count: i64
-}
\ No newline at end of file
+    pub count: f64,
+    /// Set when `count` isn't in the item's own unit, the api converts it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Set when `count` is a number of the item's packages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// When restocked stock is best used by, as `YYYY-MM-DD`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_by: Option<String>,
+    /// What was paid for the whole entry, recorded by the api as a purchase
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    /// What was paid for each one of `count`, instead of `price`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_price: Option<f64>,
+    /// The store it was bought at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>
+}
+
+/// A package an item is bought in, e.g. a case of 12. `size` is in the item's own unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub size: f64
+}
+
+/// A unit from the api's conversion table. `factor` is how many of the dimension's base unit it makes.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Unit {
+    pub name: String,
+    pub dimension: String,
+    pub factor: f64
+}
+
+/// Stock of an item that arrived together at one location, from `GET /lots`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Lot {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    pub location: String,
+    pub location_name: String,
+    pub stock: f64,
+    #[serde(default)]
+    pub best_by: Option<DateTime<Utc>>,
+    pub received: DateTime<Utc>
+}
+
+/// A single change of stock from the api's ledger.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StockEvent {
+    pub id: String,
+    pub item: String,
+    pub name: String,
+    pub category: String,
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    pub delta: f64,
+    pub stock: f64,
+    pub operation: String,
+    pub source: String,
+    pub time: DateTime<Utc>
+}
+
+/// One page of `GET /history`, newest first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HistoryPage {
+    pub events: Vec<StockEvent>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize
+} impl HistoryPage {
+    pub fn page_count(&self) -> usize {
+        self.total.div_ceil(self.per_page.max(1)).max(1)
+    }
+}
+
+/// Filters for `GET /history`. Empty fields are left out of the request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub item: Option<String>,
+    pub category: Option<String>,
+    pub operation: Option<String>,
+    pub page: usize
+}
@@ -2,22 +2,93 @@ use std::collections::BTreeMap;
 
 use yew::{AttrValue, Reducible};
 
-use crate::models::Item;
+use crate::models::{Budget, Category, Ingredient, Item, Recipe, ShoppingList, Store, Unit};
 
 pub enum ItemAction {
     Set(Vec<Item>),
-    Add(Item)
+    Add(Item),
+    SetCategories(Vec<Category>),
+    SetUnits(Vec<Unit>),
+    SetRecipes(Vec<Recipe>),
+    SetShopping(ShoppingList),
+    SetStores(Vec<Store>),
+    SetBudgets(Vec<Budget>)
 }
 
 #[derive(PartialEq, Clone)]
 pub struct ItemsState {
     pub items: Vec<Item>,
-    pub inventory: Inventory
+    pub inventory: Inventory,
+    /// Category records in display order
+    pub categories: Vec<Category>,
+    /// The api's unit conversion table
+    pub units: Vec<Unit>,
+    /// Recipes by name
+    pub recipes: Vec<Recipe>,
+    /// The api's shopping list, planned meals and extras included
+    pub shopping: ShoppingList,
+    /// Stores by name
+    pub stores: Vec<Store>,
+    /// Monthly budgets, the overall one first
+    pub budgets: Vec<Budget>
 }
 
 impl Default for ItemsState {
     fn default() -> Self {
-        Self { items: vec![], inventory: Inventory::default() }
+        Self { items: vec![], inventory: Inventory::default(), categories: vec![], units: vec![], recipes: vec![], shopping: ShoppingList::default(), stores: vec![], budgets: vec![] }
+    }
+}
+
+impl ItemsState {
+    pub fn category(&self, name: &str) -> Option<&Category> {
+        self.categories.iter().find(|category| category.name == name)
+    }
+
+    pub fn unit(&self, name: &str) -> Option<&Unit> {
+        self.units.iter().find(|unit| unit.name == name)
+    }
+
+    /// Units a quantity of `unit` can be entered in, starting with `unit` itself.
+    pub fn compatible_units(&self, unit: &str) -> Vec<&Unit> {
+        let Some(own) = self.unit(unit) else { return vec![] };
+        let mut compatible = vec![own];
+        compatible.extend(self.units.iter().filter(|other| other.dimension == own.dimension && other.name != own.name));
+        compatible
+    }
+
+    /// Converts `quantity` of `from` into `to`, the same way the api will.
+    pub fn convert(&self, quantity: f64, from: &str, to: &str) -> Option<f64> {
+        let (from, to) = (self.unit(from)?, self.unit(to)?);
+        (from.dimension == to.dimension).then(|| (quantity * from.factor / to.factor * 1000.0).round() / 1000.0)
+    }
+
+    /// What an ingredient of `recipe` comes to when it's made for `servings`, in its item's own unit.
+    /// Quantities in a unit that can't be converted are taken as they are.
+    pub fn ingredient_need(&self, recipe: &Recipe, ingredient: &Ingredient, servings: f64) -> Option<(&Item, f64)> {
+        let item = self.inventory.item_id_map.get(&AttrValue::from(ingredient.item.clone()))?;
+        let quantity = ingredient.quantity * servings / recipe.servings;
+        let quantity = match &ingredient.unit {
+            Some(unit) if *unit != item.unit => self.convert(quantity, unit, &item.unit).unwrap_or(quantity),
+            _ => (quantity * 1000.0).round() / 1000.0
+        };
+        Some((item, quantity))
+    }
+
+    pub fn store(&self, id: &str) -> Option<&Store> {
+        self.stores.iter().find(|store| store.id == id)
+    }
+
+    /// The item a scanned barcode belongs to, going by the items as last loaded.
+    pub fn item_by_barcode(&self, code: &str) -> Option<&Item> {
+        self.items.iter().find(|item| item.barcodes.iter().any(|barcode| barcode == code))
+    }
+
+    /// Puts grouped items in the order of the category records. Categories without a record,
+    /// e.g. from an item added since they were last loaded, go last in alphabetical order.
+    pub fn ordered<T>(&self, category_map: BTreeMap<String, T>) -> Vec<(String, T)> {
+        let mut ordered: Vec<(String, T)> = category_map.into_iter().collect();
+        ordered.sort_by_key(|(name, _)| self.categories.iter().position(|category| &category.name == name).unwrap_or(usize::MAX));
+        ordered
     }
 }
 
@@ -27,13 +98,25 @@ impl Reducible for ItemsState {
     fn reduce(self: std::rc::Rc<Self>, action: Self::Action) -> std::rc::Rc<Self> {
         let mut next_items = self.items.clone();
         let mut inventory = self.inventory.clone();
+        let mut categories = self.categories.clone();
+        let mut units = self.units.clone();
+        let mut recipes = self.recipes.clone();
+        let mut shopping = self.shopping.clone();
+        let mut stores = self.stores.clone();
+        let mut budgets = self.budgets.clone();
 
         match action {
             ItemAction::Set(items) => {next_items = items; inventory.make(next_items.clone())},
             ItemAction::Add(item) => {next_items.push(item); inventory.make(next_items.clone())},
+            ItemAction::SetCategories(new_categories) => categories = new_categories,
+            ItemAction::SetUnits(new_units) => units = new_units,
+            ItemAction::SetRecipes(new_recipes) => recipes = new_recipes,
+            ItemAction::SetShopping(new_shopping) => shopping = new_shopping,
+            ItemAction::SetStores(new_stores) => stores = new_stores,
+            ItemAction::SetBudgets(new_budgets) => budgets = new_budgets,
         }
 
-        Self { items: next_items, inventory }.into()
+        Self { items: next_items, inventory, categories, units, recipes, shopping, stores, budgets }.into()
     }
 }
 